@@ -3,7 +3,6 @@
 #![feature(panic_info_message)]
 
 mod lang_items;
-mod sbi;
 
 #[macro_use]
 mod console;
@@ -1,6 +1,7 @@
 use core::panic::PanicInfo;
 
-use crate::{println, sbi::shutdown};
+use crate::println;
+use sbi::shutdown;
 
 #[panic_handler]
 fn panic(info: &PanicInfo) -> ! {
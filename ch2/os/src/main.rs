@@ -6,7 +6,6 @@
 pub mod console;
 
 mod lang_items;
-mod sbi;
 mod up;
 
 pub mod batch;
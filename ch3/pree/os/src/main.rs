@@ -6,7 +6,6 @@
 pub mod console;
 mod config;
 mod lang_items;
-mod sbi;
 mod up;
 mod timer;
 
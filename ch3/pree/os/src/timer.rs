@@ -1,4 +1,5 @@
-use crate::{config::CLOCK_FREQ, sbi::set_timer};
+use crate::config::CLOCK_FREQ;
+use sbi::set_timer;
 
 use riscv::register::time;
 
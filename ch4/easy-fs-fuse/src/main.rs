@@ -0,0 +1,274 @@
+// 把用户程序打包成 easy-fs 磁盘镜像
+//
+// 使用方法：
+//
+// ```
+// easy-fs-fuse -s <源代码目录> -t <可执行文件目录> -o <镜像文件> [-b <总块数>]
+// easy-fs-fuse -i <镜像文件> -c <文件名>
+// ```
+//
+// 跟 os/build.rs 一样，根据源代码目录（../user/src/bin）里的文件名得到应用程序的名称，
+// 再从可执行文件目录（../user/target/riscv64gc-unknown-none-elf/release/）读取对应的 ELF 文件，
+// 依次写入镜像的根目录。
+//
+// 不指定总块数时，镜像的大小为存放所有文件所需的块数再加上 FREE_BLOCKS 个空闲块，
+// 供用户程序创建新文件。
+//
+// 第二种用法把镜像里的一个文件输出到标准输出，用于查看用户程序写入磁盘的内容。
+
+use std::{
+    env,
+    fs::{read_dir, File, OpenOptions},
+    io::{self, Read, Seek, SeekFrom, Write},
+    process,
+    sync::{Arc, Mutex},
+};
+
+use easy_fs::{BlockDevice, DiskInode, EasyFileSystem, Inode, BLOCK_SIZE};
+
+// 1 个块的 inode 位图，最多 4096 个文件
+const INODE_BITMAP_BLOCKS: u32 = 1;
+const INODE_AREA_BLOCKS: u32 = INODE_BITMAP_BLOCKS * 4096 / 4;
+
+const FREE_BLOCKS: u32 = 2048;
+
+/// 以宿主机上的文件作为块设备
+struct BlockFile {
+    file: Mutex<File>,
+    num_blocks: usize,
+}
+
+impl BlockDevice for BlockFile {
+    fn read_block(&self, block_id: usize, buf: &mut [u8]) {
+        let mut file = self.file.lock().unwrap();
+        file.seek(SeekFrom::Start((block_id * BLOCK_SIZE) as u64))
+            .expect("error when seeking");
+        file.read_exact(buf).expect("error when reading block");
+    }
+
+    fn write_block(&self, block_id: usize, buf: &[u8]) {
+        let mut file = self.file.lock().unwrap();
+        file.seek(SeekFrom::Start((block_id * BLOCK_SIZE) as u64))
+            .expect("error when seeking");
+        file.write_all(buf).expect("error when writing block");
+    }
+
+    fn num_blocks(&self) -> usize {
+        self.num_blocks
+    }
+}
+
+struct Args {
+    source: String,
+    target: String,
+    output: String,
+    blocks: Option<u32>,
+}
+
+enum Command {
+    Pack(Args),
+    Cat { image: String, name: String },
+}
+
+fn usage() -> ! {
+    eprintln!("usage: easy-fs-fuse -s <source dir> -t <target dir> -o <image> [-b <blocks>]");
+    eprintln!("       easy-fs-fuse -i <image> -c <file>");
+    process::exit(1);
+}
+
+fn parse_args() -> Command {
+    let mut source = None;
+    let mut target = None;
+    let mut output = None;
+    let mut blocks = None;
+    let mut image = None;
+    let mut name = None;
+
+    let mut args = env::args().skip(1);
+    while let Some(flag) = args.next() {
+        let value = args.next().unwrap_or_else(|| usage());
+        match flag.as_str() {
+            "-s" => source = Some(value),
+            "-t" => target = Some(value),
+            "-o" => output = Some(value),
+            "-b" => blocks = Some(value.parse().unwrap_or_else(|_| usage())),
+            "-i" => image = Some(value),
+            "-c" => name = Some(value),
+            _ => usage(),
+        }
+    }
+
+    match (source, target, output, image, name) {
+        (Some(source), Some(target), Some(output), None, None) => Command::Pack(Args {
+            source,
+            target,
+            output,
+            blocks,
+        }),
+        (None, None, None, Some(image), Some(name)) if blocks.is_none() => {
+            Command::Cat { image, name }
+        }
+        _ => usage(),
+    }
+}
+
+/// 源代码目录里的应用程序名称（去除扩展名），按名称排序
+fn app_names(source: &str) -> Vec<String> {
+    let mut apps: Vec<String> = read_dir(source)
+        .unwrap_or_else(|e| panic!("cannot read {}: {}", source, e))
+        .map(|dir_entry| {
+            let mut name_with_ext = dir_entry.unwrap().file_name().into_string().unwrap();
+            name_with_ext.drain(name_with_ext.find('.').unwrap()..name_with_ext.len());
+            name_with_ext
+        })
+        .collect();
+    apps.sort();
+    apps
+}
+
+/// 打开已有的镜像 `image`，返回根目录
+fn open_image(image: &str) -> Inode {
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(image)
+        .unwrap_or_else(|e| panic!("cannot open {}: {}", image, e));
+    let num_blocks = file.metadata().unwrap().len() as usize / BLOCK_SIZE;
+
+    let block_file: Arc<dyn BlockDevice> = Arc::new(BlockFile {
+        file: Mutex::new(file),
+        num_blocks,
+    });
+    let efs = EasyFileSystem::open(block_file)
+        .unwrap_or_else(|| panic!("{} is not an easy-fs image", image));
+    EasyFileSystem::root_inode(&efs)
+}
+
+/// 把镜像 `image` 里的文件 `name` 输出到标准输出
+fn cat(image: &str, name: &str) {
+    let inode = open_image(image)
+        .find(name)
+        .unwrap_or_else(|| panic!("{} not found in {}", name, image));
+
+    let mut data = vec![0u8; inode.size()];
+    inode.read_at(0, &mut data);
+    io::stdout().write_all(&data).unwrap();
+}
+
+/// 把 `apps` 依次写入新建的镜像 `output` 的根目录，返回镜像的总块数
+fn pack(apps: &[(String, Vec<u8>)], output: &str, blocks: Option<u32>) -> u32 {
+    // 超级块、inode 位图和 inode 区域、数据块位图、根目录和各个文件
+    let dir_size = (apps.len() * 32) as u32;
+    let data_blocks: u32 = DiskInode::total_blocks(dir_size)
+        + apps
+            .iter()
+            .map(|(_, data)| DiskInode::total_blocks(data.len() as u32))
+            .sum::<u32>();
+    let total_blocks = blocks.unwrap_or_else(|| {
+        let data_area_blocks = data_blocks + FREE_BLOCKS;
+        1 + INODE_BITMAP_BLOCKS
+            + INODE_AREA_BLOCKS
+            + data_area_blocks.div_ceil(4096)
+            + data_area_blocks
+    });
+
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(output)
+        .unwrap_or_else(|e| panic!("cannot create {}: {}", output, e));
+    file.set_len(total_blocks as u64 * BLOCK_SIZE as u64)
+        .unwrap();
+
+    let block_file: Arc<dyn BlockDevice> = Arc::new(BlockFile {
+        file: Mutex::new(file),
+        num_blocks: total_blocks as usize,
+    });
+    let efs = EasyFileSystem::create(block_file, total_blocks, INODE_BITMAP_BLOCKS);
+    let root_inode = EasyFileSystem::root_inode(&efs);
+
+    for (app, data) in apps {
+        let inode = root_inode
+            .create(app)
+            .unwrap_or_else(|| panic!("cannot create {} in the image", app));
+        assert_eq!(
+            inode.write_at(0, data),
+            data.len(),
+            "disk full when writing {}",
+            app
+        );
+        println!("{} ({} bytes)", app, data.len());
+    }
+
+    total_blocks
+}
+
+fn main() {
+    let args = match parse_args() {
+        Command::Pack(args) => args,
+        Command::Cat { image, name } => return cat(&image, &name),
+    };
+
+    let apps: Vec<(String, Vec<u8>)> = app_names(&args.source)
+        .into_iter()
+        .map(|app| {
+            let path = format!("{}/{}", args.target.trim_end_matches('/'), app);
+            let mut data = Vec::new();
+            File::open(&path)
+                .and_then(|mut f| f.read_to_end(&mut data))
+                .unwrap_or_else(|e| panic!("cannot read {}: {}", path, e));
+            (app, data)
+        })
+        .collect();
+
+    let total_blocks = pack(&apps, &args.output, args.blocks);
+    println!(
+        "{}: {} files, {} blocks",
+        args.output,
+        apps.len(),
+        total_blocks
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, process};
+
+    use super::*;
+
+    #[test]
+    fn pack_and_open() {
+        let image = env::temp_dir().join(format!("easy-fs-fuse-test-{}.img", process::id()));
+        let image = image.to_str().unwrap();
+        let apps: Vec<(String, Vec<u8>)> = (0..20)
+            .map(|i| {
+                let data = (0..i * 1000).map(|j| (i + j) as u8).collect();
+                (format!("{:02}app", i), data)
+            })
+            .collect();
+
+        let total_blocks = pack(&apps, image, None);
+        assert_eq!(
+            fs::metadata(image).unwrap().len(),
+            total_blocks as u64 * BLOCK_SIZE as u64
+        );
+
+        let root_inode = open_image(image);
+        let names: Vec<String> = apps.iter().map(|(app, _)| app.clone()).collect();
+        assert_eq!(root_inode.ls(), names);
+        for (app, data) in apps.iter() {
+            let inode = root_inode.find(app).unwrap();
+            let mut buf = vec![0u8; inode.size()];
+            assert_eq!(inode.read_at(0, &mut buf), data.len());
+            assert_eq!(&buf, data, "{} differs", app);
+        }
+        // 剩余 FREE_BLOCKS 个空闲块供用户程序创建新文件
+        let inode = root_inode.create("new").unwrap();
+        let data = vec![1u8; FREE_BLOCKS as usize / 2 * BLOCK_SIZE];
+        assert_eq!(inode.write_at(0, &data), data.len());
+
+        fs::remove_file(image).unwrap();
+    }
+}
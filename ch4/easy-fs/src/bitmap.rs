@@ -0,0 +1,95 @@
+// 位图：每一位表示一个 inode 或者数据块是否已经分配
+
+use alloc::sync::Arc;
+
+use crate::{
+    block_cache::get_block_cache,
+    block_dev::{BlockDevice, BLOCK_SIZE},
+};
+
+/// 每个块有 4096 位
+const BLOCK_BITS: usize = BLOCK_SIZE * 8;
+
+type BitmapBlock = [u64; BLOCK_SIZE / 8];
+
+pub struct Bitmap {
+    start_block_id: usize,
+    blocks: usize,
+    capacity: usize, // 实际可分配的数量，不超过 `blocks * BLOCK_BITS`
+}
+
+/// 位的编号 -> (块编号, 块内第几个 u64, u64 内第几位)
+fn decomposition(mut bit: usize) -> (usize, usize, usize) {
+    let block_pos = bit / BLOCK_BITS;
+    bit %= BLOCK_BITS;
+    (block_pos, bit / 64, bit % 64)
+}
+
+impl Bitmap {
+    pub fn new(start_block_id: usize, blocks: usize, capacity: usize) -> Self {
+        assert!(capacity <= blocks * BLOCK_BITS);
+        Self {
+            start_block_id,
+            blocks,
+            capacity,
+        }
+    }
+
+    /// 分配一位，返回它的编号，全部已分配时返回 None
+    pub fn alloc(&self, block_device: &Arc<dyn BlockDevice>) -> Option<usize> {
+        for block_pos in 0..self.blocks {
+            let pos = get_block_cache(self.start_block_id + block_pos, block_device)
+                .lock()
+                .modify(0, |bitmap_block: &mut BitmapBlock| {
+                    let (bits64_pos, bits64) = bitmap_block
+                        .iter_mut()
+                        .enumerate()
+                        .find(|(_, bits64)| **bits64 != u64::MAX)?;
+                    let inner_pos = bits64.trailing_ones() as usize;
+                    let pos = block_pos * BLOCK_BITS + bits64_pos * 64 + inner_pos;
+                    if pos >= self.capacity {
+                        return None;
+                    }
+                    *bits64 |= 1u64 << inner_pos;
+                    Some(pos)
+                });
+            if pos.is_some() {
+                return pos;
+            }
+        }
+        None
+    }
+
+    /// 回收编号为 `bit` 的位
+    pub fn dealloc(&self, block_device: &Arc<dyn BlockDevice>, bit: usize) {
+        let (block_pos, bits64_pos, inner_pos) = decomposition(bit);
+        get_block_cache(self.start_block_id + block_pos, block_device)
+            .lock()
+            .modify(0, |bitmap_block: &mut BitmapBlock| {
+                assert!(bitmap_block[bits64_pos] & (1u64 << inner_pos) != 0);
+                bitmap_block[bits64_pos] &= !(1u64 << inner_pos);
+            });
+    }
+
+    /// 可分配的最大数量
+    pub fn maximum(&self) -> usize {
+        self.capacity
+    }
+
+    /// 已经分配的数量，用于测试时检查文件系统的一致性
+    #[cfg(test)]
+    pub fn allocated(&self, block_device: &Arc<dyn BlockDevice>) -> usize {
+        (0..self.blocks)
+            .map(|block_pos| {
+                get_block_cache(self.start_block_id + block_pos, block_device)
+                    .lock()
+                    .read(0, |bitmap_block: &BitmapBlock| {
+                        bitmap_block
+                            .iter()
+                            .map(|bits64| bits64.count_ones() as usize)
+                            .sum::<usize>()
+                    })
+            })
+            .sum()
+    }
+}
@@ -0,0 +1,153 @@
+// 块缓存
+//
+// 文件系统对块的读写都先在内存里的缓存上进行，缓存被替换或者显式同步时才写回块设备。
+// 缓存的数量有上限，满了之后替换一个当前没有被使用的缓存。
+
+use alloc::{collections::VecDeque, sync::Arc, vec::Vec};
+
+use lazy_static::lazy_static;
+use spin::Mutex;
+
+use crate::block_dev::{BlockDevice, BLOCK_SIZE};
+
+const BLOCK_CACHE_SIZE: usize = 16;
+
+/// 按 8 bytes 对齐，这样可以直接把缓存里的数据当作磁盘上的结构体访问
+#[repr(C, align(8))]
+struct CacheData([u8; BLOCK_SIZE]);
+
+pub struct BlockCache {
+    cache: CacheData,
+    block_id: usize,
+    block_device: Arc<dyn BlockDevice>,
+    modified: bool,
+}
+
+impl BlockCache {
+    /// 从块设备读取一个块到缓存
+    fn new(block_id: usize, block_device: Arc<dyn BlockDevice>) -> Self {
+        let mut cache = CacheData([0; BLOCK_SIZE]);
+        block_device.read_block(block_id, &mut cache.0);
+        Self {
+            cache,
+            block_id,
+            block_device,
+            modified: false,
+        }
+    }
+
+    fn addr_of_offset(&self, offset: usize) -> usize {
+        &self.cache.0[offset] as *const _ as usize
+    }
+
+    pub fn get_ref<T: Sized>(&self, offset: usize) -> &T {
+        assert!(offset + core::mem::size_of::<T>() <= BLOCK_SIZE);
+        let addr = self.addr_of_offset(offset);
+        unsafe { &*(addr as *const T) }
+    }
+
+    pub fn get_mut<T: Sized>(&mut self, offset: usize) -> &mut T {
+        assert!(offset + core::mem::size_of::<T>() <= BLOCK_SIZE);
+        self.modified = true;
+        let addr = self.addr_of_offset(offset);
+        unsafe { &mut *(addr as *mut T) }
+    }
+
+    /// 把缓存里 `offset` 处的数据当作 `T` 读取
+    pub fn read<T, V>(&self, offset: usize, f: impl FnOnce(&T) -> V) -> V {
+        f(self.get_ref(offset))
+    }
+
+    /// 把缓存里 `offset` 处的数据当作 `T` 修改
+    pub fn modify<T, V>(&mut self, offset: usize, f: impl FnOnce(&mut T) -> V) -> V {
+        f(self.get_mut(offset))
+    }
+
+    /// 如果缓存被修改过，则写回块设备
+    pub fn sync(&mut self) {
+        if self.modified {
+            self.modified = false;
+            self.block_device.write_block(self.block_id, &self.cache.0);
+        }
+    }
+}
+
+impl Drop for BlockCache {
+    fn drop(&mut self) {
+        self.sync()
+    }
+}
+
+/// 用设备的地址区分不同的块设备
+fn device_id(block_device: &Arc<dyn BlockDevice>) -> usize {
+    Arc::as_ptr(block_device) as *const () as usize
+}
+
+// (设备, 块编号)
+type BlockCacheKey = (usize, usize);
+
+struct BlockCacheManager {
+    queue: VecDeque<(BlockCacheKey, Arc<Mutex<BlockCache>>)>,
+}
+
+impl BlockCacheManager {
+    fn new() -> Self {
+        Self {
+            queue: VecDeque::new(),
+        }
+    }
+
+    fn get_block_cache(
+        &mut self,
+        block_id: usize,
+        block_device: &Arc<dyn BlockDevice>,
+    ) -> Arc<Mutex<BlockCache>> {
+        let key = (device_id(block_device), block_id);
+        if let Some((_, cache)) = self.queue.iter().find(|(k, _)| *k == key) {
+            return cache.clone();
+        }
+
+        if self.queue.len() == BLOCK_CACHE_SIZE {
+            // 替换最早加入的、当前没有被使用的缓存
+            let idx = self
+                .queue
+                .iter()
+                .position(|(_, cache)| Arc::strong_count(cache) == 1)
+                .expect("run out of block cache");
+            self.queue.remove(idx);
+        }
+
+        let cache = Arc::new(Mutex::new(BlockCache::new(block_id, block_device.clone())));
+        self.queue.push_back((key, cache.clone()));
+        cache
+    }
+}
+
+lazy_static! {
+    static ref BLOCK_CACHE_MANAGER: Mutex<BlockCacheManager> = Mutex::new(BlockCacheManager::new());
+}
+
+/// 获取块设备 `block_device` 的第 `block_id` 块的缓存
+pub fn get_block_cache(
+    block_id: usize,
+    block_device: &Arc<dyn BlockDevice>,
+) -> Arc<Mutex<BlockCache>> {
+    BLOCK_CACHE_MANAGER
+        .lock()
+        .get_block_cache(block_id, block_device)
+}
+
+/// 把所有被修改过的缓存写回块设备
+pub fn block_cache_sync_all() {
+    // 持有某个缓存的锁的时候可能会调用 `get_block_cache`，
+    // 所以先释放管理器的锁，再逐个获取缓存的锁
+    let caches: Vec<Arc<Mutex<BlockCache>>> = BLOCK_CACHE_MANAGER
+        .lock()
+        .queue
+        .iter()
+        .map(|(_, cache)| cache.clone())
+        .collect();
+    for cache in caches {
+        cache.lock().sync();
+    }
+}
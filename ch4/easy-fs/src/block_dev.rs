@@ -0,0 +1,18 @@
+// 块设备的抽象
+//
+// 块设备以固定大小的块为单位读写，文件系统只依赖这个 trait，
+// 不关心数据实际保存在 virtio 磁盘、内存还是宿主机的文件里。
+
+/// 块的大小，跟 virtio-blk 的扇区大小一致
+pub const BLOCK_SIZE: usize = 512;
+
+pub trait BlockDevice: Send + Sync {
+    /// 读取第 `block_id` 块到 `buf`，`buf` 的长度必须是 `BLOCK_SIZE`
+    fn read_block(&self, block_id: usize, buf: &mut [u8]);
+
+    /// 把 `buf` 写入第 `block_id` 块，`buf` 的长度必须是 `BLOCK_SIZE`
+    fn write_block(&self, block_id: usize, buf: &[u8]);
+
+    /// 设备的总块数
+    fn num_blocks(&self) -> usize;
+}
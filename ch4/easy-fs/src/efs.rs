@@ -0,0 +1,155 @@
+// 文件系统：管理磁盘上的各个区域，分配和回收 inode 以及数据块
+
+use alloc::sync::Arc;
+
+use spin::Mutex;
+
+use crate::{
+    bitmap::Bitmap,
+    block_cache::{block_cache_sync_all, get_block_cache},
+    block_dev::{BlockDevice, BLOCK_SIZE},
+    layout::{DiskInode, DiskInodeType, SuperBlock},
+    vfs::Inode,
+};
+
+const INODE_SIZE: usize = core::mem::size_of::<DiskInode>();
+const INODES_PER_BLOCK: usize = BLOCK_SIZE / INODE_SIZE;
+
+type DataBlock = [u8; BLOCK_SIZE];
+
+pub struct EasyFileSystem {
+    pub block_device: Arc<dyn BlockDevice>,
+    pub inode_bitmap: Bitmap,
+    pub data_bitmap: Bitmap,
+    inode_area_start_block: u32,
+    data_area_start_block: u32,
+}
+
+impl EasyFileSystem {
+    /// 在块设备上创建（格式化）一个新的文件系统，
+    /// `inode_bitmap_blocks` 个块的 inode 位图最多可以有 `inode_bitmap_blocks * 4096` 个 inode
+    pub fn create(
+        block_device: Arc<dyn BlockDevice>,
+        total_blocks: u32,
+        inode_bitmap_blocks: u32,
+    ) -> Arc<Mutex<Self>> {
+        let inode_num = inode_bitmap_blocks as usize * BLOCK_SIZE * 8;
+        let inode_area_blocks = inode_num.div_ceil(INODES_PER_BLOCK) as u32;
+        let inode_total_blocks = inode_bitmap_blocks + inode_area_blocks;
+
+        // 剩下的块分给数据块位图和数据块，每个位图块管理 4096 个数据块
+        let data_total_blocks = total_blocks - 1 - inode_total_blocks;
+        let data_bitmap_blocks = data_total_blocks.div_ceil(4097);
+        let data_area_blocks = data_total_blocks - data_bitmap_blocks;
+
+        let efs = Self {
+            inode_bitmap: Bitmap::new(1, inode_bitmap_blocks as usize, inode_num),
+            data_bitmap: Bitmap::new(
+                (1 + inode_total_blocks) as usize,
+                data_bitmap_blocks as usize,
+                data_area_blocks as usize,
+            ),
+            inode_area_start_block: 1 + inode_bitmap_blocks,
+            data_area_start_block: 1 + inode_total_blocks + data_bitmap_blocks,
+            block_device,
+        };
+
+        // 清空所有块
+        for block_id in 0..total_blocks {
+            get_block_cache(block_id as usize, &efs.block_device)
+                .lock()
+                .modify(0, |data_block: &mut DataBlock| data_block.fill(0));
+        }
+
+        get_block_cache(0, &efs.block_device)
+            .lock()
+            .modify(0, |super_block: &mut SuperBlock| {
+                super_block.initialize(
+                    total_blocks,
+                    inode_bitmap_blocks,
+                    inode_area_blocks,
+                    data_bitmap_blocks,
+                    data_area_blocks,
+                );
+            });
+
+        // 0 号 inode 是根目录
+        assert_eq!(efs.alloc_inode(), Some(0));
+        let (root_block_id, root_offset) = efs.get_disk_inode_pos(0);
+        get_block_cache(root_block_id as usize, &efs.block_device)
+            .lock()
+            .modify(root_offset, |disk_inode: &mut DiskInode| {
+                disk_inode.initialize(DiskInodeType::Directory);
+            });
+        block_cache_sync_all();
+
+        Arc::new(Mutex::new(efs))
+    }
+
+    /// 打开块设备上已有的文件系统，超级块无效时返回 None
+    pub fn open(block_device: Arc<dyn BlockDevice>) -> Option<Arc<Mutex<Self>>> {
+        get_block_cache(0, &block_device)
+            .lock()
+            .read(0, |super_block: &SuperBlock| {
+                if !super_block.is_valid() {
+                    return None;
+                }
+                let inode_total_blocks =
+                    super_block.inode_bitmap_blocks + super_block.inode_area_blocks;
+                let efs = Self {
+                    inode_bitmap: Bitmap::new(
+                        1,
+                        super_block.inode_bitmap_blocks as usize,
+                        super_block.inode_area_blocks as usize * INODES_PER_BLOCK,
+                    ),
+                    data_bitmap: Bitmap::new(
+                        (1 + inode_total_blocks) as usize,
+                        super_block.data_bitmap_blocks as usize,
+                        super_block.data_area_blocks as usize,
+                    ),
+                    inode_area_start_block: 1 + super_block.inode_bitmap_blocks,
+                    data_area_start_block: 1 + inode_total_blocks + super_block.data_bitmap_blocks,
+                    block_device: block_device.clone(),
+                };
+                Some(Arc::new(Mutex::new(efs)))
+            })
+    }
+
+    /// 根目录的 inode
+    pub fn root_inode(efs: &Arc<Mutex<Self>>) -> Inode {
+        let block_device = efs.lock().block_device.clone();
+        let (block_id, block_offset) = efs.lock().get_disk_inode_pos(0);
+        Inode::new(0, block_id, block_offset, efs.clone(), block_device)
+    }
+
+    /// 编号为 `inode_id` 的 inode 所在的块编号以及块内偏移
+    pub fn get_disk_inode_pos(&self, inode_id: u32) -> (u32, usize) {
+        let inode_id = inode_id as usize;
+        let block_id = self.inode_area_start_block + (inode_id / INODES_PER_BLOCK) as u32;
+        (block_id, inode_id % INODES_PER_BLOCK * INODE_SIZE)
+    }
+
+    pub fn alloc_inode(&self) -> Option<u32> {
+        self.inode_bitmap
+            .alloc(&self.block_device)
+            .map(|id| id as u32)
+    }
+
+    /// 分配一个数据块，返回块编号
+    pub fn alloc_data(&self) -> Option<u32> {
+        self.data_bitmap
+            .alloc(&self.block_device)
+            .map(|id| id as u32 + self.data_area_start_block)
+    }
+
+    /// 回收一个数据块，同时清空它的内容
+    pub fn dealloc_data(&self, block_id: u32) {
+        get_block_cache(block_id as usize, &self.block_device)
+            .lock()
+            .modify(0, |data_block: &mut DataBlock| data_block.fill(0));
+        self.data_bitmap.dealloc(
+            &self.block_device,
+            (block_id - self.data_area_start_block) as usize,
+        );
+    }
+}
@@ -0,0 +1,384 @@
+// 磁盘上的数据结构：超级块、inode 以及目录项
+
+use alloc::{sync::Arc, vec::Vec};
+
+use crate::{
+    block_cache::get_block_cache,
+    block_dev::{BlockDevice, BLOCK_SIZE},
+};
+
+const EFS_MAGIC: u32 = 0x3b80_0001;
+
+// 文件名最长 27 bytes，再加上末尾的 `\0`
+pub const NAME_LENGTH_LIMIT: usize = 27;
+
+// DiskInode 的大小为 128 bytes：
+// size(4) + direct(28 * 4) + indirect1(4) + indirect2(4) + type_(4)
+const INODE_DIRECT_COUNT: usize = 28;
+const INODE_INDIRECT1_COUNT: usize = BLOCK_SIZE / 4;
+const INODE_INDIRECT2_COUNT: usize = INODE_INDIRECT1_COUNT * INODE_INDIRECT1_COUNT;
+const DIRECT_BOUND: usize = INODE_DIRECT_COUNT;
+const INDIRECT1_BOUND: usize = DIRECT_BOUND + INODE_INDIRECT1_COUNT;
+
+type IndirectBlock = [u32; BLOCK_SIZE / 4];
+type DataBlock = [u8; BLOCK_SIZE];
+
+/// 超级块，位于 0 号块
+#[repr(C)]
+pub struct SuperBlock {
+    magic: u32,
+    pub total_blocks: u32,
+    pub inode_bitmap_blocks: u32,
+    pub inode_area_blocks: u32,
+    pub data_bitmap_blocks: u32,
+    pub data_area_blocks: u32,
+}
+
+impl SuperBlock {
+    pub fn initialize(
+        &mut self,
+        total_blocks: u32,
+        inode_bitmap_blocks: u32,
+        inode_area_blocks: u32,
+        data_bitmap_blocks: u32,
+        data_area_blocks: u32,
+    ) {
+        *self = Self {
+            magic: EFS_MAGIC,
+            total_blocks,
+            inode_bitmap_blocks,
+            inode_area_blocks,
+            data_bitmap_blocks,
+            data_area_blocks,
+        }
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.magic == EFS_MAGIC
+    }
+}
+
+#[repr(u32)]
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum DiskInodeType {
+    File,
+    Directory,
+}
+
+/// 磁盘上的 inode
+///
+/// 文件的数据块的编号依次保存在：
+/// - `direct`：前 28 块；
+/// - `indirect1` 指向的一级索引块：之后的 128 块；
+/// - `indirect2` 指向的二级索引块：再之后的 128 * 128 块。
+#[repr(C)]
+pub struct DiskInode {
+    pub size: u32,
+    pub direct: [u32; INODE_DIRECT_COUNT],
+    pub indirect1: u32,
+    pub indirect2: u32,
+    type_: DiskInodeType,
+}
+
+impl DiskInode {
+    pub fn initialize(&mut self, type_: DiskInodeType) {
+        self.size = 0;
+        self.direct.iter_mut().for_each(|v| *v = 0);
+        self.indirect1 = 0;
+        self.indirect2 = 0;
+        self.type_ = type_;
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.type_ == DiskInodeType::Directory
+    }
+
+    #[allow(unused)]
+    pub fn is_file(&self) -> bool {
+        self.type_ == DiskInodeType::File
+    }
+
+    /// 保存数据需要的块数
+    pub fn data_blocks(&self) -> u32 {
+        Self::_data_blocks(self.size)
+    }
+
+    fn _data_blocks(size: u32) -> u32 {
+        size.div_ceil(BLOCK_SIZE as u32)
+    }
+
+    /// 大小为 `size` 的文件一共需要的块数，包括索引块
+    pub fn total_blocks(size: u32) -> u32 {
+        let data_blocks = Self::_data_blocks(size) as usize;
+        let mut total = data_blocks;
+        if data_blocks > DIRECT_BOUND {
+            total += 1;
+        }
+        if data_blocks > INDIRECT1_BOUND {
+            total += 1;
+            total += (data_blocks - INDIRECT1_BOUND).div_ceil(INODE_INDIRECT1_COUNT);
+        }
+        total as u32
+    }
+
+    /// 把文件扩大到 `new_size` 需要新分配的块数
+    pub fn blocks_num_needed(&self, new_size: u32) -> u32 {
+        assert!(new_size >= self.size);
+        Self::total_blocks(new_size) - Self::total_blocks(self.size)
+    }
+
+    /// 文件的第 `inner_id` 个数据块的块编号
+    pub fn get_block_id(&self, inner_id: u32, block_device: &Arc<dyn BlockDevice>) -> u32 {
+        let inner_id = inner_id as usize;
+        if inner_id < DIRECT_BOUND {
+            self.direct[inner_id]
+        } else if inner_id < INDIRECT1_BOUND {
+            get_block_cache(self.indirect1 as usize, block_device)
+                .lock()
+                .read(0, |indirect1: &IndirectBlock| {
+                    indirect1[inner_id - DIRECT_BOUND]
+                })
+        } else {
+            let last = inner_id - INDIRECT1_BOUND;
+            let indirect1 = get_block_cache(self.indirect2 as usize, block_device)
+                .lock()
+                .read(0, |indirect2: &IndirectBlock| {
+                    indirect2[last / INODE_INDIRECT1_COUNT]
+                });
+            get_block_cache(indirect1 as usize, block_device)
+                .lock()
+                .read(0, |indirect1: &IndirectBlock| {
+                    indirect1[last % INODE_INDIRECT1_COUNT]
+                })
+        }
+    }
+
+    /// 把文件扩大到 `new_size`，`new_blocks` 是新分配的块（数量由 `blocks_num_needed` 得到），
+    /// 依次用作数据块和索引块
+    pub fn increase_size(
+        &mut self,
+        new_size: u32,
+        new_blocks: Vec<u32>,
+        block_device: &Arc<dyn BlockDevice>,
+    ) {
+        let mut current_blocks = self.data_blocks() as usize;
+        self.size = new_size;
+        let mut total_blocks = self.data_blocks() as usize;
+        let mut new_blocks = new_blocks.into_iter();
+
+        // 直接索引
+        while current_blocks < total_blocks.min(INODE_DIRECT_COUNT) {
+            self.direct[current_blocks] = new_blocks.next().unwrap();
+            current_blocks += 1;
+        }
+
+        // 一级索引
+        if total_blocks <= INODE_DIRECT_COUNT {
+            return;
+        }
+        if current_blocks == INODE_DIRECT_COUNT {
+            self.indirect1 = new_blocks.next().unwrap();
+        }
+        current_blocks -= INODE_DIRECT_COUNT;
+        total_blocks -= INODE_DIRECT_COUNT;
+
+        get_block_cache(self.indirect1 as usize, block_device)
+            .lock()
+            .modify(0, |indirect1: &mut IndirectBlock| {
+                while current_blocks < total_blocks.min(INODE_INDIRECT1_COUNT) {
+                    indirect1[current_blocks] = new_blocks.next().unwrap();
+                    current_blocks += 1;
+                }
+            });
+
+        // 二级索引
+        if total_blocks <= INODE_INDIRECT1_COUNT {
+            return;
+        }
+        if current_blocks == INODE_INDIRECT1_COUNT {
+            self.indirect2 = new_blocks.next().unwrap();
+        }
+        current_blocks -= INODE_INDIRECT1_COUNT;
+        total_blocks -= INODE_INDIRECT1_COUNT;
+        assert!(total_blocks <= INODE_INDIRECT2_COUNT);
+
+        get_block_cache(self.indirect2 as usize, block_device)
+            .lock()
+            .modify(0, |indirect2: &mut IndirectBlock| {
+                while current_blocks < total_blocks {
+                    let (a, b) = (
+                        current_blocks / INODE_INDIRECT1_COUNT,
+                        current_blocks % INODE_INDIRECT1_COUNT,
+                    );
+                    if b == 0 {
+                        indirect2[a] = new_blocks.next().unwrap();
+                    }
+                    get_block_cache(indirect2[a] as usize, block_device)
+                        .lock()
+                        .modify(0, |indirect1: &mut IndirectBlock| {
+                            indirect1[b] = new_blocks.next().unwrap();
+                        });
+                    current_blocks += 1;
+                }
+            });
+    }
+
+    /// 把文件的大小清零，返回需要回收的块（包括索引块）
+    pub fn clear_size(&mut self, block_device: &Arc<dyn BlockDevice>) -> Vec<u32> {
+        let mut v: Vec<u32> = Vec::new();
+        let mut data_blocks = self.data_blocks() as usize;
+        self.size = 0;
+
+        // 直接索引
+        for block_id in self.direct.iter_mut().take(data_blocks) {
+            v.push(*block_id);
+            *block_id = 0;
+        }
+
+        // 一级索引
+        if data_blocks <= INODE_DIRECT_COUNT {
+            return v;
+        }
+        v.push(self.indirect1);
+        data_blocks -= INODE_DIRECT_COUNT;
+        get_block_cache(self.indirect1 as usize, block_device)
+            .lock()
+            .read(0, |indirect1: &IndirectBlock| {
+                v.extend(
+                    indirect1
+                        .iter()
+                        .take(data_blocks.min(INODE_INDIRECT1_COUNT)),
+                );
+            });
+        self.indirect1 = 0;
+
+        // 二级索引
+        if data_blocks <= INODE_INDIRECT1_COUNT {
+            return v;
+        }
+        v.push(self.indirect2);
+        data_blocks -= INODE_INDIRECT1_COUNT;
+        assert!(data_blocks <= INODE_INDIRECT2_COUNT);
+        get_block_cache(self.indirect2 as usize, block_device)
+            .lock()
+            .read(0, |indirect2: &IndirectBlock| {
+                let num_indirect1 = data_blocks.div_ceil(INODE_INDIRECT1_COUNT);
+                for (a, &indirect1_id) in indirect2.iter().take(num_indirect1).enumerate() {
+                    v.push(indirect1_id);
+                    let count =
+                        (data_blocks - a * INODE_INDIRECT1_COUNT).min(INODE_INDIRECT1_COUNT);
+                    get_block_cache(indirect1_id as usize, block_device)
+                        .lock()
+                        .read(0, |indirect1: &IndirectBlock| {
+                            v.extend(indirect1.iter().take(count));
+                        });
+                }
+            });
+        self.indirect2 = 0;
+
+        v
+    }
+
+    /// 从 `offset` 处读取数据到 `buf`，返回实际读取的字节数（不会超过文件末尾）
+    pub fn read_at(
+        &self,
+        offset: usize,
+        buf: &mut [u8],
+        block_device: &Arc<dyn BlockDevice>,
+    ) -> usize {
+        let end = (offset + buf.len()).min(self.size as usize);
+        let mut start = offset;
+        let mut read_size = 0;
+        while start < end {
+            let block_end = ((start / BLOCK_SIZE + 1) * BLOCK_SIZE).min(end);
+            let len = block_end - start;
+            let dst = &mut buf[read_size..read_size + len];
+            let block_id = self.get_block_id((start / BLOCK_SIZE) as u32, block_device);
+            get_block_cache(block_id as usize, block_device)
+                .lock()
+                .read(0, |data_block: &DataBlock| {
+                    let offset = start % BLOCK_SIZE;
+                    dst.copy_from_slice(&data_block[offset..offset + len]);
+                });
+            read_size += len;
+            start = block_end;
+        }
+        read_size
+    }
+
+    /// 把 `buf` 写入 `offset` 处，返回实际写入的字节数。
+    /// 调用者需要先用 `increase_size` 把文件扩大到足够的大小
+    pub fn write_at(
+        &mut self,
+        offset: usize,
+        buf: &[u8],
+        block_device: &Arc<dyn BlockDevice>,
+    ) -> usize {
+        let end = (offset + buf.len()).min(self.size as usize);
+        let mut start = offset;
+        let mut write_size = 0;
+        while start < end {
+            let block_end = ((start / BLOCK_SIZE + 1) * BLOCK_SIZE).min(end);
+            let len = block_end - start;
+            let src = &buf[write_size..write_size + len];
+            let block_id = self.get_block_id((start / BLOCK_SIZE) as u32, block_device);
+            get_block_cache(block_id as usize, block_device)
+                .lock()
+                .modify(0, |data_block: &mut DataBlock| {
+                    let offset = start % BLOCK_SIZE;
+                    data_block[offset..offset + len].copy_from_slice(src);
+                });
+            write_size += len;
+            start = block_end;
+        }
+        write_size
+    }
+}
+
+/// 目录项，32 bytes
+#[repr(C)]
+pub struct DirEntry {
+    name: [u8; NAME_LENGTH_LIMIT + 1],
+    inode_number: u32,
+}
+
+pub const DIRENT_SIZE: usize = 32;
+
+impl DirEntry {
+    pub fn empty() -> Self {
+        Self {
+            name: [0; NAME_LENGTH_LIMIT + 1],
+            inode_number: 0,
+        }
+    }
+
+    /// 文件名超过 27 bytes 时返回 None
+    pub fn new(name: &str, inode_number: u32) -> Option<Self> {
+        if name.len() > NAME_LENGTH_LIMIT {
+            return None;
+        }
+        let mut bytes = [0; NAME_LENGTH_LIMIT + 1];
+        bytes[..name.len()].copy_from_slice(name.as_bytes());
+        Some(Self {
+            name: bytes,
+            inode_number,
+        })
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        unsafe { core::slice::from_raw_parts(self as *const _ as *const u8, DIRENT_SIZE) }
+    }
+
+    pub fn as_bytes_mut(&mut self) -> &mut [u8] {
+        unsafe { core::slice::from_raw_parts_mut(self as *mut _ as *mut u8, DIRENT_SIZE) }
+    }
+
+    pub fn name(&self) -> &str {
+        let len = self.name.iter().position(|&b| b == 0).unwrap();
+        core::str::from_utf8(&self.name[..len]).unwrap()
+    }
+
+    pub fn inode_number(&self) -> u32 {
+        self.inode_number
+    }
+}
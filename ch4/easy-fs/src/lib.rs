@@ -0,0 +1,39 @@
+// 一个简单的文件系统（参考 rCore 的 easy-fs）
+//
+// 磁盘按块（512 bytes）划分为 5 个连续的区域：
+//
+// | 超级块 | inode 位图 | inode 区域 | 数据块位图 | 数据块区域 |
+//
+// - 超级块：魔数以及其余各个区域的大小；
+// - inode 位图 / 数据块位图：记录 inode / 数据块是否已经分配；
+// - inode 区域：每个 inode（`DiskInode`）占 128 bytes，记录文件的大小和数据块的编号；
+// - 数据块区域：文件的内容、目录项以及间接索引块。
+//
+// 只有一个目录，即根目录（0 号 inode），所有文件都在根目录里。
+//
+// 模块分层（从下往上）：
+// - block_dev：块设备接口；
+// - block_cache：块缓存，所有对块的读写都经过缓存；
+// - layout / bitmap：磁盘上的数据结构；
+// - efs：管理各个区域，分配/回收 inode 和数据块；
+// - vfs：`Inode`，提供文件的查找、创建、读写等操作。
+
+#![no_std]
+
+extern crate alloc;
+
+mod bitmap;
+mod block_cache;
+mod block_dev;
+mod efs;
+mod layout;
+mod vfs;
+
+#[cfg(test)]
+mod tests;
+
+pub use block_cache::block_cache_sync_all;
+pub use block_dev::{BlockDevice, BLOCK_SIZE};
+pub use efs::EasyFileSystem;
+pub use layout::{DiskInode, NAME_LENGTH_LIMIT};
+pub use vfs::Inode;
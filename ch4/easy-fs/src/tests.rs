@@ -0,0 +1,263 @@
+// 在内存里的块设备上测试格式化、挂载、创建和读写文件，
+// 每个测试之后都用 `fsck` 检查位图跟 inode 是否一致
+
+use alloc::{collections::BTreeSet, sync::Arc, vec, vec::Vec};
+
+use spin::Mutex;
+
+use crate::{
+    block_cache::get_block_cache,
+    block_dev::{BlockDevice, BLOCK_SIZE},
+    efs::EasyFileSystem,
+    layout::{DirEntry, DiskInode, DIRENT_SIZE},
+    NAME_LENGTH_LIMIT,
+};
+
+const TOTAL_BLOCKS: u32 = 4096;
+const INODE_BITMAP_BLOCKS: u32 = 1;
+
+/// 以 `Vec<u8>` 作为块设备
+struct MemDisk(Mutex<Vec<u8>>);
+
+/// 大小为 `blocks` 个块、内容全为 0 的块设备
+fn mem_disk(blocks: u32) -> Arc<dyn BlockDevice> {
+    Arc::new(MemDisk(Mutex::new(vec![0; blocks as usize * BLOCK_SIZE])))
+}
+
+impl BlockDevice for MemDisk {
+    fn read_block(&self, block_id: usize, buf: &mut [u8]) {
+        let start = block_id * BLOCK_SIZE;
+        buf.copy_from_slice(&self.0.lock()[start..start + BLOCK_SIZE]);
+    }
+
+    fn write_block(&self, block_id: usize, buf: &[u8]) {
+        let start = block_id * BLOCK_SIZE;
+        self.0.lock()[start..start + BLOCK_SIZE].copy_from_slice(buf);
+    }
+
+    fn num_blocks(&self) -> usize {
+        self.0.lock().len() / BLOCK_SIZE
+    }
+}
+
+/// 以 251 为周期、从 `seed` 开始的数据，用于检查读写的位置是否正确
+fn pattern(len: usize, seed: usize) -> Vec<u8> {
+    (0..len).map(|i| ((i + seed) % 251) as u8).collect()
+}
+
+/// inode 占用的所有块（数据块和索引块）
+fn inode_blocks(disk_inode: &DiskInode, block_device: &Arc<dyn BlockDevice>) -> Vec<u32> {
+    let mut blocks: Vec<u32> = (0..disk_inode.data_blocks())
+        .map(|inner_id| disk_inode.get_block_id(inner_id, block_device))
+        .collect();
+    let total = DiskInode::total_blocks(disk_inode.size) as usize;
+    if total > blocks.len() {
+        blocks.push(disk_inode.indirect1);
+    }
+    if total > blocks.len() {
+        blocks.push(disk_inode.indirect2);
+        let indirect1_blocks = total - blocks.len();
+        get_block_cache(disk_inode.indirect2 as usize, block_device)
+            .lock()
+            .read(0, |indirect2: &[u32; BLOCK_SIZE / 4]| {
+                blocks.extend_from_slice(&indirect2[..indirect1_blocks]);
+            });
+    }
+    blocks
+}
+
+/// 检查文件系统的一致性：
+/// - 已分配的 inode 正好是根目录和它的各个文件；
+/// - 各个 inode 占用的块互不重复，并且都已经在数据块位图里分配，没有泄漏的数据块。
+fn fsck(efs: &Arc<Mutex<EasyFileSystem>>) {
+    let fs = efs.lock();
+    let block_device = fs.block_device.clone();
+    let read_inode = |inode_id: u32| {
+        let (block_id, offset) = fs.get_disk_inode_pos(inode_id);
+        get_block_cache(block_id as usize, &block_device)
+            .lock()
+            .read(offset, |disk_inode: &DiskInode| {
+                (
+                    disk_inode.is_dir(),
+                    disk_inode.size,
+                    inode_blocks(disk_inode, &block_device),
+                )
+            })
+    };
+
+    let (is_dir, root_size, mut blocks) = read_inode(0);
+    assert!(is_dir, "inode 0 is not the root directory");
+    assert_eq!(root_size as usize % DIRENT_SIZE, 0);
+
+    let mut inode_ids = BTreeSet::from([0]);
+    let (root_block_id, root_offset) = fs.get_disk_inode_pos(0);
+    let dirents: Vec<u32> = get_block_cache(root_block_id as usize, &block_device)
+        .lock()
+        .read(root_offset, |root: &DiskInode| {
+            (0..root_size as usize / DIRENT_SIZE)
+                .map(|i| {
+                    let mut dirent = DirEntry::empty();
+                    root.read_at(i * DIRENT_SIZE, dirent.as_bytes_mut(), &block_device);
+                    dirent.inode_number()
+                })
+                .collect()
+        });
+    for inode_id in dirents {
+        assert!(
+            inode_ids.insert(inode_id),
+            "inode {} is linked twice",
+            inode_id
+        );
+        let (is_dir, _, file_blocks) = read_inode(inode_id);
+        assert!(!is_dir, "inode {} is a directory", inode_id);
+        blocks.extend(file_blocks);
+    }
+
+    assert_eq!(fs.inode_bitmap.allocated(&block_device), inode_ids.len());
+    let unique: BTreeSet<u32> = blocks.iter().copied().collect();
+    assert_eq!(unique.len(), blocks.len(), "a block is used twice");
+    assert!(!unique.contains(&0), "an inode points at block 0");
+    assert_eq!(fs.data_bitmap.allocated(&block_device), blocks.len());
+}
+
+#[test]
+fn format_and_mount() {
+    let device = mem_disk(TOTAL_BLOCKS);
+    assert!(EasyFileSystem::open(device.clone()).is_none());
+
+    let efs = EasyFileSystem::create(device.clone(), TOTAL_BLOCKS, INODE_BITMAP_BLOCKS);
+    let root = EasyFileSystem::root_inode(&efs);
+    assert!(root.is_dir());
+    assert_eq!(root.size(), 0);
+    assert!(root.ls().is_empty());
+    fsck(&efs);
+
+    let efs = EasyFileSystem::open(device).unwrap();
+    let root = EasyFileSystem::root_inode(&efs);
+    assert!(root.is_dir());
+    assert!(root.ls().is_empty());
+    fsck(&efs);
+}
+
+#[test]
+fn create_and_find() {
+    let device = mem_disk(TOTAL_BLOCKS);
+    let efs = EasyFileSystem::create(device.clone(), TOTAL_BLOCKS, INODE_BITMAP_BLOCKS);
+    let root = EasyFileSystem::root_inode(&efs);
+
+    let longest = "x".repeat(NAME_LENGTH_LIMIT);
+    for name in ["a", "b", longest.as_str()] {
+        let inode = root.create(name).unwrap();
+        assert!(!inode.is_dir());
+        assert_eq!(inode.size(), 0);
+    }
+    assert!(root.create("a").is_none());
+    assert!(root.create(&"x".repeat(NAME_LENGTH_LIMIT + 1)).is_none());
+    assert_eq!(root.ls(), ["a", "b", longest.as_str()]);
+    assert!(root.find("c").is_none());
+    assert_ne!(
+        root.find("a").unwrap().inode_id(),
+        root.find("b").unwrap().inode_id()
+    );
+    fsck(&efs);
+
+    let efs = EasyFileSystem::open(device).unwrap();
+    let root = EasyFileSystem::root_inode(&efs);
+    assert_eq!(root.ls(), ["a", "b", longest.as_str()]);
+    fsck(&efs);
+}
+
+#[test]
+fn write_and_read() {
+    let device = mem_disk(TOTAL_BLOCKS);
+    let efs = EasyFileSystem::create(device.clone(), TOTAL_BLOCKS, INODE_BITMAP_BLOCKS);
+    let root = EasyFileSystem::root_inode(&efs);
+
+    // 28 个直接块 + 128 个一级索引块之后，还需要二级索引块
+    let data = pattern(200 * BLOCK_SIZE + 123, 0);
+    let inode = root.create("big").unwrap();
+    assert_eq!(inode.write_at(0, &data), data.len());
+    assert_eq!(inode.size(), data.len());
+    fsck(&efs);
+
+    let mut buf = vec![0u8; data.len()];
+    assert_eq!(inode.read_at(0, &mut buf), data.len());
+    assert_eq!(buf, data);
+
+    // 跨越块边界的读写
+    let patch = pattern(3 * BLOCK_SIZE, 7);
+    let offset = 30 * BLOCK_SIZE - 10;
+    assert_eq!(inode.write_at(offset, &patch), patch.len());
+    let mut buf = vec![0u8; patch.len()];
+    assert_eq!(inode.read_at(offset, &mut buf), patch.len());
+    assert_eq!(buf, patch);
+
+    // 读到文件末尾为止
+    let mut buf = vec![0u8; 1000];
+    assert_eq!(inode.read_at(data.len() - 100, &mut buf), 100);
+    assert_eq!(inode.read_at(data.len(), &mut buf), 0);
+
+    // 在文件末尾之后写入，文件随之扩大
+    let tail = pattern(10, 3);
+    assert_eq!(inode.write_at(data.len() + 5, &tail), tail.len());
+    assert_eq!(inode.size(), data.len() + 15);
+    fsck(&efs);
+
+    let efs = EasyFileSystem::open(device).unwrap();
+    let inode = EasyFileSystem::root_inode(&efs).find("big").unwrap();
+    let mut buf = vec![0u8; inode.size()];
+    assert_eq!(inode.read_at(0, &mut buf), data.len() + 15);
+    assert_eq!(buf[..offset], data[..offset]);
+    assert_eq!(buf[offset..offset + patch.len()], patch[..]);
+    assert_eq!(buf[data.len() + 5..], tail[..]);
+    fsck(&efs);
+}
+
+#[test]
+fn clear_frees_blocks() {
+    let device = mem_disk(TOTAL_BLOCKS);
+    let efs = EasyFileSystem::create(device.clone(), TOTAL_BLOCKS, INODE_BITMAP_BLOCKS);
+    let root = EasyFileSystem::root_inode(&efs);
+    let inode = root.create("file").unwrap();
+    let free_blocks = || {
+        let fs = efs.lock();
+        fs.data_bitmap.maximum() - fs.data_bitmap.allocated(&device)
+    };
+    let before = free_blocks();
+
+    let data = pattern(300 * BLOCK_SIZE, 1);
+    assert_eq!(inode.write_at(0, &data), data.len());
+    assert!(free_blocks() < before);
+    fsck(&efs);
+
+    inode.clear();
+    assert_eq!(inode.size(), 0);
+    assert_eq!(free_blocks(), before);
+    fsck(&efs);
+
+    // 回收的块被清空之后才能重新使用
+    assert_eq!(inode.write_at(0, &data[..BLOCK_SIZE]), BLOCK_SIZE);
+    let mut buf = vec![0u8; 2 * BLOCK_SIZE];
+    assert_eq!(inode.read_at(0, &mut buf), BLOCK_SIZE);
+    assert_eq!(buf[..BLOCK_SIZE], data[..BLOCK_SIZE]);
+    fsck(&efs);
+}
+
+#[test]
+fn disk_full() {
+    const SMALL_BLOCKS: u32 = 1100;
+    let device = mem_disk(SMALL_BLOCKS);
+    let efs = EasyFileSystem::create(device, SMALL_BLOCKS, INODE_BITMAP_BLOCKS);
+    let root = EasyFileSystem::root_inode(&efs);
+    let inode = root.create("file").unwrap();
+
+    // 空间不足时一个字节都不写入，已经分配的块全部回收
+    let data = pattern(SMALL_BLOCKS as usize * BLOCK_SIZE, 0);
+    assert_eq!(inode.write_at(0, &data), 0);
+    assert_eq!(inode.size(), 0);
+    fsck(&efs);
+
+    let data = pattern(16 * BLOCK_SIZE, 0);
+    assert_eq!(inode.write_at(0, &data), data.len());
+    fsck(&efs);
+}
@@ -0,0 +1,238 @@
+// 内存里的 inode，提供文件的查找、创建、读写等操作
+//
+// 每个操作都先获取文件系统的锁，所以同一时间只有一个操作在修改磁盘上的数据结构。
+
+use alloc::{string::String, sync::Arc, vec::Vec};
+
+use spin::{Mutex, MutexGuard};
+
+use crate::{
+    block_cache::{block_cache_sync_all, get_block_cache},
+    block_dev::BlockDevice,
+    efs::EasyFileSystem,
+    layout::{DirEntry, DiskInode, DiskInodeType, DIRENT_SIZE},
+};
+
+pub struct Inode {
+    inode_id: u32,
+    block_id: usize,
+    block_offset: usize,
+    fs: Arc<Mutex<EasyFileSystem>>,
+    block_device: Arc<dyn BlockDevice>,
+}
+
+impl Inode {
+    pub fn new(
+        inode_id: u32,
+        block_id: u32,
+        block_offset: usize,
+        fs: Arc<Mutex<EasyFileSystem>>,
+        block_device: Arc<dyn BlockDevice>,
+    ) -> Self {
+        Self {
+            inode_id,
+            block_id: block_id as usize,
+            block_offset,
+            fs,
+            block_device,
+        }
+    }
+
+    fn read_disk_inode<V>(&self, f: impl FnOnce(&DiskInode) -> V) -> V {
+        get_block_cache(self.block_id, &self.block_device)
+            .lock()
+            .read(self.block_offset, f)
+    }
+
+    fn modify_disk_inode<V>(&self, f: impl FnOnce(&mut DiskInode) -> V) -> V {
+        get_block_cache(self.block_id, &self.block_device)
+            .lock()
+            .modify(self.block_offset, f)
+    }
+
+    /// inode 的编号
+    pub fn inode_id(&self) -> u32 {
+        self.inode_id
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.read_disk_inode(|disk_inode| disk_inode.is_dir())
+    }
+
+    /// 文件的大小（bytes）
+    pub fn size(&self) -> usize {
+        self.read_disk_inode(|disk_inode| disk_inode.size as usize)
+    }
+
+    /// 在目录里查找文件名为 `name` 的目录项，返回 inode 编号
+    fn find_inode_id(&self, name: &str, disk_inode: &DiskInode) -> Option<u32> {
+        assert!(disk_inode.is_dir());
+        let file_count = disk_inode.size as usize / DIRENT_SIZE;
+        let mut dirent = DirEntry::empty();
+        for i in 0..file_count {
+            assert_eq!(
+                disk_inode.read_at(i * DIRENT_SIZE, dirent.as_bytes_mut(), &self.block_device),
+                DIRENT_SIZE
+            );
+            if dirent.name() == name {
+                return Some(dirent.inode_number());
+            }
+        }
+        None
+    }
+
+    fn inode_of(&self, fs: &MutexGuard<EasyFileSystem>, inode_id: u32) -> Arc<Inode> {
+        let (block_id, block_offset) = fs.get_disk_inode_pos(inode_id);
+        Arc::new(Self::new(
+            inode_id,
+            block_id,
+            block_offset,
+            self.fs.clone(),
+            self.block_device.clone(),
+        ))
+    }
+
+    /// 在目录里查找文件
+    pub fn find(&self, name: &str) -> Option<Arc<Inode>> {
+        let fs = self.fs.lock();
+        self.read_disk_inode(|disk_inode| self.find_inode_id(name, disk_inode))
+            .map(|inode_id| self.inode_of(&fs, inode_id))
+    }
+
+    /// 把文件扩大到 `new_size`，磁盘空间不足时返回 false 并保持原来的大小
+    fn increase_size(
+        &self,
+        new_size: u32,
+        disk_inode: &mut DiskInode,
+        fs: &mut MutexGuard<EasyFileSystem>,
+    ) -> bool {
+        if new_size <= disk_inode.size {
+            return true;
+        }
+        let blocks_needed = disk_inode.blocks_num_needed(new_size);
+        let mut new_blocks: Vec<u32> = Vec::new();
+        for _ in 0..blocks_needed {
+            match fs.alloc_data() {
+                Some(block_id) => new_blocks.push(block_id),
+                None => {
+                    for block_id in new_blocks {
+                        fs.dealloc_data(block_id);
+                    }
+                    return false;
+                }
+            }
+        }
+        disk_inode.increase_size(new_size, new_blocks, &self.block_device);
+        true
+    }
+
+    /// 在目录里创建一个空文件，文件已经存在、文件名太长或者空间不足时返回 None
+    pub fn create(&self, name: &str) -> Option<Arc<Inode>> {
+        let mut fs = self.fs.lock();
+        if self
+            .read_disk_inode(|disk_inode| self.find_inode_id(name, disk_inode))
+            .is_some()
+        {
+            return None;
+        }
+
+        let new_inode_id = fs.alloc_inode()?;
+        let dirent = match DirEntry::new(name, new_inode_id) {
+            Some(dirent) => dirent,
+            None => {
+                fs.inode_bitmap
+                    .dealloc(&self.block_device, new_inode_id as usize);
+                return None;
+            }
+        };
+
+        let (new_block_id, new_block_offset) = fs.get_disk_inode_pos(new_inode_id);
+        get_block_cache(new_block_id as usize, &self.block_device)
+            .lock()
+            .modify(new_block_offset, |new_inode: &mut DiskInode| {
+                new_inode.initialize(DiskInodeType::File);
+            });
+
+        // 在目录的末尾添加目录项
+        let added = self.modify_disk_inode(|root_inode| {
+            let file_count = root_inode.size as usize / DIRENT_SIZE;
+            let new_size = (file_count + 1) * DIRENT_SIZE;
+            if !self.increase_size(new_size as u32, root_inode, &mut fs) {
+                return false;
+            }
+            root_inode.write_at(
+                file_count * DIRENT_SIZE,
+                dirent.as_bytes(),
+                &self.block_device,
+            );
+            true
+        });
+        if !added {
+            fs.inode_bitmap
+                .dealloc(&self.block_device, new_inode_id as usize);
+            return None;
+        }
+
+        let inode = self.inode_of(&fs, new_inode_id);
+        drop(fs);
+        block_cache_sync_all();
+        Some(inode)
+    }
+
+    /// 列出目录里的所有文件名
+    pub fn ls(&self) -> Vec<String> {
+        let _fs = self.fs.lock();
+        self.read_disk_inode(|disk_inode| {
+            let file_count = disk_inode.size as usize / DIRENT_SIZE;
+            let mut v: Vec<String> = Vec::new();
+            for i in 0..file_count {
+                let mut dirent = DirEntry::empty();
+                assert_eq!(
+                    disk_inode.read_at(i * DIRENT_SIZE, dirent.as_bytes_mut(), &self.block_device),
+                    DIRENT_SIZE
+                );
+                v.push(String::from(dirent.name()));
+            }
+            v
+        })
+    }
+
+    /// 从 `offset` 处读取数据到 `buf`，返回实际读取的字节数，到达文件末尾时返回 0
+    pub fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
+        let _fs = self.fs.lock();
+        self.read_disk_inode(|disk_inode| disk_inode.read_at(offset, buf, &self.block_device))
+    }
+
+    /// 把 `buf` 写入 `offset` 处，必要时扩大文件，返回实际写入的字节数。
+    /// 磁盘空间不足时返回 0
+    pub fn write_at(&self, offset: usize, buf: &[u8]) -> usize {
+        let mut fs = self.fs.lock();
+        let size = self.modify_disk_inode(|disk_inode| {
+            if !self.increase_size((offset + buf.len()) as u32, disk_inode, &mut fs) {
+                return 0;
+            }
+            disk_inode.write_at(offset, buf, &self.block_device)
+        });
+        drop(fs);
+        block_cache_sync_all();
+        size
+    }
+
+    /// 把文件的大小清零，回收所有数据块
+    pub fn clear(&self) {
+        let fs = self.fs.lock();
+        self.modify_disk_inode(|disk_inode| {
+            let size = disk_inode.size;
+            let data_blocks_dealloc = disk_inode.clear_size(&self.block_device);
+            assert_eq!(
+                data_blocks_dealloc.len(),
+                DiskInode::total_blocks(size) as usize
+            );
+            for data_block in data_blocks_dealloc {
+                fs.dealloc_data(data_block);
+            }
+        });
+        drop(fs);
+        block_cache_sync_all();
+    }
+}
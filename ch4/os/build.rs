@@ -1,70 +1,84 @@
-// 用于生成 src/link_app.S 文件
-use std::fs::{read_dir, File};
+// 用于生成嵌入内核符号表的 ksyms.S 文件，以及嵌入磁盘镜像的 ramdisk.S 文件
+//
+// ch4 MODIFY:
+// 应用程序不再由 link_app.S 链接进内核，而是由 ../easy-fs-fuse 打包进磁盘镜像，
+// 内核启动时从文件系统里加载（见 `loader.rs`）。
+use std::env;
+use std::fs::File;
 use std::io::{Result, Write};
+use std::path::{Path, PathBuf};
 
 fn main() {
-    println!("cargo:rerun-if-changed=../user/src/");
-    println!("cargo:rerun-if-changed={}", TARGET_PATH);
-    insert_app_data().unwrap();
+    insert_kernel_symbols().unwrap();
+    insert_ramdisk_image().unwrap();
 }
 
-// 编译目标去除元数据之后的可执行文件的所在目录
-static TARGET_PATH: &str = "../user/target/riscv64gc-unknown-none-elf/release/";
+// 生成 `$OUT_DIR/ksyms.S`，把内核的符号表嵌入到 `.ksyms` 段，用于 panic 时打印函数名称。
+//
+// 符号表来自上一次链接得到的内核（由 `build-bin` 脚本使用 `rust-nm` 导出），
+// 所以内核需要链接两次：
+// 1. 第一次链接时符号表为空（或者是旧的）；
+// 2. 导出第一次链接得到的符号表，再链接一次，把符号表嵌入内核。
+//
+// `.ksyms` 段位于 `.data` 和 `.bss` 之间，它的大小不影响 `.text` 里各个函数的地址，
+// 所以第二次链接得到的内核跟嵌入的符号表是一致的。
+//
+// 符号表是文本格式，每行一个函数，按地址从小到大排列：`<16 进制地址> <函数名称>`
+fn insert_kernel_symbols() -> Result<()> {
+    let profile = env::var("PROFILE").unwrap();
+    let symbols_path = Path::new(&env::var("CARGO_MANIFEST_DIR").unwrap())
+        .join(format!("target/riscv64gc-unknown-none-elf/{}/os.sym", profile));
 
-fn insert_app_data() -> Result<()> {
-    let mut f = File::create("src/link_app.S").unwrap();
+    println!("cargo:rerun-if-changed={}", symbols_path.display());
 
-    // 获取应用程序的名称列表
-    let mut apps: Vec<_> = read_dir("../user/src/bin")
-        .unwrap()
-        .into_iter()
-        .map(|dir_entry| {
-            let mut name_with_ext = dir_entry.unwrap().file_name().into_string().unwrap();
-
-            // 移除文件的扩展名部分（即 ".rs"）
-            name_with_ext.drain(name_with_ext.find('.').unwrap()..name_with_ext.len());
-            name_with_ext
-        })
-        .collect();
-    apps.sort();
+    let out_path = PathBuf::from(env::var("OUT_DIR").unwrap()).join("ksyms.S");
+    let mut f = File::create(out_path)?;
 
     writeln!(f, "    # Generated by build.rs")?;
     writeln!(f, "    # do NOT modify this file manually")?;
+    writeln!(f, r#"    .section .ksyms, "a""#)?;
+
+    // 注意 rustc 并不会追踪 `.incbin` 引用的文件，
+    // 所以同时写入符号表的大小，让符号表改变时 ksyms.S 的内容也随之改变，从而触发重新编译
+    if symbols_path.exists() {
+        let len = symbols_path.metadata()?.len();
+        writeln!(f, "    # {} bytes", len)?;
+        writeln!(f, r#"    .incbin "{}""#, symbols_path.display())?;
+    }
+    Ok(())
+}
+
+// ch4 新增
+// 生成 `$OUT_DIR/ramdisk.S`，在开启 `embedded-fs` 特性时把磁盘镜像 `fs.img` 嵌入到 `.data` 段，
+// 内核在没有 virtio-blk 磁盘时把它作为 RAM disk 使用（见 `drivers/ramdisk.rs`）。
+//
+// 镜像按页对齐，`_ramdisk_start` 等于 `_ramdisk_end` 表示没有嵌入镜像。
+fn insert_ramdisk_image() -> Result<()> {
+    let image_path = Path::new(&env::var("CARGO_MANIFEST_DIR").unwrap()).join("fs.img");
+
+    println!("cargo:rerun-if-changed={}", image_path.display());
 
+    let out_path = PathBuf::from(env::var("OUT_DIR").unwrap()).join("ramdisk.S");
+    let mut f = File::create(out_path)?;
+
+    writeln!(f, "    # Generated by build.rs")?;
+    writeln!(f, "    # do NOT modify this file manually")?;
     writeln!(
         f,
         r#"
-    .align 3
     .section .data
-    .global _num_app
-_num_app:
-    .quad {}"#,
-        apps.len()
+    .global _ramdisk_start
+    .global _ramdisk_end
+    .align 12
+_ramdisk_start:"#
     )?;
 
-    for i in 0..apps.len() {
-        writeln!(f, r#"    .quad app_{}_start"#, i)?;
-    }
-
-    writeln!(f, r#"    .quad app_{}_end"#, apps.len() - 1)?;
-
-    for (idx, app) in apps.iter().enumerate() {
-        println!("app_{}: {}", idx, app);
-        writeln!(
-            f,
-            r#"
-    .section .data
-    .global app_{0}_start
-    .global app_{0}_end
-    .align 3
-app_{0}_start:
-    .incbin "{2}{1}"
-app_{0}_end:"#,
-            idx, app, TARGET_PATH
-        )?;
-        // >> ch4 开始直接加载 app 的 elf 格式
-        // >> 而不是去除了元数据的 bin 格式
-        // >> ch4 在 `app_{0}_start:` 之前添加了 `.align 3`
+    // 跟符号表一样，写入镜像的大小，让镜像改变时 ramdisk.S 的内容也随之改变
+    if env::var_os("CARGO_FEATURE_EMBEDDED_FS").is_some() && image_path.exists() {
+        let len = image_path.metadata()?.len();
+        writeln!(f, "    # {} bytes", len)?;
+        writeln!(f, r#"    .incbin "{}""#, image_path.display())?;
     }
+    writeln!(f, "_ramdisk_end:")?;
     Ok(())
 }
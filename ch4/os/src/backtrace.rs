@@ -0,0 +1,130 @@
+// 内核栈回溯
+//
+// 内核使用 `-C force-frame-pointers=yes` 编译，每个函数的栈帧里 `fp - 8` 处是返回地址，
+// `fp - 16` 处是调用者的帧指针，所以从当前的 fp（s0）开始就能得到整个调用链。
+//
+// 返回地址通过内核符号表（见 `build.rs` 的 `insert_kernel_symbols()`）转换为函数名称，
+// 符号表被嵌入在 `.ksyms` 段，每行的格式为 `<16 进制地址> <函数名称>`，按地址从小到大排列。
+
+use core::arch::asm;
+
+use crate::config::{kernel_stack_position, KERNEL_STACK_SIZE, PAGE_SIZE, TRAMPOLINE};
+
+/// 回溯的最大层数，防止栈被破坏时出现死循环
+const MAX_BACKTRACE_DEPTH: usize = 64;
+
+/// 打印当前的内核调用链
+pub fn print_backtrace() {
+    let mut fp: usize;
+    unsafe {
+        asm!("mv {}, s0", out(reg) fp);
+    }
+
+    // 只有位于当前内核栈范围内的帧指针才是可信的，
+    // 比如从用户态陷入时 s0 里还是应用程序的帧指针
+    let (bottom, top) = match current_stack_range(fp) {
+        Some(range) => range,
+        None => {
+            println!("[kernel] backtrace: unknown kernel stack, fp = {:#x}", fp);
+            return;
+        }
+    };
+
+    println!("[kernel] backtrace:");
+    for depth in 0..MAX_BACKTRACE_DEPTH {
+        if fp < bottom + 16 || fp > top || fp % core::mem::size_of::<usize>() != 0 {
+            return;
+        }
+
+        let ra = unsafe { *((fp - 8) as *const usize) };
+        let prev_fp = unsafe { *((fp - 16) as *const usize) };
+        if ra == 0 {
+            return;
+        }
+
+        // ra 指向 call 指令的下一条指令，如果 call 是函数的最后一条指令（比如调用 `-> !` 函数），
+        // ra 会落在下一个函数里，所以使用 `ra - 1` 查找函数名称
+        match lookup_symbol(ra - 1) {
+            Some((name, offset)) => println!(
+                "[kernel]   #{:<2} {:#x} {}+{:#x}",
+                depth,
+                ra,
+                name,
+                offset + 1
+            ),
+            None => println!("[kernel]   #{:<2} {:#x} ??", depth, ra),
+        }
+
+        // 栈向低地址增长，调用者的栈帧一定位于更高的地址
+        if prev_fp <= fp {
+            return;
+        }
+        fp = prev_fp;
+    }
+}
+
+/// 返回包含地址 `addr` 的内核栈的范围 (bottom, top)：
+/// 启动时使用的 boot stack，或者某个任务（线程）的内核栈
+fn current_stack_range(addr: usize) -> Option<(usize, usize)> {
+    extern "C" {
+        fn boot_stack();
+        fn boot_stack_top();
+    }
+
+    let (bottom, top) = (boot_stack as usize, boot_stack_top as usize);
+    if addr > bottom && addr <= top {
+        return Some((bottom, top));
+    }
+
+    // 各个任务的内核栈从 TRAMPOLINE 开始往低地址方向依次排列，栈之间有一个保护页
+    if addr > TRAMPOLINE {
+        return None;
+    }
+    let task_id = (TRAMPOLINE - addr) / (KERNEL_STACK_SIZE + PAGE_SIZE);
+    let (bottom, top) = kernel_stack_position(task_id);
+    if addr > bottom && addr <= top {
+        Some((bottom, top))
+    } else {
+        None
+    }
+}
+
+/// 内核符号表，第一次链接得到的内核没有符号表，此时返回空字符串
+fn kernel_symbols() -> &'static str {
+    extern "C" {
+        fn sksyms();
+        fn eksyms();
+    }
+
+    let bytes = unsafe {
+        core::slice::from_raw_parts(
+            sksyms as usize as *const u8,
+            eksyms as usize - sksyms as usize,
+        )
+    };
+    core::str::from_utf8(bytes).unwrap_or("")
+}
+
+/// 查找包含地址 `addr` 的函数，返回函数名称以及 `addr` 相对于函数起始地址的偏移
+fn lookup_symbol(addr: usize) -> Option<(&'static str, usize)> {
+    let mut found = None;
+
+    for line in kernel_symbols().lines() {
+        let (start, name) = match line.split_once(' ') {
+            Some(pair) => pair,
+            None => continue,
+        };
+        let start = match usize::from_str_radix(start, 16) {
+            Ok(start) => start,
+            Err(_) => continue,
+        };
+
+        // 符号表按地址排列，遇到第一个大于 addr 的符号即可停止
+        if start > addr {
+            break;
+        }
+        found = Some((name, addr - start));
+    }
+
+    found
+}
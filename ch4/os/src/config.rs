@@ -22,6 +22,42 @@ pub const MEMORY_END: usize = 0x8080_0000;
 pub const TRAMPOLINE: usize = usize::MAX - PAGE_SIZE + 1;
 pub const TRAP_CONTEXT: usize = TRAMPOLINE - PAGE_SIZE;
 
+// 多线程的应用程序，每个线程都有各自的 TrapContext 和用户栈
+// application address space (high)
+// |----------------------| 2^64
+// |      trampoline      | 4 KB
+// |----------------------|
+// | trap context (tid 0) | 4 KB  <-- TRAP_CONTEXT
+// |----------------------|
+// | trap context (tid 1) | 4 KB
+// |----------------------|
+// |         ...          |
+// |                      |
+// |         ...          |
+// |----------------------|
+// |  user stack (tid 2)  |
+// |      guard page      |
+// |----------------------|
+// |  user stack (tid 1)  |
+// |      guard page      |
+// |----------------------| <-- 主线程用户栈的栈顶（base_size）
+// |  user stack (tid 0)  |
+// |      guard page      |
+// |----------------------|
+// |     .text/.data      |
+// |----------------------| 0
+
+/// 线程 `tid` 的 TrapContext 在应用地址空间中的虚拟地址
+pub fn trap_context_position(tid: usize) -> usize {
+    TRAP_CONTEXT - tid * PAGE_SIZE
+}
+
+/// 线程 `tid`（不为 0）的用户栈的 (bottom, top)，`ustack_base` 是主线程用户栈的栈顶
+pub fn thread_user_stack_position(ustack_base: usize, tid: usize) -> (usize, usize) {
+    let bottom = ustack_base + (tid - 1) * (USER_STACK_SIZE + PAGE_SIZE) + PAGE_SIZE;
+    (bottom, bottom + USER_STACK_SIZE)
+}
+
 // 注意：
 // 关于为什么 TRAMPOLINE, TRAP_CONTEXT 会放在靠近 usize::MAX 的地方，原因：
 //
@@ -46,17 +82,41 @@ pub const TRAP_CONTEXT: usize = TRAMPOLINE - PAGE_SIZE;
 // |      guard page    | 4 KB
 // |--------------------|
 // |                    |
+//
+// ch4 MODIFY:
+// 每个线程都有各自的内核栈，所以这里的 id 是任务（线程）的 id：
+// 前 num_app 个任务是各个应用的主线程，之后创建的线程的内核栈依次往下排列。
 /// Return (bottom, top) of a kernel stack in kernel space.
-pub fn kernel_stack_position(app_id: usize) -> (usize, usize) {
-    let top = TRAMPOLINE - app_id * (KERNEL_STACK_SIZE + PAGE_SIZE);
+pub fn kernel_stack_position(task_id: usize) -> (usize, usize) {
+    let top = TRAMPOLINE - task_id * (KERNEL_STACK_SIZE + PAGE_SIZE);
     let bottom = top - KERNEL_STACK_SIZE;
     (bottom, top)
 }
 
+// 系统调用号的上限（不含），用于统计每个系统调用的次数（见 `sys_task_info`）
+pub const MAX_SYSCALL_NUM: usize = 1040;
+
+// 支持的 hart 数量上限，每个 hart 有各自的 64KB 启动栈（见 entry.asm）
+pub const MAX_HARTS: usize = 8;
+
 // QEMU 的时钟频率, 12.5MHz
 pub const CLOCK_FREQ: usize = 12500000;
 
+// QEMU virt 外设的 MMIO 地址
+pub const VIRT_TEST: usize = 0x0010_0000;
+pub const VIRT_RTC: usize = 0x0010_1000;
+pub const VIRT_PLIC: usize = 0x0c00_0000;
+pub const VIRT_UART: usize = 0x1000_0000;
+pub const VIRT_BLK: usize = 0x1000_1000; // 第一个 virtio-mmio 插槽
+
+// PLIC 的中断源编号
+pub const UART_IRQ: usize = 10;
+pub const VIRTIO_BLK_IRQ: usize = 1;
+
 // QEMU MMIO
 pub const MMIO: &[(usize, usize)] = &[
     (0x0010_0000, 0x00_2000), // VIRT_TEST/RTC  in virt machine
+    (0x0c00_0000, 0x21_0000), // VIRT_PLIC in virt machine
+    (0x1000_0000, 0x00_1000), // VIRT_UART0 in virt machine
+    (0x1000_1000, 0x00_1000), // VIRTIO0 in virt machine
 ];
\ No newline at end of file
@@ -1,21 +1,78 @@
-use crate::sbi::console_putchar;
-use core::fmt::{self, Write};
+use crate::{
+    drivers::UART,
+    sync::{SpinLock, SpinLockGuard},
+};
+use core::{
+    fmt::{self, Write},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+/// 保证一次 `print!` 的输出不会跟其他 hart 的输出交错在一起
+/// 不参与加锁顺序检查：任何锁的持有期间都可能输出
+static PRINT_LOCK: SpinLock<()> = SpinLock::named((), "PRINT_LOCK", 0);
+
+/// panic 之后为 true：输出不再获取输出锁和 UART 的锁，而是以轮询的方式直接写入 UART
+static PANIC_MODE: AtomicBool = AtomicBool::new(false);
 
 struct Stdout;
 
+// ch4 MODIFY:
+// 不再通过 SBI 的 `console_putchar` 逐个字符地陷入 M 态，而是直接写 UART
 impl Write for Stdout {
     fn write_str(&mut self, s: &str) -> fmt::Result {
-        for c in s.chars() {
-            console_putchar(c as usize);
+        for c in s.bytes() {
+            putchar(c);
         }
         Ok(())
     }
 }
 
+fn putchar(c: u8) {
+    if PANIC_MODE.load(Ordering::Relaxed) {
+        UART.putchar_polling(c);
+    } else {
+        UART.putchar(c);
+    }
+}
+
 pub fn print(args: fmt::Arguments) {
+    let _guard = lock_print();
     Stdout.write_fmt(args).unwrap();
 }
 
+/// 原样输出一段字节，用于 `sys_write`（应用程序写入的数据不一定是合法的 UTF-8）
+pub fn write_bytes(bytes: &[u8]) {
+    let _guard = lock_print();
+    for c in bytes {
+        putchar(*c);
+    }
+}
+
+/// panic 之后不再获取输出锁
+fn lock_print() -> Option<SpinLockGuard<'static, ()>> {
+    if PANIC_MODE.load(Ordering::Relaxed) {
+        None
+    } else {
+        Some(PRINT_LOCK.lock_irqsave())
+    }
+}
+
+/// panic 时调用：panic 可能发生在持有输出锁或者 UART 的锁的期间（比如在 UART 中断处理程序里），
+/// 再获取它们就会死锁，所以之后的输出绕过这两个锁，以轮询的方式直接写入 UART
+pub fn enter_panic_mode() {
+    if !PANIC_MODE.swap(true, Ordering::Relaxed) {
+        UART.flush_for_panic();
+    }
+}
+
+/// 把尚未发送的字符全部输出，用于 panic 或者关机之前
+pub fn flush() {
+    // panic 之后的输出不经过 tx 缓冲区
+    if !PANIC_MODE.load(Ordering::Relaxed) {
+        UART.flush();
+    }
+}
+
 #[macro_export]
 macro_rules! print {
     ($fmt: literal $(, $($arg: tt)+)?) => {
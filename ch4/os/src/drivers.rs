@@ -0,0 +1,121 @@
+use alloc::sync::Arc;
+use lazy_static::lazy_static;
+use riscv::register::sie;
+
+use crate::{
+    config::{UART_IRQ, VIRTIO_BLK_IRQ, VIRT_BLK, VIRT_PLIC, VIRT_RTC, VIRT_UART},
+    smp::hart_id,
+    sync::{lock_order, SpinLock},
+};
+
+use self::{
+    block::BlockDevice,
+    plic::{IntrTargetPriority, PLIC},
+    ramdisk::RamDisk,
+    rtc::GoldfishRtc,
+    uart::NS16550a,
+    virtio_blk::VirtIOBlk,
+};
+
+pub mod block;
+pub mod plic;
+pub mod ramdisk;
+pub mod rtc;
+pub mod uart;
+pub mod virtio;
+pub mod virtio_blk;
+
+lazy_static! {
+    pub static ref PLIC_DEVICE: SpinLock<PLIC> = SpinLock::named(
+        unsafe { PLIC::new(VIRT_PLIC) },
+        "PLIC",
+        lock_order::PLIC,
+    );
+    pub static ref UART: NS16550a = unsafe { NS16550a::new(VIRT_UART) };
+
+    // ch4 新增
+    // QEMU 启动时没有挂载磁盘则为 None
+    pub static ref VIRTIO_BLK: Option<Arc<VirtIOBlk>> =
+        unsafe { VirtIOBlk::probe(VIRT_BLK) }.map(Arc::new);
+
+    // ch4 新增
+    // 由 build.rs 嵌入内核的磁盘镜像，没有嵌入时为 None
+    pub static ref EMBEDDED_RAMDISK: Option<Arc<RamDisk>> =
+        RamDisk::from_embedded_image().map(Arc::new);
+}
+
+pub static RTC: GoldfishRtc = unsafe { GoldfishRtc::new(VIRT_RTC) };
+
+/// 初始化外设，并开启 S 态的外部中断
+///
+/// 由启动 hart 调用，外设的中断只发送给启动 hart。
+pub fn init() {
+    UART.init();
+
+    match VIRTIO_BLK.as_ref() {
+        Some(blk) => println!("[kernel] virtio-blk: {} blocks", blk.num_blocks()),
+        None => println!("[kernel] virtio-blk: no device"),
+    }
+    if let Some(ramdisk) = EMBEDDED_RAMDISK.as_ref() {
+        println!("[kernel] ramdisk: {} blocks", ramdisk.num_blocks());
+    }
+    // block::block_device_test(&*block_device().unwrap()); // 测试（轮询方式）
+    // block::block_device_test(&RamDisk::with_frames(64).unwrap()); // 测试
+
+    let hart_id = hart_id();
+    let mut plic = PLIC_DEVICE.lock_irqsave();
+
+    // M 态不接收外设中断，S 态接收所有优先级大于 0 的中断
+    plic.set_threshold(hart_id, IntrTargetPriority::Machine, 1);
+    plic.set_threshold(hart_id, IntrTargetPriority::Supervisor, 0);
+
+    for intr_src_id in [UART_IRQ, VIRTIO_BLK_IRQ] {
+        plic.enable(hart_id, IntrTargetPriority::Supervisor, intr_src_id);
+        plic.set_priority(intr_src_id, 1);
+    }
+
+    unsafe {
+        sie::set_sext();
+    }
+
+    if let Some(blk) = VIRTIO_BLK.as_ref() {
+        blk.enable_irq();
+    }
+}
+
+// ch4 新增
+/// 内核使用的块设备：优先使用 virtio-blk 磁盘，其次是嵌入内核的磁盘镜像
+pub fn block_device() -> Option<Arc<dyn BlockDevice>> {
+    if let Some(blk) = VIRTIO_BLK.as_ref() {
+        return Some(blk.clone());
+    }
+    EMBEDDED_RAMDISK
+        .as_ref()
+        .map(|ramdisk| ramdisk.clone() as Arc<dyn BlockDevice>)
+}
+
+/// 处理 S 态外部中断：从 PLIC 领取中断源，交给相应的驱动处理
+pub fn handle_irq() {
+    let hart_id = hart_id();
+    let intr_src_id = PLIC_DEVICE
+        .lock_irqsave()
+        .claim(hart_id, IntrTargetPriority::Supervisor);
+
+    match intr_src_id as usize {
+        0 => {
+            // 中断已经被处理了（比如在轮询 UART 的时候）
+            return;
+        }
+        UART_IRQ => UART.handle_irq(),
+        VIRTIO_BLK_IRQ => {
+            if let Some(blk) = VIRTIO_BLK.as_ref() {
+                blk.handle_irq();
+            }
+        }
+        _ => panic!("unsupported IRQ {}", intr_src_id),
+    }
+
+    PLIC_DEVICE
+        .lock_irqsave()
+        .complete(hart_id, IntrTargetPriority::Supervisor, intr_src_id);
+}
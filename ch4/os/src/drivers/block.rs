@@ -0,0 +1,30 @@
+// 块设备的抽象
+//
+// ch4 MODIFY:
+// `BlockDevice` 定义在 easy-fs 里，这样文件系统既可以使用内核的块设备驱动，
+// 也可以在宿主机上使用普通文件（见 ../easy-fs-fuse）。
+
+pub use easy_fs::{BlockDevice, BLOCK_SIZE};
+
+/// 读写设备的最后一块，然后恢复原来的内容
+#[allow(unused)]
+pub fn block_device_test(device: &dyn BlockDevice) {
+    let block_id = device.num_blocks() - 1;
+
+    let mut origin = [0u8; BLOCK_SIZE];
+    device.read_block(block_id, &mut origin);
+
+    let mut pattern = [0u8; BLOCK_SIZE];
+    for (i, byte) in pattern.iter_mut().enumerate() {
+        *byte = (i * 7 + 3) as u8;
+    }
+    device.write_block(block_id, &pattern);
+
+    let mut buf = [0u8; BLOCK_SIZE];
+    device.read_block(block_id, &mut buf);
+    assert_eq!(buf, pattern);
+
+    device.write_block(block_id, &origin);
+
+    println!("block_device_test passed!");
+}
@@ -0,0 +1,106 @@
+// 内存里的块设备（RAM disk）
+//
+// 数据保存在内存里，不需要 QEMU 模拟任何设备，用于测试文件系统等上层模块。
+// 有两种存储方式：
+// - 从帧分配器申请的页面，初始内容全为 0，每个页面保存 8 块；
+// - 由 build.rs 嵌入到内核 `.data` 段的磁盘镜像（见 build.rs 的 `insert_ramdisk_image`），
+//   写入的数据只保存在内存里，不会写回镜像文件。
+
+use alloc::vec::Vec;
+
+use crate::{
+    config::PAGE_SIZE,
+    mm::{frame_alloc, FrameTracker},
+    sync::{lock_order, SpinLock},
+};
+
+use super::block::{BlockDevice, BLOCK_SIZE};
+
+const BLOCKS_PER_FRAME: usize = PAGE_SIZE / BLOCK_SIZE;
+
+enum Storage {
+    Frames(Vec<FrameTracker>),
+    Image(&'static mut [u8]),
+}
+
+impl Storage {
+    fn block(&mut self, block_id: usize) -> &mut [u8] {
+        match self {
+            Storage::Frames(frames) => {
+                let bytes = frames[block_id / BLOCKS_PER_FRAME].ppn.get_bytes_array();
+                let offset = block_id % BLOCKS_PER_FRAME * BLOCK_SIZE;
+                &mut bytes[offset..offset + BLOCK_SIZE]
+            }
+            Storage::Image(image) => &mut image[block_id * BLOCK_SIZE..(block_id + 1) * BLOCK_SIZE],
+        }
+    }
+}
+
+pub struct RamDisk {
+    num_blocks: usize,
+    storage: SpinLock<Storage>,
+}
+
+impl RamDisk {
+    fn new(num_blocks: usize, storage: Storage) -> Self {
+        Self {
+            num_blocks,
+            storage: SpinLock::named(storage, "RAMDISK", lock_order::BLOCK_DEVICE),
+        }
+    }
+
+    /// 创建一个 `num_blocks` 块、内容全为 0 的 RAM disk，内存不足时返回 None
+    pub fn with_frames(num_blocks: usize) -> Option<Self> {
+        let num_frames = num_blocks.div_ceil(BLOCKS_PER_FRAME);
+        // 页面之间不需要连续，逐个分配，这样可以使用已回收的页面
+        let frames = (0..num_frames)
+            .map(|_| frame_alloc())
+            .collect::<Option<Vec<FrameTracker>>>()?;
+        Some(Self::new(num_blocks, Storage::Frames(frames)))
+    }
+
+    /// 使用内核里嵌入的磁盘镜像，没有嵌入镜像时返回 None。
+    /// 镜像只能被一个 RAM disk 使用
+    pub fn from_embedded_image() -> Option<Self> {
+        extern "C" {
+            fn _ramdisk_start();
+            fn _ramdisk_end();
+        }
+
+        let start = _ramdisk_start as usize;
+        let len = _ramdisk_end as usize - start;
+        if len < BLOCK_SIZE {
+            return None;
+        }
+
+        let image = unsafe { core::slice::from_raw_parts_mut(start as *mut u8, len) };
+        Some(Self::new(len / BLOCK_SIZE, Storage::Image(image)))
+    }
+}
+
+impl BlockDevice for RamDisk {
+    fn read_block(&self, block_id: usize, buf: &mut [u8]) {
+        assert!(
+            block_id < self.num_blocks,
+            "ramdisk: block {} out of range",
+            block_id
+        );
+        buf.copy_from_slice(self.storage.lock_irqsave().block(block_id));
+    }
+
+    fn write_block(&self, block_id: usize, buf: &[u8]) {
+        assert!(
+            block_id < self.num_blocks,
+            "ramdisk: block {} out of range",
+            block_id
+        );
+        self.storage
+            .lock_irqsave()
+            .block(block_id)
+            .copy_from_slice(buf);
+    }
+
+    fn num_blocks(&self) -> usize {
+        self.num_blocks
+    }
+}
@@ -0,0 +1,99 @@
+// Goldfish RTC 驱动（QEMU virt 的实时时钟）
+//
+// 寄存器（每个 4 bytes，base 为 0x0010_1000）
+//
+// | offset | 名称       | 说明                                         |
+// |--------|------------|----------------------------------------------|
+// | 0x00   | TIME_LOW   | 当前时间（Unix epoch 以来的纳秒数）的低 32 位 |
+// | 0x04   | TIME_HIGH  | 当前时间的高 32 位                           |
+//
+// 读取 TIME_LOW 的同时设备会锁存当时的高 32 位，所以必须先读 TIME_LOW 再读 TIME_HIGH。
+// 闹钟和中断相关的寄存器目前没有用到。
+//
+// https://android.googlesource.com/platform/external/qemu/+/master/docs/GOLDFISH-VIRTUAL-HARDWARE.TXT
+
+use core::fmt::{self, Display, Formatter};
+
+const TIME_LOW: usize = 0x00;
+const TIME_HIGH: usize = 0x04;
+
+const SECS_PER_MINUTE: u64 = 60;
+const SECS_PER_HOUR: u64 = 60 * SECS_PER_MINUTE;
+const SECS_PER_DAY: u64 = 24 * SECS_PER_HOUR;
+
+pub struct GoldfishRtc {
+    base_addr: usize,
+}
+
+impl GoldfishRtc {
+    /// 调用者需确保 `base_addr` 是 RTC 的 MMIO 地址，并且已经映射到内核地址空间
+    pub const unsafe fn new(base_addr: usize) -> Self {
+        Self { base_addr }
+    }
+
+    fn read_reg(&self, offset: usize) -> u32 {
+        unsafe { ((self.base_addr + offset) as *const u32).read_volatile() }
+    }
+
+    /// Unix epoch（1970-01-01 00:00:00 UTC）以来的纳秒数
+    pub fn read_ns(&self) -> u64 {
+        let low = self.read_reg(TIME_LOW);
+        let high = self.read_reg(TIME_HIGH);
+        ((high as u64) << 32) | low as u64
+    }
+
+    /// 当前的日期和时间（UTC）
+    pub fn now(&self) -> DateTime {
+        DateTime::from_unix_secs(self.read_ns() / 1_000_000_000)
+    }
+}
+
+/// 日期和时间（UTC），用于打印日志
+pub struct DateTime {
+    pub year: u64,
+    pub month: u64, // 1 ~ 12
+    pub day: u64,   // 1 ~ 31
+    pub hour: u64,
+    pub minute: u64,
+    pub second: u64,
+}
+
+impl DateTime {
+    /// 把 Unix 时间戳换算为公历日期
+    ///
+    /// 以 3 月 1 日作为一年的开始，闰日就落在一年的最后，每 400 年（146097 天）为一个周期。
+    /// http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    pub fn from_unix_secs(secs: u64) -> Self {
+        let days = secs / SECS_PER_DAY;
+        let rem = secs % SECS_PER_DAY;
+
+        let z = days + 719468; // 从 0000-03-01 开始计算的天数
+        let era = z / 146097;
+        let doe = z - era * 146097; // 在 400 年周期中的第几天 [0, 146096]
+        let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365; // 第几年 [0, 399]
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100); // 在该年中的第几天 [0, 365]
+        let mp = (5 * doy + 2) / 153; // 从 3 月开始的第几个月 [0, 11]
+        let day = doy - (153 * mp + 2) / 5 + 1;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+        let year = yoe + era * 400 + u64::from(month <= 2); // 1、2 月属于下一年
+
+        Self {
+            year,
+            month,
+            day,
+            hour: rem / SECS_PER_HOUR,
+            minute: rem % SECS_PER_HOUR / SECS_PER_MINUTE,
+            second: rem % SECS_PER_MINUTE,
+        }
+    }
+}
+
+impl Display for DateTime {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC",
+            self.year, self.month, self.day, self.hour, self.minute, self.second
+        )
+    }
+}
@@ -0,0 +1,305 @@
+// 16550 UART（QEMU virt 模拟的是 NS16550A）驱动
+//
+// 寄存器（每个 1 byte，base 为 0x1000_0000）
+//
+// | offset | 读                         | 写                         |
+// |--------|----------------------------|----------------------------|
+// | 0      | RBR 接收缓冲               | THR 发送保持               |
+// | 1      | IER 中断使能               | IER 中断使能               |
+// | 2      | IIR 中断标识               | FCR FIFO 控制              |
+// | 3      | LCR 线路控制               | LCR 线路控制               |
+// | 4      | MCR Modem 控制             | MCR Modem 控制             |
+// | 5      | LSR 线路状态               | -                          |
+//
+// 当 LCR 的 DLAB 位为 1 时，offset 0 和 1 分别是波特率除数的低/高字节（DLL/DLM）。
+//
+// 之前的 `console_putchar` 每输出一个字符都需要通过 ecall 陷入 M 态的 SBI，
+// 现在内核直接读写 UART 的 MMIO 寄存器，并在内存里维护收发两个环形缓冲区：
+// - 接收：UART 产生中断时把硬件 FIFO 里的字符搬到 rx 缓冲区，并唤醒等待输入的任务；
+// - 发送：字符先放进 tx 缓冲区，UART 能接收时立即写入，来不及写入的字符由
+//   "发送保持寄存器空" 中断继续写入。
+//
+// http://byterunner.com/16550.html
+
+use alloc::collections::VecDeque;
+
+use crate::{
+    task::{
+        block_current_and_run_next, current_has_pending_signal, current_task_id, wakeup_task,
+    },
+    sync::{lock_order, SpinLock},
+};
+
+const RX_BUFFER_SIZE: usize = 256;
+const TX_BUFFER_SIZE: usize = 1024;
+
+const RBR: usize = 0;
+const THR: usize = 0;
+const DLL: usize = 0;
+const IER: usize = 1;
+const DLM: usize = 1;
+const FCR: usize = 2;
+const LCR: usize = 3;
+const MCR: usize = 4;
+const LSR: usize = 5;
+
+const IER_RX_AVAILABLE: u8 = 1 << 0;
+const IER_TX_EMPTY: u8 = 1 << 1;
+
+const FCR_FIFO_ENABLE: u8 = 1 << 0;
+const FCR_FIFO_CLEAR: u8 = 3 << 1;
+
+const LCR_EIGHT_BITS: u8 = 3;
+const LCR_DLAB: u8 = 1 << 7;
+
+const MCR_DATA_TERMINAL_READY: u8 = 1 << 0;
+const MCR_REQUEST_TO_SEND: u8 = 1 << 1;
+const MCR_AUX_OUTPUT2: u8 = 1 << 3; // 部分硬件需要设置 OUT2 才会把中断信号送出
+
+const LSR_DATA_AVAILABLE: u8 = 1 << 0;
+const LSR_THR_EMPTY: u8 = 1 << 5;
+
+/// 固定容量的环形缓冲区
+pub struct RingBuffer<const N: usize> {
+    buf: [u8; N],
+    head: usize, // 下一个读取的位置
+    tail: usize, // 下一个写入的位置
+    len: usize,
+}
+
+impl<const N: usize> RingBuffer<N> {
+    pub const fn new() -> Self {
+        Self {
+            buf: [0; N],
+            head: 0,
+            tail: 0,
+            len: 0,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.len == N
+    }
+
+    /// 缓冲区已满时返回 false，字符被丢弃
+    pub fn push(&mut self, c: u8) -> bool {
+        if self.is_full() {
+            return false;
+        }
+        self.buf[self.tail] = c;
+        self.tail = (self.tail + 1) % N;
+        self.len += 1;
+        true
+    }
+
+    pub fn pop(&mut self) -> Option<u8> {
+        if self.is_empty() {
+            return None;
+        }
+        let c = self.buf[self.head];
+        self.head = (self.head + 1) % N;
+        self.len -= 1;
+        Some(c)
+    }
+}
+
+pub struct NS16550a {
+    base_addr: usize,
+    inner: SpinLock<NS16550aInner>,
+}
+
+struct NS16550aInner {
+    rx_buffer: RingBuffer<RX_BUFFER_SIZE>,
+    tx_buffer: RingBuffer<TX_BUFFER_SIZE>,
+    rx_waiters: VecDeque<usize>, // 等待输入的任务的 id
+    ier: u8,                     // IER 寄存器的当前值
+}
+
+impl NS16550a {
+    /// 调用者需确保 `base_addr` 是 UART 的 MMIO 地址，并且已经映射到内核地址空间
+    pub unsafe fn new(base_addr: usize) -> Self {
+        Self {
+            base_addr,
+            inner: SpinLock::named(
+                NS16550aInner {
+                    rx_buffer: RingBuffer::new(),
+                    tx_buffer: RingBuffer::new(),
+                    rx_waiters: VecDeque::new(),
+                    ier: 0,
+                },
+                "UART",
+                lock_order::UART,
+            ),
+        }
+    }
+
+    fn read_reg(&self, offset: usize) -> u8 {
+        unsafe { ((self.base_addr + offset) as *const u8).read_volatile() }
+    }
+
+    fn write_reg(&self, offset: usize, value: u8) {
+        unsafe {
+            ((self.base_addr + offset) as *mut u8).write_volatile(value);
+        }
+    }
+
+    pub fn init(&self) {
+        let mut inner = self.inner.lock_irqsave();
+
+        // 先关闭所有中断
+        self.write_reg(IER, 0);
+
+        // 设置波特率除数（QEMU 会忽略该值），38400 = 115200 / 3
+        self.write_reg(LCR, LCR_DLAB);
+        self.write_reg(DLL, 3);
+        self.write_reg(DLM, 0);
+
+        // 8 位数据位，无校验位，1 位停止位，同时清除 DLAB 位
+        self.write_reg(LCR, LCR_EIGHT_BITS);
+
+        // 启用并清空收发 FIFO
+        self.write_reg(FCR, FCR_FIFO_ENABLE | FCR_FIFO_CLEAR);
+
+        self.write_reg(
+            MCR,
+            MCR_DATA_TERMINAL_READY | MCR_REQUEST_TO_SEND | MCR_AUX_OUTPUT2,
+        );
+
+        // 开启 "接收到数据" 中断，"发送保持寄存器空" 中断只在 tx 缓冲区有数据时才开启
+        inner.ier = IER_RX_AVAILABLE;
+        self.write_reg(IER, inner.ier);
+    }
+
+    /// 把硬件 FIFO 里已接收的字符全部搬到 rx 缓冲区
+    fn pull_rx(&self, inner: &mut NS16550aInner) {
+        while self.read_reg(LSR) & LSR_DATA_AVAILABLE != 0 {
+            let c = self.read_reg(RBR);
+            // 缓冲区满时丢弃新字符
+            inner.rx_buffer.push(c);
+        }
+    }
+
+    /// 把 tx 缓冲区的字符尽量写入硬件，
+    /// 如果还有剩余则开启 "发送保持寄存器空" 中断，由中断继续发送
+    fn push_tx(&self, inner: &mut NS16550aInner) {
+        while self.read_reg(LSR) & LSR_THR_EMPTY != 0 {
+            match inner.tx_buffer.pop() {
+                Some(c) => self.write_reg(THR, c),
+                None => break,
+            }
+        }
+
+        let ier = if inner.tx_buffer.is_empty() {
+            inner.ier & !IER_TX_EMPTY
+        } else {
+            inner.ier | IER_TX_EMPTY
+        };
+
+        if ier != inner.ier {
+            inner.ier = ier;
+            self.write_reg(IER, ier);
+        }
+    }
+
+    pub fn putchar(&self, c: u8) {
+        let mut inner = self.inner.lock_irqsave();
+
+        // tx 缓冲区已满（比如内核在关中断的情况下大量输出），
+        // 只能以轮询的方式等待硬件腾出空间
+        while inner.tx_buffer.is_full() {
+            while self.read_reg(LSR) & LSR_THR_EMPTY == 0 {}
+            let c = inner.tx_buffer.pop().unwrap();
+            self.write_reg(THR, c);
+        }
+
+        inner.tx_buffer.push(c);
+        self.push_tx(&mut inner);
+    }
+
+    /// 以轮询的方式把 tx 缓冲区的字符全部写入硬件，用于关机之前
+    pub fn flush(&self) {
+        let mut inner = self.inner.lock_irqsave();
+        self.drain_tx(&mut inner);
+    }
+
+    /// panic 时调用：UART 的锁没有被持有时，先把 tx 缓冲区的字符全部写入硬件，
+    /// 让它们出现在 panic 信息之前；锁正被持有（panic 可能就发生在持有锁的期间）时放弃这些字符
+    pub fn flush_for_panic(&self) {
+        if let Some(mut inner) = self.inner.try_lock_irqsave() {
+            self.drain_tx(&mut inner);
+        }
+    }
+
+    fn drain_tx(&self, inner: &mut NS16550aInner) {
+        while let Some(c) = inner.tx_buffer.pop() {
+            self.putchar_polling(c);
+        }
+        self.push_tx(inner);
+    }
+
+    /// 不经过 tx 缓冲区也不获取锁，以轮询的方式直接写入硬件，
+    /// 用于 panic 之后的输出（见 `console::enter_panic_mode`）
+    pub fn putchar_polling(&self, c: u8) {
+        while self.read_reg(LSR) & LSR_THR_EMPTY == 0 {}
+        self.write_reg(THR, c);
+    }
+
+    /// 读取一个字符，如果暂时没有输入则返回 None
+    pub fn getchar(&self) -> Option<u8> {
+        let mut inner = self.inner.lock_irqsave();
+        self.pull_rx(&mut inner);
+        inner.rx_buffer.pop()
+    }
+
+    /// 读取一个字符，如果暂时没有输入则阻塞当前任务，直到 UART 中断将其唤醒。
+    /// 如果等待期间当前任务收到了信号，则返回 None
+    ///
+    /// 访问当前任务需要获取 TaskManager 的锁，它排在 UART 的锁之前，
+    /// 所以只能在持有 UART 的锁之外进行。
+    pub fn getchar_blocking(&self) -> Option<u8> {
+        let current = current_task_id();
+        loop {
+            let pending_signal = current_has_pending_signal();
+
+            let mut inner = self.inner.lock_irqsave();
+            self.pull_rx(&mut inner);
+            if let Some(c) = inner.rx_buffer.pop() {
+                inner.rx_waiters.retain(|id| *id != current);
+                return Some(c);
+            }
+
+            if pending_signal {
+                inner.rx_waiters.retain(|id| *id != current);
+                return None;
+            }
+
+            if !inner.rx_waiters.contains(&current) {
+                inner.rx_waiters.push_back(current);
+            }
+            drop(inner);
+            block_current_and_run_next();
+        }
+    }
+
+    /// UART 中断处理：接收字符并唤醒等待输入的任务，继续发送 tx 缓冲区的字符
+    pub fn handle_irq(&self) {
+        let mut inner = self.inner.lock_irqsave();
+        self.pull_rx(&mut inner);
+        self.push_tx(&mut inner);
+
+        if inner.rx_buffer.is_empty() {
+            return;
+        }
+
+        let waiters: VecDeque<usize> = inner.rx_waiters.drain(..).collect();
+        drop(inner);
+
+        for task_id in waiters {
+            wakeup_task(task_id);
+        }
+    }
+}
@@ -0,0 +1,369 @@
+// virtio-mmio 传输层，以及 virtqueue
+//
+// QEMU virt 在 0x1000_1000 ~ 0x1000_8fff 有 8 个 virtio-mmio 插槽，每个占 0x1000，
+// 没有连接设备的插槽的 DeviceID 为 0。
+//
+// 寄存器（每个 4 bytes）
+//
+// | offset | 名称                 | 说明                                      |
+// |--------|----------------------|-------------------------------------------|
+// | 0x000  | MagicValue           | 固定为 0x74726976 ("virt")                |
+// | 0x004  | Version              | 1 为 legacy 接口，2 为 virtio 1.0 接口    |
+// | 0x008  | DeviceID             | 2 为块设备                                |
+// | 0x010  | DeviceFeatures       | 由 DeviceFeaturesSel 选择高/低 32 位      |
+// | 0x020  | DriverFeatures       | 由 DriverFeaturesSel 选择高/低 32 位      |
+// | 0x028  | GuestPageSize        | 仅 legacy                                 |
+// | 0x030  | QueueSel             |                                           |
+// | 0x034  | QueueNumMax          |                                           |
+// | 0x038  | QueueNum             |                                           |
+// | 0x03c  | QueueAlign           | 仅 legacy                                 |
+// | 0x040  | QueuePFN             | 仅 legacy，virtqueue 所在的物理页面号     |
+// | 0x044  | QueueReady           | 仅 1.0                                    |
+// | 0x050  | QueueNotify          | 写入 virtqueue 的编号，通知设备处理请求   |
+// | 0x060  | InterruptStatus      |                                           |
+// | 0x064  | InterruptACK         |                                           |
+// | 0x070  | Status               | 设备状态，驱动初始化过程中逐步设置        |
+// | 0x080  | QueueDescLow/High    | 仅 1.0，描述符表的物理地址                |
+// | 0x090  | QueueDriverLow/High  | 仅 1.0，available ring 的物理地址         |
+// | 0x0a0  | QueueDeviceLow/High  | 仅 1.0，used ring 的物理地址              |
+// | 0x100  | Config               | 设备相关的配置空间                        |
+//
+// QEMU 默认提供的是 legacy 接口，加上 `-global virtio-mmio.force-legacy=false` 则为 1.0 接口，
+// 这里两种都支持。
+//
+// 一个 virtqueue 由三部分组成：
+// - 描述符表：每个描述符指向一段缓冲区，一个请求由多个描述符通过 `next` 串成一条链；
+// - available ring：驱动把请求的第一个描述符的编号放进去，交给设备；
+// - used ring：设备处理完请求之后，把第一个描述符的编号放进去，还给驱动。
+//
+// legacy 接口要求 used ring 从下一个对齐（QueueAlign）的位置开始，
+// 所以 virtqueue 占用两个物理地址连续的页面：
+// 第一个页面放描述符表和 available ring，第二个页面放 used ring。
+//
+// https://docs.oasis-open.org/virtio/virtio/v1.1/virtio-v1.1.html
+
+use core::sync::atomic::{fence, Ordering};
+
+use alloc::vec::Vec;
+
+use crate::{
+    config::PAGE_SIZE,
+    mm::{address::PhysAddr, frame_alloc_contiguous, FrameTracker},
+};
+
+const MAGIC_VALUE: usize = 0x000;
+const VERSION: usize = 0x004;
+const DEVICE_ID: usize = 0x008;
+const DEVICE_FEATURES: usize = 0x010;
+const DEVICE_FEATURES_SEL: usize = 0x014;
+const DRIVER_FEATURES: usize = 0x020;
+const DRIVER_FEATURES_SEL: usize = 0x024;
+const GUEST_PAGE_SIZE: usize = 0x028;
+const QUEUE_SEL: usize = 0x030;
+const QUEUE_NUM_MAX: usize = 0x034;
+const QUEUE_NUM: usize = 0x038;
+const QUEUE_ALIGN: usize = 0x03c;
+const QUEUE_PFN: usize = 0x040;
+const QUEUE_READY: usize = 0x044;
+const QUEUE_NOTIFY: usize = 0x050;
+const INTERRUPT_STATUS: usize = 0x060;
+const INTERRUPT_ACK: usize = 0x064;
+const STATUS: usize = 0x070;
+const QUEUE_DESC_LOW: usize = 0x080;
+const QUEUE_DESC_HIGH: usize = 0x084;
+const QUEUE_DRIVER_LOW: usize = 0x090;
+const QUEUE_DRIVER_HIGH: usize = 0x094;
+const QUEUE_DEVICE_LOW: usize = 0x0a0;
+const QUEUE_DEVICE_HIGH: usize = 0x0a4;
+const CONFIG: usize = 0x100;
+
+const MAGIC: u32 = 0x7472_6976;
+
+const STATUS_ACKNOWLEDGE: u32 = 1;
+const STATUS_DRIVER: u32 = 2;
+const STATUS_DRIVER_OK: u32 = 4;
+const STATUS_FEATURES_OK: u32 = 8;
+
+const FEATURE_VERSION_1: u64 = 1 << 32;
+
+const DESC_F_NEXT: u16 = 1;
+const DESC_F_WRITE: u16 = 2; // 设备写入（否则为设备读取）
+
+/// virtqueue 的描述符数量
+pub const QUEUE_SIZE: usize = 16;
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct Descriptor {
+    addr: u64,
+    len: u32,
+    flags: u16,
+    next: u16,
+}
+
+#[repr(C)]
+struct AvailRing {
+    flags: u16,
+    idx: u16,
+    ring: [u16; QUEUE_SIZE],
+    used_event: u16,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct UsedElem {
+    id: u32,
+    len: u32,
+}
+
+#[repr(C)]
+struct UsedRing {
+    flags: u16,
+    idx: u16,
+    ring: [UsedElem; QUEUE_SIZE],
+    avail_event: u16,
+}
+
+/// virtqueue 的第一个页面
+#[repr(C)]
+struct DriverArea {
+    desc: [Descriptor; QUEUE_SIZE],
+    avail: AvailRing,
+}
+
+/// 交给设备的一段缓冲区：(物理地址, 长度, 是否由设备写入)
+pub type DmaBuffer = (usize, usize, bool);
+
+pub struct VirtQueue {
+    frames: Vec<FrameTracker>,
+    free_desc: Vec<u16>,
+    avail_idx: u16,     // 下一个放进 available ring 的位置
+    last_used_idx: u16, // 下一个从 used ring 取出的位置
+}
+
+impl VirtQueue {
+    pub fn new() -> Option<Self> {
+        let frames = frame_alloc_contiguous(2)?;
+        Some(Self {
+            frames,
+            free_desc: (0..QUEUE_SIZE as u16).rev().collect(),
+            avail_idx: 0,
+            last_used_idx: 0,
+        })
+    }
+
+    fn driver_area(&self) -> &'static mut DriverArea {
+        self.frames[0].ppn.get_mut()
+    }
+
+    fn device_area(&self) -> &'static mut UsedRing {
+        self.frames[1].ppn.get_mut()
+    }
+
+    fn desc_pa(&self) -> usize {
+        PhysAddr::from(self.frames[0].ppn).0
+    }
+
+    fn avail_pa(&self) -> usize {
+        self.desc_pa() + core::mem::size_of::<[Descriptor; QUEUE_SIZE]>()
+    }
+
+    fn used_pa(&self) -> usize {
+        PhysAddr::from(self.frames[1].ppn).0
+    }
+
+    /// 空闲的描述符数量
+    pub fn num_free(&self) -> usize {
+        self.free_desc.len()
+    }
+
+    /// 把一组缓冲区串成一个请求放进 available ring，返回第一个描述符的编号。
+    /// 描述符不够时返回 None
+    pub fn add(&mut self, buffers: &[DmaBuffer]) -> Option<u16> {
+        if buffers.is_empty() || buffers.len() > self.free_desc.len() {
+            return None;
+        }
+
+        let area = self.driver_area();
+        let ids: Vec<u16> = (0..buffers.len())
+            .map(|_| self.free_desc.pop().unwrap())
+            .collect();
+
+        for (i, &(addr, len, device_writable)) in buffers.iter().enumerate() {
+            let mut flags = if device_writable { DESC_F_WRITE } else { 0 };
+            let next = if i + 1 < ids.len() {
+                flags |= DESC_F_NEXT;
+                ids[i + 1]
+            } else {
+                0
+            };
+            area.desc[ids[i] as usize] = Descriptor {
+                addr: addr as u64,
+                len: len as u32,
+                flags,
+                next,
+            };
+        }
+
+        let head = ids[0];
+        area.avail.ring[self.avail_idx as usize % QUEUE_SIZE] = head;
+
+        // 设备必须先看到描述符和 ring 的内容，再看到新的 idx
+        fence(Ordering::SeqCst);
+        self.avail_idx = self.avail_idx.wrapping_add(1);
+        unsafe {
+            core::ptr::write_volatile(&mut area.avail.idx, self.avail_idx);
+        }
+        fence(Ordering::SeqCst);
+
+        Some(head)
+    }
+
+    /// 从 used ring 取出一个已完成的请求，回收它的描述符，返回第一个描述符的编号
+    pub fn pop_used(&mut self) -> Option<u16> {
+        let used = self.device_area();
+        let used_idx = unsafe { core::ptr::read_volatile(&used.idx) };
+        if used_idx == self.last_used_idx {
+            return None;
+        }
+        fence(Ordering::SeqCst);
+
+        let elem = used.ring[self.last_used_idx as usize % QUEUE_SIZE];
+        self.last_used_idx = self.last_used_idx.wrapping_add(1);
+
+        let head = elem.id as u16;
+        let desc = &self.driver_area().desc;
+        let mut id = head;
+        loop {
+            self.free_desc.push(id);
+            if desc[id as usize].flags & DESC_F_NEXT == 0 {
+                break;
+            }
+            id = desc[id as usize].next;
+        }
+
+        Some(head)
+    }
+}
+
+/// 一个 virtio-mmio 设备的寄存器
+pub struct VirtIOHeader {
+    base_addr: usize,
+    version: u32,
+}
+
+impl VirtIOHeader {
+    /// 检查 `base_addr` 处是否为类型为 `device_id` 的 virtio 设备，
+    /// 调用者需确保 `base_addr` 已经映射到内核地址空间
+    pub unsafe fn probe(base_addr: usize, device_id: u32) -> Option<Self> {
+        let header = Self {
+            base_addr,
+            version: 0,
+        };
+        let version = header.read(VERSION);
+        if header.read(MAGIC_VALUE) != MAGIC
+            || !(1..=2).contains(&version)
+            || header.read(DEVICE_ID) != device_id
+        {
+            return None;
+        }
+        Some(Self { base_addr, version })
+    }
+
+    fn read(&self, offset: usize) -> u32 {
+        unsafe { ((self.base_addr + offset) as *const u32).read_volatile() }
+    }
+
+    fn write(&self, offset: usize, value: u32) {
+        unsafe {
+            ((self.base_addr + offset) as *mut u32).write_volatile(value);
+        }
+    }
+
+    fn is_legacy(&self) -> bool {
+        self.version == 1
+    }
+
+    /// 重置设备并协商特性，`negotiate` 根据设备提供的特性返回驱动接受的特性。
+    /// 设备不接受时返回 false
+    pub fn begin_init(&self, negotiate: impl FnOnce(u64) -> u64) -> bool {
+        self.write(STATUS, 0);
+        self.write(STATUS, STATUS_ACKNOWLEDGE);
+        self.write(STATUS, STATUS_ACKNOWLEDGE | STATUS_DRIVER);
+
+        self.write(DEVICE_FEATURES_SEL, 0);
+        let low = self.read(DEVICE_FEATURES) as u64;
+        self.write(DEVICE_FEATURES_SEL, 1);
+        let high = self.read(DEVICE_FEATURES) as u64;
+
+        let mut features = negotiate(low | (high << 32));
+        if !self.is_legacy() {
+            // 1.0 接口必须接受 VERSION_1
+            features |= FEATURE_VERSION_1;
+        }
+
+        self.write(DRIVER_FEATURES_SEL, 0);
+        self.write(DRIVER_FEATURES, features as u32);
+        self.write(DRIVER_FEATURES_SEL, 1);
+        self.write(DRIVER_FEATURES, (features >> 32) as u32);
+
+        if self.is_legacy() {
+            self.write(GUEST_PAGE_SIZE, PAGE_SIZE as u32);
+            return true;
+        }
+
+        let status = STATUS_ACKNOWLEDGE | STATUS_DRIVER | STATUS_FEATURES_OK;
+        self.write(STATUS, status);
+        self.read(STATUS) & STATUS_FEATURES_OK != 0
+    }
+
+    /// 把 virtqueue 的地址告诉设备，设备支持的描述符数量不够时返回 false
+    pub fn setup_queue(&self, queue_idx: u32, queue: &VirtQueue) -> bool {
+        self.write(QUEUE_SEL, queue_idx);
+        if (self.read(QUEUE_NUM_MAX) as usize) < QUEUE_SIZE {
+            return false;
+        }
+        self.write(QUEUE_NUM, QUEUE_SIZE as u32);
+
+        if self.is_legacy() {
+            self.write(QUEUE_ALIGN, PAGE_SIZE as u32);
+            self.write(QUEUE_PFN, queue.frames[0].ppn.0 as u32);
+        } else {
+            let (desc, avail, used) = (queue.desc_pa(), queue.avail_pa(), queue.used_pa());
+            self.write(QUEUE_DESC_LOW, desc as u32);
+            self.write(QUEUE_DESC_HIGH, (desc >> 32) as u32);
+            self.write(QUEUE_DRIVER_LOW, avail as u32);
+            self.write(QUEUE_DRIVER_HIGH, (avail >> 32) as u32);
+            self.write(QUEUE_DEVICE_LOW, used as u32);
+            self.write(QUEUE_DEVICE_HIGH, (used >> 32) as u32);
+            self.write(QUEUE_READY, 1);
+        }
+        true
+    }
+
+    /// 初始化完成，设备开始工作
+    pub fn finish_init(&self) {
+        let status = self.read(STATUS);
+        self.write(STATUS, status | STATUS_DRIVER_OK);
+    }
+
+    /// 通知设备处理 virtqueue 里的新请求
+    pub fn notify(&self, queue_idx: u32) {
+        self.write(QUEUE_NOTIFY, queue_idx);
+    }
+
+    /// 确认设备的中断，返回是否有中断
+    pub fn ack_interrupt(&self) -> bool {
+        let status = self.read(INTERRUPT_STATUS);
+        if status == 0 {
+            return false;
+        }
+        self.write(INTERRUPT_ACK, status);
+        true
+    }
+
+    /// 读取配置空间里 `offset` 处的 u32
+    pub fn read_config(&self, offset: usize) -> u32 {
+        self.read(CONFIG + offset)
+    }
+}
@@ -0,0 +1,314 @@
+// virtio-blk 块设备驱动
+//
+// 每个读写请求由三个描述符组成：
+//
+// | 描述符 | 内容                                           | 方向     |
+// |--------|------------------------------------------------|----------|
+// | 1      | 请求头 { type: u32, reserved: u32, sector: u64 } | 设备读取 |
+// | 2      | 512 bytes 的数据                               | 读请求为设备写入，写请求为设备读取 |
+// | 3      | 1 byte 的状态，0 表示成功                      | 设备写入 |
+//
+// 设备通过 DMA 直接访问物理内存，而调用者的缓冲区可能位于用户地址空间或者跨页，
+// 所以驱动从帧分配器申请一个页面，划分成若干个请求槽，每个槽包含上面三段缓冲区，
+// 读写的数据在槽和调用者的缓冲区之间复制。
+// 内核恒等映射了可分配的物理内存，所以槽的虚拟地址就是物理地址。
+//
+// 等待请求完成有两种方式：
+// - 轮询：不断检查 used ring，用于内核初始化阶段（此时还没有任务可以阻塞）
+//   或者尚未开启设备中断的时候；
+// - 中断：阻塞当前任务，设备完成请求之后产生中断，由 `handle_irq` 唤醒任务。
+
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use alloc::{collections::VecDeque, vec::Vec};
+
+use crate::{
+    mm::{frame_alloc_contiguous, FrameTracker},
+    sync::{lock_order, SpinLock},
+    task::{block_current_and_run_next, current_task_id, has_current_task, wakeup_task},
+};
+
+use super::{
+    block::{BlockDevice, BLOCK_SIZE},
+    virtio::{VirtIOHeader, VirtQueue, QUEUE_SIZE},
+};
+
+const VIRTIO_DEVICE_ID_BLOCK: u32 = 2;
+
+const VIRTIO_BLK_T_IN: u32 = 0; // 读
+const VIRTIO_BLK_T_OUT: u32 = 1; // 写
+
+const VIRTIO_BLK_S_OK: u8 = 0;
+
+// 每个请求占用 3 个描述符
+const NUM_SLOTS: usize = 4;
+const _: () = assert!(NUM_SLOTS * 3 <= QUEUE_SIZE);
+
+#[repr(C)]
+struct BlkReqHeader {
+    req_type: u32,
+    reserved: u32,
+    sector: u64,
+}
+
+#[repr(C)]
+struct SlotBuffer {
+    header: BlkReqHeader,
+    data: [u8; BLOCK_SIZE],
+    status: u8,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum SlotState {
+    Free,
+    InFlight,
+    Done,
+}
+
+#[derive(Clone, Copy)]
+struct Slot {
+    state: SlotState,
+    head: u16,             // 请求的第一个描述符的编号
+    waiter: Option<usize>, // 等待该请求完成的任务的 id
+}
+
+pub struct VirtIOBlk {
+    header: VirtIOHeader,
+    capacity: usize, // 总扇区数
+    irq_enabled: AtomicBool,
+    inner: SpinLock<VirtIOBlkInner>,
+}
+
+struct VirtIOBlkInner {
+    queue: VirtQueue,
+    dma: FrameTracker,
+    slots: [Slot; NUM_SLOTS],
+    slot_waiters: VecDeque<usize>, // 等待空闲槽的任务的 id
+}
+
+impl VirtIOBlkInner {
+    fn buffer(&self, slot_id: usize) -> &'static mut SlotBuffer {
+        let buffers: &'static mut [SlotBuffer; NUM_SLOTS] = self.dma.ppn.get_mut();
+        &mut buffers[slot_id]
+    }
+
+    /// 处理 used ring 里已完成的请求，返回需要唤醒的任务
+    fn process_used(&mut self) -> Vec<usize> {
+        let mut wakeups = Vec::new();
+        while let Some(head) = self.queue.pop_used() {
+            let slot = self
+                .slots
+                .iter_mut()
+                .find(|slot| slot.state == SlotState::InFlight && slot.head == head)
+                .expect("virtio-blk: unknown request completed");
+            slot.state = SlotState::Done;
+            if let Some(task_id) = slot.waiter.take() {
+                wakeups.push(task_id);
+            }
+        }
+        wakeups
+    }
+}
+
+impl VirtIOBlk {
+    /// 探测并初始化 `base_addr` 处的 virtio-blk 设备，没有设备时返回 None。
+    /// 调用者需确保 `base_addr` 已经映射到内核地址空间
+    pub unsafe fn probe(base_addr: usize) -> Option<Self> {
+        let header = VirtIOHeader::probe(base_addr, VIRTIO_DEVICE_ID_BLOCK)?;
+
+        // 不需要任何可选特性
+        if !header.begin_init(|_| 0) {
+            return None;
+        }
+
+        let queue = VirtQueue::new()?;
+        if !header.setup_queue(0, &queue) {
+            return None;
+        }
+
+        let dma = frame_alloc_contiguous(1)?.pop().unwrap();
+
+        // 配置空间的第一个字段是 u64 的扇区数
+        let capacity = header.read_config(0) as usize | ((header.read_config(4) as usize) << 32);
+
+        header.finish_init();
+
+        Some(Self {
+            header,
+            capacity,
+            irq_enabled: AtomicBool::new(false),
+            inner: SpinLock::named(
+                VirtIOBlkInner {
+                    queue,
+                    dma,
+                    slots: [Slot {
+                        state: SlotState::Free,
+                        head: 0,
+                        waiter: None,
+                    }; NUM_SLOTS],
+                    slot_waiters: VecDeque::new(),
+                },
+                "VIRTIO_BLK",
+                lock_order::BLOCK_DEVICE,
+            ),
+        })
+    }
+
+    /// 在 PLIC 里开启设备中断之后调用，之后的请求（如果有当前任务）以中断的方式等待完成
+    pub fn enable_irq(&self) {
+        self.irq_enabled.store(true, Ordering::Release);
+    }
+
+    /// 返回 Some(task_id) 表示以中断的方式等待，None 表示轮询
+    fn waiter(&self) -> Option<usize> {
+        if self.irq_enabled.load(Ordering::Acquire) && has_current_task() {
+            Some(current_task_id())
+        } else {
+            None
+        }
+    }
+
+    /// 提交一个请求，返回所占用的槽
+    fn submit(&self, req_type: u32, block_id: usize, data: Option<&[u8]>) -> usize {
+        assert!(
+            block_id < self.capacity,
+            "virtio-blk: block {} out of range",
+            block_id
+        );
+
+        let waiter = self.waiter();
+        loop {
+            let mut inner = self.inner.lock_irqsave();
+
+            if let Some(slot_id) = inner
+                .slots
+                .iter()
+                .position(|slot| slot.state == SlotState::Free)
+            {
+                let buffer = inner.buffer(slot_id);
+                buffer.header = BlkReqHeader {
+                    req_type,
+                    reserved: 0,
+                    sector: block_id as u64,
+                };
+                if let Some(data) = data {
+                    buffer.data.copy_from_slice(data);
+                }
+                buffer.status = 0xff;
+
+                let head = inner
+                    .queue
+                    .add(&[
+                        (&buffer.header as *const _ as usize, 16, false),
+                        (
+                            buffer.data.as_ptr() as usize,
+                            BLOCK_SIZE,
+                            req_type == VIRTIO_BLK_T_IN,
+                        ),
+                        (&buffer.status as *const _ as usize, 1, true),
+                    ])
+                    .unwrap();
+
+                inner.slots[slot_id] = Slot {
+                    state: SlotState::InFlight,
+                    head,
+                    waiter,
+                };
+                self.header.notify(0);
+                return slot_id;
+            }
+
+            // 没有空闲的槽
+            match waiter {
+                Some(task_id) => {
+                    inner.slot_waiters.push_back(task_id);
+                    drop(inner);
+                    block_current_and_run_next();
+                }
+                None => {
+                    let wakeups = inner.process_used();
+                    drop(inner);
+                    wakeup_all(wakeups);
+                }
+            }
+        }
+    }
+
+    /// 等待请求完成，然后把数据复制到 `data`（如果有）并释放槽
+    fn wait(&self, slot_id: usize, block_id: usize, data: Option<&mut [u8]>) {
+        let waiter = self.waiter();
+        loop {
+            let mut inner = self.inner.lock_irqsave();
+
+            if waiter.is_none() {
+                let wakeups = inner.process_used();
+                if !wakeups.is_empty() {
+                    drop(inner);
+                    wakeup_all(wakeups);
+                    continue;
+                }
+            }
+
+            if inner.slots[slot_id].state == SlotState::Done {
+                let buffer = inner.buffer(slot_id);
+                if buffer.status != VIRTIO_BLK_S_OK {
+                    panic!(
+                        "virtio-blk: request for block {} failed with status {}",
+                        block_id, buffer.status
+                    );
+                }
+                if let Some(data) = data {
+                    data.copy_from_slice(&buffer.data);
+                }
+
+                inner.slots[slot_id].state = SlotState::Free;
+                let wakeups: Vec<usize> = inner.slot_waiters.drain(..).collect();
+                drop(inner);
+                wakeup_all(wakeups);
+                return;
+            }
+
+            match waiter {
+                Some(task_id) => {
+                    // 中断可能在上一次被唤醒之后、重新加锁之前再次清空了 waiter
+                    inner.slots[slot_id].waiter = Some(task_id);
+                    drop(inner);
+                    block_current_and_run_next();
+                }
+                None => {
+                    drop(inner);
+                    core::hint::spin_loop();
+                }
+            }
+        }
+    }
+
+    /// 设备中断处理：确认中断，唤醒请求已完成的任务
+    pub fn handle_irq(&self) {
+        self.header.ack_interrupt();
+        let wakeups = self.inner.lock_irqsave().process_used();
+        wakeup_all(wakeups);
+    }
+}
+
+fn wakeup_all(task_ids: Vec<usize>) {
+    for task_id in task_ids {
+        wakeup_task(task_id);
+    }
+}
+
+impl BlockDevice for VirtIOBlk {
+    fn read_block(&self, block_id: usize, buf: &mut [u8]) {
+        let slot_id = self.submit(VIRTIO_BLK_T_IN, block_id, None);
+        self.wait(slot_id, block_id, Some(buf));
+    }
+
+    fn write_block(&self, block_id: usize, buf: &[u8]) {
+        let slot_id = self.submit(VIRTIO_BLK_T_OUT, block_id, Some(buf));
+        self.wait(slot_id, block_id, None);
+    }
+
+    fn num_blocks(&self) -> usize {
+        self.capacity
+    }
+}
@@ -1,12 +1,38 @@
+    # 每个 hart 进入内核时：a0 = hart id，a1 = 设备树地址（或者 hart_start 的 opaque 参数）
+    #
+    # tp 在内核里始终保存当前 hart 的 id（见 `task/processor.rs` 的 `hart_id()`），
+    # 每个 hart 使用各自的启动栈：sp = boot_stack_top - hart_id * 64KB
+
     .section .text.entry
     .globl _start
 _start:
+    mv tp, a0
+    li t0, 8                    # MAX_HARTS，需要跟 config.rs 保持一致
+    bgeu a0, t0, park
     la sp, boot_stack_top
+    slli t0, a0, 16
+    sub sp, sp, t0
     call rust_main
 
+    # 从 hart 通过 SBI HSM 扩展的 hart_start 从这里开始运行（此时尚未开启分页）
+    .globl _start_secondary
+_start_secondary:
+    mv tp, a0
+    li t0, 8
+    bgeu a0, t0, park
+    la sp, boot_stack_top
+    slli t0, a0, 16
+    sub sp, sp, t0
+    call rust_main_secondary
+
+    # hart id 超出了支持的范围
+park:
+    wfi
+    j park
+
     .section .bss.stack
     .globl boot_stack
 boot_stack:
-    .space 4096 * 16
+    .space 4096 * 16 * 8        # 64KB * MAX_HARTS
     .globl boot_stack_top
-boot_stack_top:
\ No newline at end of file
+boot_stack_top:
@@ -0,0 +1,137 @@
+// ch4 新增
+// 文件系统
+//
+// 内核启动时打开块设备（virtio-blk 磁盘或者嵌入内核的磁盘镜像）上的 easy-fs，
+// 应用程序从根目录里加载（见 `loader.rs`）。
+//
+// 进程通过文件描述符访问各种可以读写的对象（标准输入输出、管道、文件等），
+// 它们都实现了 `File` trait，保存在进程的文件描述符表里（`ProcessControlBlock::fd_table`）。
+//
+// easy-fs 内部使用自旋锁，而读写 virtio-blk 磁盘时当前任务会被阻塞，
+// 如果此时另一个任务也访问文件系统，它会一直自旋等待（单核时即死锁）。
+// 所以系统调用对文件系统的访问都通过 `with_fs` 进行：由一个会阻塞的锁保证同一时刻只有一个任务在访问。
+
+use alloc::{sync::Arc, vec, vec::Vec};
+
+use easy_fs::{EasyFileSystem, Inode};
+use lazy_static::lazy_static;
+
+use crate::{
+    drivers::{block_device, ramdisk::RamDisk},
+    sync::Mutex,
+    syscall::errno::{Errno, SyscallResult},
+};
+
+pub use self::{
+    inode::{open_file, OpenFlags},
+    pipe::make_pipe,
+    stdio::{Stdin, Stdout},
+};
+
+mod inode;
+mod pipe;
+mod stdio;
+
+// 文件类型，用于 `Stat::mode`
+pub const S_IFIFO: u32 = 0o010000;
+pub const S_IFCHR: u32 = 0o020000;
+pub const S_IFDIR: u32 = 0o040000;
+pub const S_IFREG: u32 = 0o100000;
+
+// `lseek` 的 whence
+pub const SEEK_SET: usize = 0;
+pub const SEEK_CUR: usize = 1;
+pub const SEEK_END: usize = 2;
+
+/// 文件的状态，由 `fstat` 写入应用程序的内存，所以布局需要跟用户库保持一致
+#[derive(Copy, Clone, Default)]
+#[repr(C)]
+pub struct Stat {
+    pub dev: u64,    // 文件所在的设备，目前只有一个文件系统，总是 0
+    pub ino: u64,    // inode 编号，不在文件系统里的文件为 0
+    pub mode: u32,   // 文件类型
+    pub nlink: u32,  // 硬链接的数量
+    pub size: u64,   // 文件的大小（bytes）
+    pub blocks: u64, // 占用的块数，包括索引块
+}
+
+impl Stat {
+    /// 不在文件系统里的文件（标准输入输出、管道）
+    pub fn special(mode: u32) -> Self {
+        Self {
+            mode,
+            nlink: 1,
+            ..Self::default()
+        }
+    }
+}
+
+/// 可以通过文件描述符读写的对象
+pub trait File: Send + Sync {
+    fn readable(&self) -> bool;
+    fn writable(&self) -> bool;
+
+    /// 读取数据到 `buf`，返回实际读取的字节数，0 表示已经到达末尾
+    ///
+    /// `buf` 是内核的缓冲区：读写可能会阻塞，阻塞期间应用程序的缓冲区可能被取消映射，
+    /// 所以由 `sys_read`/`sys_write` 负责在内核和应用程序之间复制数据。
+    fn read(&self, buf: &mut [u8]) -> SyscallResult;
+
+    /// 写入 `buf` 里的数据，返回实际写入的字节数
+    fn write(&self, buf: &[u8]) -> SyscallResult;
+
+    fn stat(&self) -> Result<Stat, Errno>;
+
+    /// 移动读写的位置，返回新的位置；默认不支持（比如管道）
+    fn seek(&self, _offset: isize, _whence: usize) -> SyscallResult {
+        Err(Errno::ESPIPE)
+    }
+}
+
+lazy_static! {
+    static ref FS_LOCK: Mutex = Mutex::new();
+}
+
+/// 持有文件系统的锁执行 `f`，等待锁的期间收到信号时返回 EINTR
+pub fn with_fs<T>(f: impl FnOnce() -> T) -> Result<T, Errno> {
+    FS_LOCK.lock()?;
+    let ret = f();
+    FS_LOCK.unlock().unwrap();
+    Ok(ret)
+}
+
+lazy_static! {
+    pub static ref ROOT_INODE: Arc<Inode> = {
+        let device = block_device().expect("no block device (see ch4/os/run)");
+        let efs = EasyFileSystem::open(device).expect("the block device is not an easy-fs image");
+        Arc::new(EasyFileSystem::root_inode(&efs))
+    };
+}
+
+/// 读取文件的全部内容
+pub fn read_all(inode: &Inode) -> Vec<u8> {
+    let mut data = vec![0u8; inode.size()];
+    let len = inode.read_at(0, &mut data);
+    data.truncate(len);
+    data
+}
+
+/// 在 RAM disk 上格式化一个文件系统，然后创建、读写、重新打开
+#[allow(unused)]
+pub fn fs_test() {
+    let device = Arc::new(RamDisk::with_frames(2048).unwrap());
+    let efs = EasyFileSystem::create(device.clone(), 2048, 1);
+    let root = EasyFileSystem::root_inode(&efs);
+
+    let data: Vec<u8> = (0..20000).map(|i| (i % 251) as u8).collect();
+    let inode = root.create("test").unwrap();
+    assert!(root.create("test").is_none());
+    assert_eq!(inode.write_at(0, &data), data.len());
+
+    let efs = EasyFileSystem::open(device).unwrap();
+    let root = EasyFileSystem::root_inode(&efs);
+    assert_eq!(root.ls(), ["test"]);
+    assert_eq!(read_all(&root.find("test").unwrap()), data);
+
+    println!("fs_test passed!");
+}
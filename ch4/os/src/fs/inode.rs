@@ -0,0 +1,149 @@
+// 文件系统里的文件
+//
+// 每次打开文件都会创建一个新的 `OSInode`，它有自己的读写位置，
+// 多个 `OSInode` 可以对应同一个磁盘上的文件（easy-fs 的 `Inode`）。
+// 所有对文件的访问都在文件系统的锁里进行（见 `with_fs`）。
+
+use alloc::sync::Arc;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use bitflags::bitflags;
+use easy_fs::{DiskInode, Inode, NAME_LENGTH_LIMIT};
+
+use crate::syscall::errno::{Errno, SyscallResult};
+
+use super::{with_fs, File, Stat, ROOT_INODE, SEEK_CUR, SEEK_END, SEEK_SET, S_IFDIR, S_IFREG};
+
+bitflags! {
+    /// `openat` 的 flags，数值跟 Linux 一致
+    pub struct OpenFlags: u32 {
+        const RDONLY = 0;
+        const WRONLY = 1 << 0;
+        const RDWR   = 1 << 1;
+        const CREAT  = 1 << 6;
+        const TRUNC  = 1 << 9;
+        const APPEND = 1 << 10;
+    }
+}
+
+impl OpenFlags {
+    /// 访问模式：(可读, 可写)，无效时返回 None
+    fn read_write(&self) -> Option<(bool, bool)> {
+        match self.bits() & 0b11 {
+            0 => Some((true, false)),
+            1 => Some((false, true)),
+            2 => Some((true, true)),
+            _ => None,
+        }
+    }
+}
+
+pub struct OSInode {
+    readable: bool,
+    writable: bool,
+    append: bool, // 每次写入之前把读写位置移到文件末尾
+    offset: AtomicUsize,
+    inode: Arc<Inode>,
+}
+
+/// 打开根目录里名为 `name` 的文件
+pub fn open_file(name: &str, flags: OpenFlags) -> Result<Arc<OSInode>, Errno> {
+    let (readable, writable) = flags.read_write().ok_or(Errno::EINVAL)?;
+    if name.is_empty() {
+        return Err(Errno::ENOENT);
+    }
+    if name.len() > NAME_LENGTH_LIMIT {
+        return Err(Errno::ENAMETOOLONG);
+    }
+
+    let inode = with_fs(|| match ROOT_INODE.find(name) {
+        Some(inode) => {
+            if flags.contains(OpenFlags::TRUNC) && writable {
+                inode.clear();
+            }
+            Ok(inode)
+        }
+        None if flags.contains(OpenFlags::CREAT) => ROOT_INODE.create(name).ok_or(Errno::ENOSPC),
+        None => Err(Errno::ENOENT),
+    })??;
+
+    Ok(Arc::new(OSInode {
+        readable,
+        writable,
+        append: flags.contains(OpenFlags::APPEND),
+        offset: AtomicUsize::new(0),
+        inode,
+    }))
+}
+
+impl File for OSInode {
+    fn readable(&self) -> bool {
+        self.readable
+    }
+
+    fn writable(&self) -> bool {
+        self.writable
+    }
+
+    fn read(&self, buf: &mut [u8]) -> SyscallResult {
+        with_fs(|| {
+            let offset = self.offset.load(Ordering::Relaxed);
+            let count = self.inode.read_at(offset, buf);
+            self.offset.store(offset + count, Ordering::Relaxed);
+            count
+        })
+    }
+
+    /// 磁盘空间不足时只写入一部分，一个字节都没有写入则返回 ENOSPC
+    fn write(&self, buf: &[u8]) -> SyscallResult {
+        let count = with_fs(|| {
+            let offset = if self.append {
+                self.inode.size()
+            } else {
+                self.offset.load(Ordering::Relaxed)
+            };
+            let count = self.inode.write_at(offset, buf);
+            self.offset.store(offset + count, Ordering::Relaxed);
+            count
+        })?;
+
+        if count == 0 && !buf.is_empty() {
+            return Err(Errno::ENOSPC);
+        }
+        Ok(count)
+    }
+
+    fn stat(&self) -> Result<Stat, Errno> {
+        with_fs(|| {
+            let size = self.inode.size();
+            let mode = if self.inode.is_dir() {
+                S_IFDIR
+            } else {
+                S_IFREG
+            };
+            Stat {
+                dev: 0,
+                ino: self.inode.inode_id() as u64,
+                mode,
+                nlink: 1,
+                size: size as u64,
+                blocks: DiskInode::total_blocks(size as u32) as u64,
+            }
+        })
+    }
+
+    /// 可以移动到文件末尾之后，之后的写入会用 0 填充中间的空隙
+    fn seek(&self, offset: isize, whence: usize) -> SyscallResult {
+        with_fs(|| {
+            let base = match whence {
+                SEEK_SET => 0,
+                SEEK_CUR => self.offset.load(Ordering::Relaxed),
+                SEEK_END => self.inode.size(),
+                _ => return Err(Errno::EINVAL),
+            };
+            let offset = base.checked_add_signed(offset).ok_or(Errno::EINVAL)?;
+            self.offset.store(offset, Ordering::Relaxed);
+            Ok(offset)
+        })?
+    }
+}
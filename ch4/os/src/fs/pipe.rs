@@ -0,0 +1,225 @@
+// 管道
+//
+// 一个管道由读端和写端两个 `Pipe` 组成，它们共享一个环形缓冲区。
+// - 读取空的管道时阻塞，直到有数据写入；所有写端都关闭之后返回 0（EOF）；
+// - 写入满的管道时阻塞，直到有数据被读出；读端已经关闭时返回 EPIPE。
+//
+// 同一个管道端可以被多个文件描述符（或者多个线程）共享，
+// 它们持有的是同一个 `Arc<Pipe>`，所以 `Pipe` 被 drop 即意味着该端的所有描述符都已经关闭。
+// 注意 drop 的时候会唤醒等待的任务，所以不能在持有 TaskManager 的锁的期间 drop。
+
+use alloc::{collections::VecDeque, sync::Arc};
+
+use crate::{
+    sync::{lock_order, SpinLock},
+    syscall::errno::{Errno, SyscallResult},
+    task::{block_current_and_run_next, current_has_pending_signal, current_task_id, wakeup_task},
+};
+
+use super::{File, Stat, S_IFIFO};
+
+const PIPE_BUFFER_SIZE: usize = 256;
+
+pub struct Pipe {
+    readable: bool,
+    writable: bool,
+    buffer: Arc<SpinLock<PipeRingBuffer>>,
+}
+
+struct PipeRingBuffer {
+    arr: [u8; PIPE_BUFFER_SIZE],
+    head: usize, // 下一个读取的位置
+    len: usize,  // 缓冲区里的数据长度
+    read_end_closed: bool,
+    write_end_closed: bool,
+    read_waiters: VecDeque<usize>,  // 等待数据的任务
+    write_waiters: VecDeque<usize>, // 等待空间的任务
+}
+
+impl PipeRingBuffer {
+    fn new() -> Self {
+        Self {
+            arr: [0; PIPE_BUFFER_SIZE],
+            head: 0,
+            len: 0,
+            read_end_closed: false,
+            write_end_closed: false,
+            read_waiters: VecDeque::new(),
+            write_waiters: VecDeque::new(),
+        }
+    }
+
+    /// 从缓冲区读取数据到 `buf`，返回读取的字节数
+    fn read(&mut self, buf: &mut [u8]) -> usize {
+        let count = buf.len().min(self.len);
+        for byte in buf[..count].iter_mut() {
+            *byte = self.arr[self.head];
+            self.head = (self.head + 1) % PIPE_BUFFER_SIZE;
+        }
+        self.len -= count;
+        count
+    }
+
+    /// 把 `buf` 里的数据写入缓冲区，返回写入的字节数
+    fn write(&mut self, buf: &[u8]) -> usize {
+        let count = buf.len().min(PIPE_BUFFER_SIZE - self.len);
+        let mut tail = (self.head + self.len) % PIPE_BUFFER_SIZE;
+        for byte in buf[..count].iter() {
+            self.arr[tail] = *byte;
+            tail = (tail + 1) % PIPE_BUFFER_SIZE;
+        }
+        self.len += count;
+        count
+    }
+}
+
+/// 创建一个管道，返回 (读端, 写端)
+pub fn make_pipe() -> (Arc<Pipe>, Arc<Pipe>) {
+    let buffer = Arc::new(SpinLock::named(
+        PipeRingBuffer::new(),
+        "PIPE",
+        lock_order::USER_SYNC,
+    ));
+    let read_end = Arc::new(Pipe {
+        readable: true,
+        writable: false,
+        buffer: buffer.clone(),
+    });
+    let write_end = Arc::new(Pipe {
+        readable: false,
+        writable: true,
+        buffer,
+    });
+    (read_end, write_end)
+}
+
+/// 唤醒等待队列里的所有任务，它们醒来之后会重新检查条件
+fn wakeup_all(waiters: VecDeque<usize>) {
+    for task_id in waiters {
+        wakeup_task(task_id);
+    }
+}
+
+impl File for Pipe {
+    fn readable(&self) -> bool {
+        self.readable
+    }
+
+    fn writable(&self) -> bool {
+        self.writable
+    }
+
+    /// 读取已经到达的数据，至少读取一个字节；
+    /// 管道为空时阻塞，等待期间收到信号时返回 EINTR
+    fn read(&self, buf: &mut [u8]) -> SyscallResult {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        let current = current_task_id();
+        loop {
+            let mut inner = self.buffer.lock_irqsave();
+            if inner.len > 0 {
+                let count = inner.read(buf);
+                inner.read_waiters.retain(|id| *id != current);
+                let waiters = core::mem::take(&mut inner.write_waiters);
+                drop(inner);
+
+                wakeup_all(waiters);
+                return Ok(count);
+            }
+            if inner.write_end_closed {
+                inner.read_waiters.retain(|id| *id != current);
+                return Ok(0);
+            }
+            if !inner.read_waiters.contains(&current) {
+                inner.read_waiters.push_back(current);
+            }
+            drop(inner);
+
+            if current_has_pending_signal() {
+                self.buffer
+                    .lock_irqsave()
+                    .read_waiters
+                    .retain(|id| *id != current);
+                return Err(Errno::EINTR);
+            }
+            block_current_and_run_next();
+        }
+    }
+
+    /// 写入全部数据，管道满时阻塞；
+    /// 等待期间收到信号或者读端被关闭时，返回已经写入的字节数，一个字节都没有写入则返回 EINTR 或 EPIPE
+    fn write(&self, buf: &[u8]) -> SyscallResult {
+        let current = current_task_id();
+        let mut pending = buf;
+        let mut count = 0;
+        loop {
+            let mut inner = self.buffer.lock_irqsave();
+            if inner.read_end_closed {
+                inner.write_waiters.retain(|id| *id != current);
+                return if count > 0 {
+                    Ok(count)
+                } else {
+                    Err(Errno::EPIPE)
+                };
+            }
+
+            let n = inner.write(pending);
+            count += n;
+            pending = &pending[n..];
+
+            let done = pending.is_empty();
+            if done {
+                inner.write_waiters.retain(|id| *id != current);
+            } else if !inner.write_waiters.contains(&current) {
+                inner.write_waiters.push_back(current);
+            }
+            let waiters = if n > 0 {
+                core::mem::take(&mut inner.read_waiters)
+            } else {
+                VecDeque::new()
+            };
+            drop(inner);
+
+            wakeup_all(waiters);
+            if done {
+                return Ok(count);
+            }
+
+            if current_has_pending_signal() {
+                self.buffer
+                    .lock_irqsave()
+                    .write_waiters
+                    .retain(|id| *id != current);
+                return if count > 0 {
+                    Ok(count)
+                } else {
+                    Err(Errno::EINTR)
+                };
+            }
+            block_current_and_run_next();
+        }
+    }
+
+    fn stat(&self) -> Result<Stat, Errno> {
+        Ok(Stat::special(S_IFIFO))
+    }
+}
+
+impl Drop for Pipe {
+    /// 关闭管道的一端，唤醒在另一端等待的任务
+    fn drop(&mut self) {
+        let mut inner = self.buffer.lock_irqsave();
+        let waiters = if self.readable {
+            inner.read_end_closed = true;
+            core::mem::take(&mut inner.write_waiters)
+        } else {
+            inner.write_end_closed = true;
+            core::mem::take(&mut inner.read_waiters)
+        };
+        drop(inner);
+
+        wakeup_all(waiters);
+    }
+}
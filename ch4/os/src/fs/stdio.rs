@@ -0,0 +1,80 @@
+// 标准输入输出，分别对应文件描述符 0 和 1、2
+
+use crate::{
+    console,
+    drivers::UART,
+    syscall::errno::{Errno, SyscallResult},
+};
+
+use super::{File, Stat, S_IFCHR};
+
+pub struct Stdin;
+
+pub struct Stdout;
+
+impl File for Stdin {
+    fn readable(&self) -> bool {
+        true
+    }
+
+    fn writable(&self) -> bool {
+        false
+    }
+
+    /// 至少读取一个字符：没有输入时当前任务会被阻塞，直到 UART 中断将其唤醒；
+    /// 之后只读取已经到达的字符，不再阻塞。
+    /// 等待期间收到信号时返回 EINTR。
+    fn read(&self, buf: &mut [u8]) -> SyscallResult {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        let mut first = Some(UART.getchar_blocking().ok_or(Errno::EINTR)?);
+        let mut count: usize = 0;
+
+        for byte in buf.iter_mut() {
+            let c = match first.take() {
+                Some(c) => c,
+                None => match UART.getchar() {
+                    Some(c) => c,
+                    None => break,
+                },
+            };
+            *byte = c;
+            count += 1;
+        }
+
+        Ok(count)
+    }
+
+    fn write(&self, _buf: &[u8]) -> SyscallResult {
+        Err(Errno::EBADF)
+    }
+
+    fn stat(&self) -> Result<Stat, Errno> {
+        Ok(Stat::special(S_IFCHR))
+    }
+}
+
+impl File for Stdout {
+    fn readable(&self) -> bool {
+        false
+    }
+
+    fn writable(&self) -> bool {
+        true
+    }
+
+    fn read(&self, _buf: &mut [u8]) -> SyscallResult {
+        Err(Errno::EBADF)
+    }
+
+    fn write(&self, buf: &[u8]) -> SyscallResult {
+        console::write_bytes(buf);
+        Ok(buf.len())
+    }
+
+    fn stat(&self) -> Result<Stat, Errno> {
+        Ok(Stat::special(S_IFCHR))
+    }
+}
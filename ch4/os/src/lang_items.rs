@@ -1,9 +1,20 @@
-use core::panic::PanicInfo;
+use core::{
+    panic::PanicInfo,
+    sync::atomic::{AtomicBool, Ordering},
+};
 
-use crate::{println, sbi::shutdown};
+use crate::{
+    backtrace, console, println,
+    power::{shutdown, ExitCode},
+};
+
+/// 是否已经在处理 panic，用于避免打印调用链的过程中再次 panic 而导致无限递归
+static PANICKING: AtomicBool = AtomicBool::new(false);
 
 #[panic_handler]
 fn panic(info: &PanicInfo) -> ! {
+    console::enter_panic_mode();
+
     if let Some(location) = info.location() {
         println!(
             "[kernel] panic at [{}:{}] {}",
@@ -15,5 +26,10 @@ fn panic(info: &PanicInfo) -> ! {
         println!("[kernel] panic: {}", info.message().unwrap());
     }
 
-    shutdown()
+    if !PANICKING.swap(true, Ordering::Relaxed) {
+        backtrace::print_backtrace();
+    }
+
+    console::flush();
+    shutdown(ExitCode::Failure(1))
 }
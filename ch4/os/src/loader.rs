@@ -1,46 +1,54 @@
-/// Get the total number of applications.
-pub fn get_num_app() -> usize {
-    extern "C" {
-        fn _num_app();
-    }
-    unsafe { (_num_app as usize as *const usize).read_volatile() }
+// ch4 MODIFY:
+// 应用程序不再链接进内核的数据段（link_app.S），而是保存在文件系统的根目录里，
+// 文件名即应用程序的名称（见 ../easy-fs-fuse）。
+// 应用程序运行时也可能在根目录里创建文件，所以只加载 ELF 文件。
+//
+// 名称以数字开头的应用程序（比如 `00power_3`）在启动时加载运行，
+// 其余的应用程序（比如 `user_shell`）只能由其他应用程序通过 `sys_spawn` 启动。
+
+use alloc::{string::String, vec::Vec};
+
+use easy_fs::Inode;
+
+use crate::{
+    fs::{read_all, with_fs, ROOT_INODE},
+    syscall::errno::Errno,
+};
+
+const ELF_MAGIC: [u8; 4] = *b"\x7fELF";
+
+fn is_elf(inode: &Inode) -> bool {
+    let mut magic = [0u8; 4];
+    inode.read_at(0, &mut magic) == magic.len() && magic == ELF_MAGIC
+}
+
+/// 启动时加载的所有应用程序的名称，按名称排序，下标即应用程序的 id
+pub fn get_app_names() -> Vec<String> {
+    let mut names: Vec<String> = ROOT_INODE
+        .ls()
+        .into_iter()
+        .filter(|name| name.starts_with(|c: char| c.is_ascii_digit()))
+        .filter(|name| is_elf(&ROOT_INODE.find(name).unwrap()))
+        .collect();
+    names.sort();
+    names
+}
+
+/// 读取应用程序的 ELF 文件
+pub fn get_app_data(name: &str) -> Vec<u8> {
+    let inode = ROOT_INODE
+        .find(name)
+        .unwrap_or_else(|| panic!("app {} not found", name));
+    read_all(&inode)
 }
 
-/// get applications data
-pub fn get_app_data(app_id: usize) -> &'static [u8] {
-    extern "C" {
-        fn _num_app();
-    }
-
-    // 用户应用程序在数据段中的开始/结束地址
-    // link_app.S 有如下文本：
-    //
-    // ```
-    // _num_app:
-    // .quad M
-    // .quad app_0_start
-    // .quad app_1_start
-    // .quad ...
-    // .quad app_N_start
-    // .quad app_N_end
-    // ```
-    //
-    // 这里的 _num_app 即数字 `M` 的指针/地址。
-    // 上面一共有 M+1 个 int64 数字，共占用 (M+1) * 8 bytes
-
-    let num_app_ptr = _num_app as usize as *const usize;
-    let num_app = get_num_app();
-    let app_start = unsafe { core::slice::from_raw_parts(num_app_ptr.add(1), num_app + 1) };
-
-    assert!(app_id < num_app);
-
-    // load app data
-    let src = unsafe {
-        core::slice::from_raw_parts(
-            app_start[app_id] as *const u8,
-            app_start[app_id + 1] - app_start[app_id],
-        )
-    };
-
-    src
+/// 运行时读取应用程序 `name` 的 ELF 文件，文件不存在时返回 ENOENT，不是 ELF 文件时返回 ENOEXEC
+pub fn load_app(name: &str) -> Result<Vec<u8>, Errno> {
+    with_fs(|| {
+        let inode = ROOT_INODE.find(name).ok_or(Errno::ENOENT)?;
+        if !is_elf(&inode) {
+            return Err(Errno::ENOEXEC);
+        }
+        Ok(read_all(&inode))
+    })?
 }
@@ -7,11 +7,15 @@ extern crate alloc;
 
 #[macro_use]
 pub mod console;
+mod backtrace;
 mod config;
+mod drivers;
+mod fs;
 mod lang_items;
-mod sbi;
+mod power;
+mod smp;
+mod sync;
 mod timer;
-mod up;
 
 // pub mod batch;
 mod loader;
@@ -24,21 +28,55 @@ pub mod trap;
 use core::arch::global_asm;
 
 global_asm!(include_str!("entry.asm"));
-global_asm!(include_str!("link_app.S"));
+global_asm!(include_str!(concat!(env!("OUT_DIR"), "/ksyms.S")));
+global_asm!(include_str!(concat!(env!("OUT_DIR"), "/ramdisk.S")));
 
 #[no_mangle]
-pub fn rust_main() -> ! {
+pub fn rust_main(hart_id: usize) -> ! {
+    if !smp::claim_boot_hart(hart_id) {
+        // 固件同时启动了多个 hart，没有抽中的 hart 作为从 hart 运行
+        smp::wait_for_boot();
+        rust_main_secondary(hart_id);
+    }
+
     clear_bss();
     print_section_info();
 
-    println!("[kernel] Hello, world!");
+    println!("[kernel] Hello, world! (boot hart {})", hart_id);
+    let spec_version = sbi::get_spec_version();
+    if spec_version.is_ok() {
+        println!(
+            "[kernel] SBI specification v{}.{}",
+            (spec_version.value >> 24) & 0x7f,
+            spec_version.value & 0xff_ffff
+        );
+    }
     mm::init();
+    drivers::init();
+    println!("[kernel] current time: {}", drivers::RTC.now());
+    trap::init();
+    trap::enable_timer_interrupt();
+    timer::set_next_trigger();
+    task::init();
+
+    let num_harts = smp::start_secondary_harts(hart_id);
+    println!("[kernel] {} hart(s) online", num_harts);
+
+    task::run_tasks();
+}
+
+/// 从 hart 的入口，见 `entry.asm` 的 `_start_secondary`
+#[no_mangle]
+pub fn rust_main_secondary(hart_id: usize) -> ! {
+    smp::activate_kernel_space();
     trap::init();
     trap::enable_timer_interrupt();
     timer::set_next_trigger();
-    task::run_first_task();
 
-    panic!("Unreachable in rust_main!");
+    println!("[kernel] hart {} online", hart_id);
+    smp::mark_online();
+
+    task::run_tasks();
 }
 
 fn clear_bss() {
@@ -1,11 +1,17 @@
 use self::memory_set::KERNEL_SPACE;
 
+pub use self::{
+    frame_allocator::{frame_alloc, frame_alloc_contiguous},
+    frame_tracker::FrameTracker,
+};
+
 mod heap_allocator;
 pub mod address;
 pub mod page_table;
 mod frame_tracker;
 mod frame_allocator;
 pub mod memory_set;
+pub mod tlb;
 
 pub fn init() {
     heap_allocator::init_heap();
@@ -14,6 +20,6 @@ pub fn init() {
     frame_allocator::init_frame_allocator();
     // frame_allocator::frame_allocator_test(); // 测试
 
-    KERNEL_SPACE.exclusive_access().activate();
+    KERNEL_SPACE.lock_irqsave().activate();
     // memory_set::remap_test(); // 测试
 }
\ No newline at end of file
@@ -1,4 +1,8 @@
-use crate::{config::MEMORY_END, mm::address::PhysAddr, up::UPSafeCell};
+use crate::{
+    config::MEMORY_END,
+    mm::address::PhysAddr,
+    sync::{lock_order, SpinLock},
+};
 
 use super::{address::PhysPageNum, frame_tracker::FrameTracker};
 
@@ -24,6 +28,17 @@ impl StackFrameAllocator {
         self.current = l.0;
         self.end = r.0;
     }
+
+    // ch4 新增
+    // 分配物理地址连续的多个页面，只从未分配过的空间里分配，
+    // 因为已回收的页面一般不连续。
+    fn alloc_contiguous(&mut self, count: usize) -> Option<PhysPageNum> {
+        if self.end - self.current < count {
+            return None;
+        }
+        self.current += count;
+        Some((self.current - count).into())
+    }
 }
 
 impl FrameAllocator for StackFrameAllocator {
@@ -67,8 +82,11 @@ type FrameAllocatorImpl = StackFrameAllocator;
 
 // 帧分配器（记录着分配情况的结构体 StackFrameAllocator 实例）创建在 .bss 里
 lazy_static! {
-    pub static ref FRAME_ALLOCATOR: UPSafeCell<FrameAllocatorImpl> =
-        unsafe { UPSafeCell::new(FrameAllocatorImpl::new()) };
+    pub static ref FRAME_ALLOCATOR: SpinLock<FrameAllocatorImpl> = SpinLock::named(
+        FrameAllocatorImpl::new(),
+        "FRAME_ALLOCATOR",
+        lock_order::FRAME_ALLOCATOR,
+    );
 }
 
 pub fn init_frame_allocator() {
@@ -80,7 +98,7 @@ pub fn init_frame_allocator() {
     // `物理内存的结束的位置（MEMORY_END）` 作为帧可分配的空间。
     // 注意物理内存的起始物理地址为 0x80000000
     // 虽然 MEMORY_END 的值为 0x8080_0000，实际上只有 8MB
-    FRAME_ALLOCATOR.exclusive_access().init(
+    FRAME_ALLOCATOR.lock_irqsave().init(
         PhysAddr::from(ekernel as usize).ceil(),
         PhysAddr::from(MEMORY_END).floor(),
     );
@@ -90,15 +108,26 @@ pub fn init_frame_allocator() {
 /// allocate a frame
 pub fn frame_alloc() -> Option<FrameTracker> {
     FRAME_ALLOCATOR
-        .exclusive_access()
+        .lock_irqsave()
         .alloc()
         .map(FrameTracker::new)
 }
 
+// ch4 新增
+/// 分配物理地址连续的 `count` 个帧，用于设备的 DMA 缓冲区（比如 virtio 的 virtqueue）
+pub fn frame_alloc_contiguous(count: usize) -> Option<Vec<FrameTracker>> {
+    let start = FRAME_ALLOCATOR.lock_irqsave().alloc_contiguous(count)?;
+    Some(
+        (start.0..start.0 + count)
+            .map(|ppn| FrameTracker::new(ppn.into()))
+            .collect(),
+    )
+}
+
 /// 对外服务的函数
 /// deallocate a frame
 pub fn frame_dealloc(ppn: PhysPageNum) {
-    FRAME_ALLOCATOR.exclusive_access().dealloc(ppn);
+    FRAME_ALLOCATOR.lock_irqsave().dealloc(ppn);
 }
 
 #[allow(unused)]
@@ -1,9 +1,12 @@
 use alloc::{collections::BTreeMap, sync::Arc, vec::Vec};
 
+use xmas_elf::program::ProgramHeader;
+
 use crate::{
     config::{MEMORY_END, MMIO, PAGE_SIZE, TRAMPOLINE, TRAP_CONTEXT, USER_STACK_SIZE},
     mm::address::StepByOne,
-    up::UPSafeCell,
+    sync::{lock_order, SpinLock},
+    syscall::errno::Errno,
 };
 
 use super::{
@@ -11,6 +14,7 @@ use super::{
     frame_allocator::frame_alloc,
     frame_tracker::FrameTracker,
     page_table::{PTEFlags, PageTable, PageTableEntry},
+    tlb::flush_tlb,
 };
 
 /// `内存段`
@@ -22,6 +26,13 @@ pub struct MapArea {
     map_perm: MapPermission,                          // 该段内存的访问权限
 }
 
+/// 为内存段分配页帧失败的原因
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum MapError {
+    OverLimit,   // 超出了地址空间的页帧数量上限（RLIMIT_RSS，见 `task/rlimit.rs`）
+    OutOfMemory, // 物理页帧已经耗尽
+}
+
 /// 内存的映射方式
 /// map type for memory set: identical or framed
 #[derive(Copy, Clone, PartialEq, Debug)]
@@ -62,7 +73,28 @@ impl MapArea {
         }
     }
 
-    pub fn map_one(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) -> usize { // 新增，返回物理页面地址
+    /// 内存段的起始和结束虚拟地址（以页面对齐）
+    pub fn va_range(&self) -> (VirtAddr, VirtAddr) {
+        (
+            self.vpn_range.get_start().into(),
+            self.vpn_range.get_end().into(),
+        )
+    }
+
+    pub fn map_type(&self) -> MapType {
+        self.map_type
+    }
+
+    pub fn map_perm(&self) -> MapPermission {
+        self.map_perm
+    }
+
+    pub fn contains(&self, va: VirtAddr) -> bool {
+        let vpn = va.floor();
+        vpn >= self.vpn_range.get_start() && vpn < self.vpn_range.get_end()
+    }
+
+    pub fn map_one(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) -> Option<usize> { // 新增，返回物理页面地址；物理页帧耗尽时返回 None
 
         // 注：
         // 这里最好检查以下参数 vpn 是否属于 self.vpn_range 之内。
@@ -74,7 +106,7 @@ impl MapArea {
             }
 
             MapType::Framed => {
-                let frame = frame_alloc().unwrap();
+                let frame = frame_alloc()?;
                 ppn = frame.ppn;
                 self.data_frames.insert(vpn, frame);
             }
@@ -83,12 +115,15 @@ impl MapArea {
         let ppn_clone = ppn.0; // 新增
 
         let pte_flags = PTEFlags::from_bits(self.map_perm.bits).unwrap();
-        page_table.map(vpn, ppn, pte_flags);
+        if page_table.map(vpn, ppn, pte_flags).is_none() {
+            // 页表本身分配页帧失败，回收刚刚分配的页帧
+            self.data_frames.remove(&vpn);
+            return None;
+        }
 
-        ppn_clone // 新增
+        Some(ppn_clone) // 新增
     }
 
-    #[allow(unused)]
     pub fn unmap_one(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) {
         if self.map_type == MapType::Framed {
             self.data_frames.remove(&vpn);
@@ -96,15 +131,28 @@ impl MapArea {
         page_table.unmap(vpn);
     }
 
-    pub fn map(&mut self, page_table: &mut PageTable) -> Vec<usize> { // 新增，返回物理页面地址列表
+    pub fn map(&mut self, page_table: &mut PageTable) -> Option<Vec<usize>> { // 新增，返回物理页面地址列表；物理页帧耗尽时返回 None
         let mut ppns = Vec::<usize>::new();
 
         for vpn in self.vpn_range {
-            let ppn = self.map_one(page_table, vpn);
-            ppns.push(ppn);
+            match self.map_one(page_table, vpn) {
+                Some(ppn) => ppns.push(ppn),
+                None => {
+                    // 撤销已经建立的映射，已经分配的页帧随之回收
+                    for mapped in self.vpn_range.into_iter().take(ppns.len()) {
+                        self.unmap_one(page_table, mapped);
+                    }
+                    return None;
+                }
+            }
         }
 
-        ppns
+        Some(ppns)
+    }
+
+    /// 内存段包含的页面数量
+    pub fn page_count(&self) -> usize {
+        self.vpn_range.get_end().0 - self.vpn_range.get_start().0
     }
 
     #[allow(unused)]
@@ -151,6 +199,7 @@ impl MapArea {
 pub struct MemorySet {
     page_table: PageTable, // 第一个 page table，即 L2 page table
     areas: Vec<MapArea>,   // `内存段` 集合
+    frame_limit: usize,    // Framed 内存段最多可以占用的页帧数量
 }
 
 extern "C" {
@@ -160,6 +209,8 @@ extern "C" {
     fn erodata();
     fn sdata();
     fn edata();
+    fn sksyms();
+    fn eksyms();
     fn sbss_with_stack();
     fn ebss();
     fn ekernel();
@@ -172,16 +223,31 @@ use riscv::register::satp;
 
 lazy_static! {
     /// a memory set instance through lazy_static! managing kernel space
-    pub static ref KERNEL_SPACE: Arc<UPSafeCell<MemorySet>> =
-        Arc::new(unsafe { UPSafeCell::new(MemorySet::new_kernel()) });
+    pub static ref KERNEL_SPACE: Arc<SpinLock<MemorySet>> = Arc::new(SpinLock::named(
+        MemorySet::new_kernel(),
+        "KERNEL_SPACE",
+        lock_order::KERNEL_SPACE,
+    ));
 }
 
 impl MemorySet {
-    pub fn new_bare() -> Self {
-        Self {
-            page_table: PageTable::new(),
+    /// 物理页帧耗尽时返回 None
+    pub fn new_bare() -> Option<Self> {
+        Some(Self {
+            page_table: PageTable::new()?,
             areas: Vec::new(),
-        }
+            frame_limit: usize::MAX,
+        })
+    }
+
+    /// Framed 内存段占用的页帧数量，即驻留内存的大小（以页为单位）
+    pub fn resident_frames(&self) -> usize {
+        self.areas.iter().map(|area| area.data_frames.len()).sum()
+    }
+
+    /// 设置页帧数量的上限：之后超出上限的 `insert_framed_area` 会失败，已经占用的页帧不受影响
+    pub fn set_frame_limit(&mut self, limit: usize) {
+        self.frame_limit = limit;
     }
 
     pub fn token(&self) -> usize {
@@ -189,22 +255,43 @@ impl MemorySet {
     }
 
     /// Assume that no conflicts.
+    ///
+    /// 运行期间为地址空间增加内存段（内核栈、线程的用户栈等），
+    /// 超出页帧数量上限或者物理页帧耗尽时失败，地址空间保持不变。
     pub fn insert_framed_area(
         &mut self,
         start_va: VirtAddr,
         end_va: VirtAddr,
         permission: MapPermission,
-    ) {
-        println!("insert_framed_area (map a kernel-stack in kernel space) 0x{:x}-0x{:x}", start_va.0, end_va.0);
-
-        self.push(
+    ) -> Result<(), MapError> {
+        self.try_push(
             MapArea::new(start_va, end_va, MapType::Framed, permission),
             None,
-        );
+        )?;
+        Ok(())
+    }
+
+    /// 用于建立内核地址空间，此时还不存在页帧数量上限，物理页帧耗尽时 panic
+    fn push(&mut self, map_area: MapArea, data: Option<&[u8]>) -> Vec<usize> { // 新增，返回物理页面地址列表
+        self.try_push(map_area, data)
+            .expect("out of physical frames while building address space")
     }
 
-    fn push(&mut self, mut map_area: MapArea, data: Option<&[u8]>) -> Vec<usize> { // 新增，返回物理页面地址列表
-        let pnns = map_area.map(&mut self.page_table);
+    fn try_push(
+        &mut self,
+        mut map_area: MapArea,
+        data: Option<&[u8]>,
+    ) -> Result<Vec<usize>, MapError> {
+        // 在分配页帧之前检查上限
+        if map_area.map_type == MapType::Framed
+            && self.resident_frames() + map_area.page_count() > self.frame_limit
+        {
+            return Err(MapError::OverLimit);
+        }
+
+        let pnns = map_area
+            .map(&mut self.page_table)
+            .ok_or(MapError::OutOfMemory)?;
 
         // 用于加载 app 的二进制数据
         if let Some(data) = data {
@@ -213,7 +300,7 @@ impl MemorySet {
 
         self.areas.push(map_area);
 
-        pnns
+        Ok(pnns)
     }
 
     /// Mention that trampoline is not collected by areas.
@@ -221,27 +308,32 @@ impl MemorySet {
     /// 启用了分页机制之后，用户 app trap 需要切换到内核地址空间，以及内核处理完 trap 之后需要切换回到 app 的地址空间，
     /// 要求地址空间的切换不能影响指令的连续执行，即要求应用和内核地址空间在切换地址空间指令附近是平滑的。
     /// 所以需要一个跳板。
-    fn map_trampoline(&mut self) {
+    ///
+    /// 为页表分配页帧失败时返回 None。
+    fn map_trampoline(&mut self) -> Option<()> {
         self.page_table.map(
             VirtAddr::from(TRAMPOLINE).into(),
             PhysAddr::from(strampoline as usize).into(),
             PTEFlags::R | PTEFlags::X,
-        );
+        )?;
 
         println!("map trampoline, virtual page number: 0x{:x}, physical page number: 0x{:x}",
             TRAMPOLINE,
             strampoline as usize
         );
+        Some(())
     }
 
     /// Without kernel stacks.
     pub fn new_kernel() -> Self {
         println!("------ mapping kernel");
 
-        let mut memory_set = Self::new_bare();
+        let mut memory_set = Self::new_bare().expect("out of physical frames for kernel page table");
 
         // map trampoline
-        memory_set.map_trampoline();
+        memory_set
+            .map_trampoline()
+            .expect("out of physical frames for kernel page table");
 
         // map kernel sections
 
@@ -295,6 +387,23 @@ impl MemorySet {
             pnns_data.first().unwrap(),
             pnns_data.last().unwrap());
 
+        // 只有第二次链接得到的内核才包含符号表
+        if eksyms as usize > sksyms as usize {
+            println!("mapping .ksyms section");
+            let pnns_ksyms = memory_set.push(
+                MapArea::new(
+                    (sksyms as usize).into(),
+                    (eksyms as usize).into(),
+                    MapType::Identical,
+                    MapPermission::R,
+                ),
+                None,
+            );
+            println!("map to physical page number (identical): 0x{:x} ... 0x{:x}",
+                pnns_ksyms.first().unwrap(),
+                pnns_ksyms.last().unwrap());
+        }
+
         println!("mapping .bss section");
         let pnns_bss = memory_set.push(
             MapArea::new(
@@ -344,29 +453,42 @@ impl MemorySet {
 
     /// Include sections in elf and trampoline and TrapContext and user stack,
     /// also returns user_sp and entry point.
-    pub fn from_elf(elf_data: &[u8]) -> (Self, usize, usize) {
-        let mut memory_set = Self::new_bare();
+    ///
+    /// ch4 MODIFY:
+    /// 运行时加载的 ELF 文件可能是任意数据，所以不再 panic：
+    /// 格式不正确时返回 `ENOEXEC`，物理页帧耗尽或者 ELF 段、用户栈和 TrapContext 占用的页帧
+    /// 超过 `frame_limit`（由 RLIMIT_RSS 换算而来）时返回 `ENOMEM`，
+    /// 已经建立的部分地址空间随 `memory_set` 一起释放。
+    pub fn from_elf(elf_data: &[u8], frame_limit: usize) -> Result<(Self, usize, usize), Errno> {
+        let mut memory_set = Self::new_bare().ok_or(Errno::ENOMEM)?;
+        memory_set.set_frame_limit(frame_limit);
 
         // map trampoline
-        memory_set.map_trampoline();
+        memory_set.map_trampoline().ok_or(Errno::ENOMEM)?;
 
         // map program headers of elf, with U flag
-        let elf = xmas_elf::ElfFile::new(elf_data).unwrap();
+        let elf = xmas_elf::ElfFile::new(elf_data).map_err(|_| Errno::ENOEXEC)?;
         let elf_header = elf.header;
         let magic = elf_header.pt1.magic;
-        assert_eq!(magic, [0x7f, 0x45, 0x4c, 0x46], "invalid elf!");
+        if magic != [0x7f, 0x45, 0x4c, 0x46] {
+            return Err(Errno::ENOEXEC);
+        }
 
         let ph_count = elf_header.pt2.ph_count();
         let mut max_end_vpn = VirtPageNum(0);
 
         for i in 0..ph_count {
-            let ph = elf.program_header(i).unwrap();
+            let ph = elf.program_header(i).map_err(|_| Errno::ENOEXEC)?;
 
-            if ph.get_type().unwrap() == xmas_elf::program::Type::Load {
+            if ph.get_type().map_err(|_| Errno::ENOEXEC)? == xmas_elf::program::Type::Load {
                 // 对于非 Type::Load 类型的 program header 不予理睬
 
-                let start_va: VirtAddr = (ph.virtual_addr() as usize).into();
-                let end_va: VirtAddr = ((ph.virtual_addr() + ph.mem_size()) as usize).into();
+                let (start, end, data) = load_segment_range(elf.input, &ph)?;
+                if start == end {
+                    continue;
+                }
+                let start_va: VirtAddr = start.into();
+                let end_va: VirtAddr = end.into();
 
                 let mut map_perm = MapPermission::U; // U 表示用户 app 可访问权限
                 let ph_flags = ph.flags();
@@ -385,13 +507,20 @@ impl MemorySet {
 
                 let map_area = MapArea::new(start_va, end_va, MapType::Framed, map_perm);
 
-                max_end_vpn = map_area.vpn_range.get_end();
+                // 段之间有重叠时 `PageTable::map` 会 panic
+                if memory_set.overlaps(&map_area) {
+                    return Err(Errno::ENOEXEC);
+                }
+
+                max_end_vpn = max_end_vpn.max(map_area.vpn_range.get_end());
 
-                let pnns = memory_set.push(
-                    map_area,
-                    // 注意当存在一部分零初始化的时候， ph.file_size() 将会小于 ph.mem_size()
-                    Some(&elf.input[ph.offset() as usize..(ph.offset() + ph.file_size()) as usize]),
-                );
+                let pnns = memory_set
+                    .try_push(
+                        map_area,
+                        // 注意当存在一部分零初始化的时候， ph.file_size() 将会小于 ph.mem_size()
+                        Some(data),
+                    )
+                    .map_err(|_| Errno::ENOMEM)?;
 
                 println!("map to physical page number (framed): 0x{:x}, ???, ... 0x{:x}",
                     pnns.first().unwrap(),
@@ -410,30 +539,37 @@ impl MemorySet {
         user_stack_bottom += PAGE_SIZE; // guard page
 
         let user_stack_top = user_stack_bottom + USER_STACK_SIZE;
-        let ppns_stack = memory_set.push(
-            MapArea::new(
-                user_stack_bottom.into(),
-                user_stack_top.into(),
-                MapType::Framed,
-                MapPermission::R | MapPermission::W | MapPermission::U,
-            ),
-            None,
-        );
+        if user_stack_top > USER_SPACE_END {
+            return Err(Errno::ENOEXEC);
+        }
+        let ppns_stack = memory_set
+            .try_push(
+                MapArea::new(
+                    user_stack_bottom.into(),
+                    user_stack_top.into(),
+                    MapType::Framed,
+                    MapPermission::R | MapPermission::W | MapPermission::U,
+                ),
+                None,
+            )
+            .map_err(|_| Errno::ENOMEM)?;
         println!("map to physical page number (framed): 0x{:x}, ???, ... 0x{:x}",
             ppns_stack.first().unwrap(),
             ppns_stack.last().unwrap());
 
         // map TrapContext
         println!("mapping user application TrapContext");
-        let ppns_tc = memory_set.push(
-            MapArea::new(
-                TRAP_CONTEXT.into(),
-                TRAMPOLINE.into(),
-                MapType::Framed,
-                MapPermission::R | MapPermission::W,
-            ),
-            None,
-        );
+        let ppns_tc = memory_set
+            .try_push(
+                MapArea::new(
+                    TRAP_CONTEXT.into(),
+                    TRAMPOLINE.into(),
+                    MapType::Framed,
+                    MapPermission::R | MapPermission::W,
+                ),
+                None,
+            )
+            .map_err(|_| Errno::ENOMEM)?;
         println!("map to physical page number (framed): 0x{:x}, ???, ... 0x{:x}",
             ppns_tc.first().unwrap(),
             ppns_tc.last().unwrap());
@@ -441,11 +577,19 @@ impl MemorySet {
         // 返回的内容不仅仅包括内存空间，
         // 还应该包括用户 app 栈的页面地址
         // 以及应用程序的入口地址等。
-        (
+        Ok((
             memory_set,
             user_stack_top,
             elf.header.pt2.entry_point() as usize,
-        )
+        ))
+    }
+
+    /// 地址空间里是否已经有内存段跟 `map_area` 重叠
+    fn overlaps(&self, map_area: &MapArea) -> bool {
+        let (start, end) = (map_area.vpn_range.get_start(), map_area.vpn_range.get_end());
+        self.areas
+            .iter()
+            .any(|area| area.vpn_range.get_start() < end && start < area.vpn_range.get_end())
     }
 
     pub fn activate(&self) {
@@ -461,12 +605,99 @@ impl MemorySet {
     pub fn translate(&self, vpn: VirtPageNum) -> Option<PageTableEntry> {
         self.page_table.translate(vpn)
     }
+
+    /// 查找包含虚拟地址 `va` 的内存段，用于打印异常报告
+    pub fn find_area(&self, va: VirtAddr) -> Option<&MapArea> {
+        self.areas.iter().find(|area| area.contains(va))
+    }
+
+    /// 移除起始地址为 `start_va` 的内存段，返回是否找到了该内存段
+    ///
+    /// 先取消映射并刷新各个 hart 的 TLB，之后才回收页帧，
+    /// 避免其他 hart 通过旧的 TLB 项访问已经被重新分配的页帧。
+    pub fn remove_area(&mut self, start_va: VirtAddr) -> bool {
+        let start_vpn = start_va.floor();
+        let idx = match self
+            .areas
+            .iter()
+            .position(|area| area.vpn_range.get_start() == start_vpn)
+        {
+            Some(idx) => idx,
+            None => return false,
+        };
+
+        let area = self.areas.remove(idx);
+        for vpn in area.vpn_range {
+            self.page_table.unmap(vpn);
+        }
+
+        let (start_va, end_va) = area.va_range();
+        flush_tlb(self.token(), start_va, end_va);
+
+        // area 被释放时回收它的页帧
+        drop(area);
+        true
+    }
+
+    /// 修改起始地址为 `start_va` 的内存段的访问权限，返回是否找到了该内存段
+    #[allow(unused)]
+    pub fn protect_area(&mut self, start_va: VirtAddr, permission: MapPermission) -> bool {
+        let start_vpn = start_va.floor();
+        let token = self.token();
+        let area = match self
+            .areas
+            .iter_mut()
+            .find(|area| area.vpn_range.get_start() == start_vpn)
+        {
+            Some(area) => area,
+            None => return false,
+        };
+
+        area.map_perm = permission;
+        let pte_flags = PTEFlags::from_bits(permission.bits).unwrap();
+        for vpn in area.vpn_range {
+            self.page_table.set_flags(vpn, pte_flags);
+        }
+
+        let (start_va, end_va) = area.va_range();
+        flush_tlb(token, start_va, end_va);
+        true
+    }
 }
 
 
+// ch4 新增
+/// ELF 段和用户栈只能位于 SV39 地址空间的低半部分（低 256 GiB），
+/// 更高的地址会被 `VirtAddr::from` 截断，跟其他内存段重叠
+const USER_SPACE_END: usize = 1 << 38;
+
+/// 检查 ELF 段的地址和大小，返回它的 [start, end) 以及要复制的数据
+///
+/// `copy_data` 从内存段的第一个页面开始复制，所以段的起始地址必须按页面对齐。
+fn load_segment_range<'a>(
+    input: &'a [u8],
+    ph: &ProgramHeader,
+) -> Result<(usize, usize, &'a [u8]), Errno> {
+    let start = ph.virtual_addr() as usize;
+    let end = start
+        .checked_add(ph.mem_size() as usize)
+        .filter(|&end| end <= USER_SPACE_END)
+        .ok_or(Errno::ENOEXEC)?;
+    if start % PAGE_SIZE != 0 || ph.file_size() > ph.mem_size() {
+        return Err(Errno::ENOEXEC);
+    }
+
+    let offset = ph.offset() as usize;
+    let data = offset
+        .checked_add(ph.file_size() as usize)
+        .and_then(|data_end| input.get(offset..data_end))
+        .ok_or(Errno::ENOEXEC)?;
+    Ok((start, end, data))
+}
+
 #[allow(unused)]
 pub fn remap_test() {
-    let mut kernel_space = KERNEL_SPACE.exclusive_access();
+    let mut kernel_space = KERNEL_SPACE.lock_irqsave();
 
     let mid_text: VirtAddr = ((stext as usize + etext as usize) / 2).into();
     let mid_rodata: VirtAddr = ((srodata as usize + erodata as usize) / 2).into();
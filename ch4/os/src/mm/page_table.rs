@@ -3,7 +3,7 @@ use alloc::vec::Vec;
 use bitflags::bitflags;
 
 use super::{
-    address::{PhysPageNum, StepByOne, VirtAddr, VirtPageNum},
+    address::{PhysAddr, PhysPageNum, StepByOne, VirtAddr, VirtPageNum},
     frame_allocator::frame_alloc,
     frame_tracker::FrameTracker,
 };
@@ -72,6 +72,10 @@ impl PageTableEntry {
     pub fn executable(&self) -> bool {
         (self.flags() & PTEFlags::X) != PTEFlags::empty()
     }
+
+    pub fn is_user(&self) -> bool {
+        (self.flags() & PTEFlags::U) != PTEFlags::empty()
+    }
 }
 
 // 页表数据结构
@@ -157,7 +161,7 @@ impl PageTable {
     }
 
     /// 跟据虚拟地址找到对应的 PageTableEntry，
-    /// 如果找不到则创建新的。
+    /// 如果找不到则创建新的；为下级页表分配页帧失败时返回 None。
     fn find_pte_create(&mut self, vpn: VirtPageNum) -> Option<&mut PageTableEntry> {
         let idxs = vpn.indexes();
         let mut ppn = self.root_ppn;
@@ -174,7 +178,7 @@ impl PageTable {
             if !pte.is_valid() {
                 // 没找到对应的 PageTableEntry，创建一个下级表
                 // 注意这里是创建一个 PageTable
-                let frame = frame_alloc().unwrap();
+                let frame = frame_alloc()?;
                 *pte = PageTableEntry::new(frame.ppn, PTEFlags::V);
                 self.frames.push(frame);
             }
@@ -184,12 +188,13 @@ impl PageTable {
         result
     }
 
-    pub fn new() -> Self {
-        let frame = frame_alloc().unwrap();
-        PageTable {
+    /// 物理页帧耗尽时返回 None
+    pub fn new() -> Option<Self> {
+        let frame = frame_alloc()?;
+        Some(PageTable {
             root_ppn: frame.ppn,
             frames: vec![frame],
-        }
+        })
     }
 
     /// Temporarily used to get arguments from user space.
@@ -216,11 +221,13 @@ impl PageTable {
         8usize << 60 | self.root_ppn.0
     }
 
+    /// 为下级页表分配页帧失败时返回 None，已经分配的下级页表留在页表里，随页表一起释放
     #[allow(unused)]
-    pub fn map(&mut self, vpn: VirtPageNum, ppn: PhysPageNum, flags: PTEFlags) {
-        let pte = self.find_pte_create(vpn).unwrap();
+    pub fn map(&mut self, vpn: VirtPageNum, ppn: PhysPageNum, flags: PTEFlags) -> Option<()> {
+        let pte = self.find_pte_create(vpn)?;
         assert!(!pte.is_valid(), "vpn {:?} is mapped before mapping", vpn);
         *pte = PageTableEntry::new(ppn, flags | PTEFlags::V);
+        Some(())
     }
 
     #[allow(unused)]
@@ -230,6 +237,13 @@ impl PageTable {
         *pte = PageTableEntry::empty();
     }
 
+    /// 修改已经映射的页面的访问权限，修改之后需要刷新 TLB（见 `mm/tlb.rs`）
+    pub fn set_flags(&mut self, vpn: VirtPageNum, flags: PTEFlags) {
+        let pte = self.find_pte(vpn).unwrap();
+        assert!(pte.is_valid(), "vpn {:?} is invalid before changing flags", vpn);
+        *pte = PageTableEntry::new(pte.ppn(), flags | PTEFlags::V);
+    }
+
     pub fn translate(&self, vpn: VirtPageNum) -> Option<PageTableEntry> {
         self.find_pte(vpn).map(|pte| *pte)
     }
@@ -239,26 +253,170 @@ impl PageTable {
 // 而需要手动查页表才能知道那些数据被放置在哪些物理页帧上并进行访问。
 // 该函数能够将 `应用地址空间` 中一个缓冲区转化为在 `内核空间` 中能够直接访问的地址，然后读取
 // 其中的数据并返回。
-pub fn translated_byte_buffer(token: usize, ptr: *const u8, len: usize) -> Vec<&'static [u8]> {
+//
+// 缓冲区的地址由应用程序传入，并不可信：如果缓冲区的某一部分没有映射，
+// 不允许 U 特权级访问（比如 TrapContext 和跳板所在的页面），
+// 或者不允许应用程序以 `access` 的方式访问（比如内核要写入的缓冲区位于只读的 .text 段），则返回 None，
+// 由系统调用返回 `-EFAULT`，而不是让内核 panic。
+//
+// 内核通过恒等映射访问物理页帧，不受应用程序页表里的 R/W 权限约束，所以需要在这里检查。
+pub fn translated_byte_buffer(
+    token: usize,
+    ptr: *const u8,
+    len: usize,
+    access: UserAccess,
+) -> Option<Vec<&'static mut [u8]>> {
     let page_table = PageTable::from_token(token);
     let mut start = ptr as usize;
-    let end = start + len;
+    let end = start.checked_add(len)?;
 
     let mut v = Vec::new();
 
     while start < end {
         let start_va = VirtAddr::from(start);
         let mut vpn = start_va.floor();
-        let ppn = page_table.translate(vpn).unwrap().ppn();
+
+        let pte = page_table.translate(vpn)?;
+        if !access.is_allowed(&pte) {
+            return None;
+        }
+        let ppn = pte.ppn();
 
         vpn.step();
 
         let mut end_va: VirtAddr = vpn.into();
         end_va = end_va.min(VirtAddr::from(end));
 
-        v.push(&ppn.get_bytes_array()[start_va.page_offset()..end_va.page_offset()]); // 获取指定范围的切片
+        // 获取指定范围的切片
+        if end_va.page_offset() == 0 {
+            // 范围一直到页面的末尾
+            v.push(&mut ppn.get_bytes_array()[start_va.page_offset()..]);
+        } else {
+            v.push(&mut ppn.get_bytes_array()[start_va.page_offset()..end_va.page_offset()]);
+        }
 
         start = end_va.into();
     }
-    v
+    Some(v)
+}
+
+// ch4 新增
+/// 内核访问应用程序内存的方式
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum UserAccess {
+    Read,  // 内核读取应用程序的数据
+    Write, // 内核把数据写入应用程序的内存
+}
+
+impl UserAccess {
+    /// 页面是否有效、允许 U 特权级访问，并且允许以该方式访问
+    fn is_allowed(self, pte: &PageTableEntry) -> bool {
+        let permitted = match self {
+            UserAccess::Read => pte.readable(),
+            UserAccess::Write => pte.writable(),
+        };
+        pte.is_valid() && pte.is_user() && permitted
+    }
+}
+
+/// 应用地址空间中的一个缓冲区，由若干段（每段位于同一个页面内）组成，内核可以直接访问，
+/// 用于在文件读写时复制数据（见 `syscall/fs.rs`）
+pub struct UserBuffer {
+    pub buffers: Vec<&'static mut [u8]>,
+}
+
+impl UserBuffer {
+    /// 缓冲区的某一部分无效，或者不允许以 `access` 的方式访问时返回 None
+    pub fn from_user(token: usize, ptr: *const u8, len: usize, access: UserAccess) -> Option<Self> {
+        translated_byte_buffer(token, ptr, len, access).map(|buffers| Self { buffers })
+    }
+
+    /// 把 `data` 复制到缓冲区的开头，超出缓冲区的部分被忽略
+    pub fn copy_from_slice(&mut self, data: &[u8]) {
+        let mut start = 0;
+        for buffer in self.buffers.iter_mut() {
+            let n = buffer.len().min(data.len() - start);
+            buffer[..n].copy_from_slice(&data[start..start + n]);
+            start += n;
+        }
+    }
+
+    /// 把缓冲区开头的数据复制到 `data`，超出缓冲区的部分保持不变
+    pub fn copy_to_slice(&self, data: &mut [u8]) {
+        let mut start = 0;
+        for buffer in self.buffers.iter() {
+            let n = buffer.len().min(data.len() - start);
+            data[start..start + n].copy_from_slice(&buffer[..n]);
+            start += n;
+        }
+    }
+}
+
+/// 把应用地址空间中的虚拟地址转换为物理地址，
+/// 地址没有映射、不允许 U 特权级访问或者不允许以 `access` 的方式访问时返回 None
+///
+/// 内核对物理内存是恒等映射的，所以得到的物理地址可以直接被内核访问。
+pub fn translate_user_va(token: usize, va: usize, access: UserAccess) -> Option<PhysAddr> {
+    let va = VirtAddr::from(va);
+    let pte = PageTable::from_token(token).translate(va.floor())?;
+    if !access.is_allowed(&pte) {
+        return None;
+    }
+    let pa: PhysAddr = pte.ppn().into();
+    Some(PhysAddr::from(usize::from(pa) + va.page_offset()))
+}
+
+// ch4 新增
+/// 从应用地址空间读取一个以 `\0` 结尾的字符串（不包括 `\0`），最多读取 `max_len` bytes；
+/// 地址无效时返回 None
+pub fn copy_str_from_user(token: usize, ptr: *const u8, max_len: usize) -> Option<Vec<u8>> {
+    let mut bytes = Vec::new();
+    let mut va = ptr as usize;
+    while bytes.len() < max_len {
+        let pa = translate_user_va(token, va, UserAccess::Read)?;
+        let byte = unsafe { *(usize::from(pa) as *const u8) };
+        if byte == 0 {
+            break;
+        }
+        bytes.push(byte);
+        va = va.checked_add(1)?;
+    }
+    Some(bytes)
+}
+
+/// 把内核中的一个值复制到应用地址空间，目标位置可以跨越页面；
+/// 目标地址无效时返回 None
+pub fn copy_to_user<T>(token: usize, dst: *mut T, src: &T) -> Option<()> {
+    let src = unsafe {
+        core::slice::from_raw_parts(src as *const T as *const u8, core::mem::size_of::<T>())
+    };
+
+    let buffers = translated_byte_buffer(token, dst as *const u8, src.len(), UserAccess::Write)?;
+    let mut start = 0;
+    for buffer in buffers {
+        let len = buffer.len();
+        buffer.copy_from_slice(&src[start..start + len]);
+        start += len;
+    }
+    Some(())
+}
+
+/// 从应用地址空间读取一个值，源位置可以跨越页面；源地址无效时返回 None
+///
+/// 注意 `T` 必须是任意比特组合都合法的类型（比如整数以及由整数组成的结构体）。
+pub fn copy_from_user<T>(token: usize, src: *const T) -> Option<T> {
+    let mut value = core::mem::MaybeUninit::<T>::uninit();
+    let dst = unsafe {
+        core::slice::from_raw_parts_mut(value.as_mut_ptr() as *mut u8, core::mem::size_of::<T>())
+    };
+
+    let buffers = translated_byte_buffer(token, src as *const u8, dst.len(), UserAccess::Read)?;
+    let mut start = 0;
+    for buffer in buffers {
+        let len = buffer.len();
+        dst[start..start + len].copy_from_slice(buffer);
+        start += len;
+    }
+
+    Some(unsafe { value.assume_init() })
 }
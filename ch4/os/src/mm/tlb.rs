@@ -0,0 +1,93 @@
+// TLB 刷新（TLB shootdown）
+//
+// 修改页表之后，各个 hart 的 TLB 里可能还缓存着旧的页表项：
+// - 当前 hart 通过 `sfence.vma` 刷新；
+// - 其他 hart 通过 SBI 的 remote sfence.vma 让它们各自刷新。
+//
+// 只有 "可能正在使用" 该地址空间的 hart 才需要刷新：
+// - 应用的地址空间：`__restore` 和 `__alltraps` 切换 satp 之后都会执行 `sfence.vma`，
+//   所以只有正处于用户态（或者正在返回用户态）的 hart 才可能缓存了它的页表项，
+//   每个 hart 在返回用户态之前记录它正在使用的地址空间（`set_active_token`）；
+// - 内核地址空间：所有在线的 hart 都在使用。
+//
+// 记录地址空间和检查记录都使用 SeqCst，保证 "修改页表 -> 检查记录" 和 "写入记录 -> 返回用户态（sfence.vma）"
+// 两者之中至少有一方看到对方的操作：要么该 hart 收到刷新请求，要么它返回用户态时已经能看到新的页表项。
+
+use core::{
+    arch::asm,
+    sync::atomic::{fence, AtomicUsize, Ordering},
+};
+use sbi::remote_sfence_vma;
+
+use crate::{
+    config::{MAX_HARTS, PAGE_SIZE},
+    smp::{hart_id, kernel_satp, online_hart_mask},
+};
+
+use super::address::VirtAddr;
+
+/// 超过该页数时直接刷新整个 TLB，而不是逐页刷新
+const FLUSH_ALL_THRESHOLD: usize = 64;
+
+/// 没有在用户态使用任何地址空间
+const NO_USER_SPACE: usize = 0;
+
+#[allow(clippy::declare_interior_mutable_const)]
+const NONE: AtomicUsize = AtomicUsize::new(NO_USER_SPACE);
+
+/// 每个 hart 当前在用户态使用的地址空间的 token
+static ACTIVE_TOKEN: [AtomicUsize; MAX_HARTS] = [NONE; MAX_HARTS];
+
+/// 记录当前 hart 在用户态使用的地址空间，返回用户态之前调用；
+/// trap 进入内核之后调用 `clear_active_token`
+pub fn set_active_token(token: usize) {
+    ACTIVE_TOKEN[hart_id()].store(token, Ordering::SeqCst);
+}
+
+pub fn clear_active_token() {
+    ACTIVE_TOKEN[hart_id()].store(NO_USER_SPACE, Ordering::SeqCst);
+}
+
+/// 地址空间 `token` 中虚拟地址范围 [start, end) 的映射被修改之后调用，
+/// 刷新当前 hart 以及可能正在使用该地址空间的其他 hart 的 TLB
+pub fn flush_tlb(token: usize, start: VirtAddr, end: VirtAddr) {
+    let start = usize::from(start) & !(PAGE_SIZE - 1);
+    let end = usize::from(end);
+    if start >= end {
+        return;
+    }
+
+    // 页表的修改对其他 hart 可见之后，才检查哪些 hart 需要刷新
+    fence(Ordering::SeqCst);
+
+    local_flush(start, end);
+
+    let me = hart_id();
+    let mut hart_mask = if token == kernel_satp() {
+        online_hart_mask()
+    } else {
+        (0..MAX_HARTS)
+            .filter(|hart| ACTIVE_TOKEN[*hart].load(Ordering::SeqCst) == token)
+            .fold(0, |mask, hart| mask | (1 << hart))
+    };
+    hart_mask &= !(1 << me);
+
+    if hart_mask != 0 {
+        remote_sfence_vma(hart_mask, start, end - start);
+    }
+}
+
+fn local_flush(start: usize, end: usize) {
+    if (end - start) / PAGE_SIZE > FLUSH_ALL_THRESHOLD {
+        unsafe {
+            asm!("sfence.vma");
+        }
+        return;
+    }
+
+    for va in (start..end).step_by(PAGE_SIZE) {
+        unsafe {
+            asm!("sfence.vma {}, zero", in(reg) va);
+        }
+    }
+}
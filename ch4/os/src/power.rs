@@ -0,0 +1,38 @@
+// 关机
+//
+// QEMU virt 的 VIRT_TEST（"sifive,test1"）设备可以让 QEMU 以指定的状态退出：
+// - 写入 FINISHER_PASS 时 QEMU 以状态 0 退出；
+// - 写入 (code << 16) | FINISHER_FAIL 时 QEMU 以状态 code 退出。
+// 这样运行内核的脚本就能区分 "所有应用程序都已结束" 和 "内核 panic"。
+//
+// 写入之后 QEMU 没有退出（比如不是 QEMU virt）时，再通过 SBI 的 SRST 扩展关机，
+// 此时只能通过复位原因区分成功和失败。
+
+use sbi::{system_reset, RESET_REASON_SYSTEM_FAILURE, RESET_TYPE_SHUTDOWN};
+
+use crate::config::VIRT_TEST;
+
+const FINISHER_FAIL: u32 = 0x3333;
+const FINISHER_PASS: u32 = 0x5555;
+
+/// 关机时 QEMU 的退出状态
+#[derive(Copy, Clone, Debug)]
+pub enum ExitCode {
+    Success,
+    Failure(u16), // 不能为 0，否则 QEMU 的退出状态跟 Success 相同
+}
+
+pub fn shutdown(exit_code: ExitCode) -> ! {
+    let value = match exit_code {
+        ExitCode::Success => FINISHER_PASS,
+        ExitCode::Failure(code) => ((code as u32) << 16) | FINISHER_FAIL,
+    };
+    unsafe {
+        (VIRT_TEST as *mut u32).write_volatile(value);
+    }
+
+    if let ExitCode::Failure(_) = exit_code {
+        system_reset(RESET_TYPE_SHUTDOWN, RESET_REASON_SYSTEM_FAILURE);
+    }
+    sbi::shutdown()
+}
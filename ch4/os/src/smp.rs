@@ -0,0 +1,111 @@
+// 多核（SMP）启动
+//
+// 固件（RustSBI/OpenSBI）只让一个 hart（启动 hart）跳转到内核，其他 hart（从 hart）处于停止状态。
+// 启动 hart 完成内核的初始化之后，通过 SBI HSM 扩展的 `hart_start` 逐个启动从 hart，
+// 从 hart 从 `entry.asm` 的 `_start_secondary` 开始运行，开启分页和时钟中断之后进入各自的调度循环。
+//
+// 有些固件会让所有 hart 同时跳转到内核，所以启动 hart 通过 "抽签" 决定：
+// 第一个到达 `rust_main` 的 hart 成为启动 hart，其余的 hart 等待内核初始化完毕之后作为从 hart 运行。
+
+use core::{
+    arch::asm,
+    hint::spin_loop,
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+};
+
+use riscv::register::satp;
+use sbi::hart_start;
+
+use crate::{config::MAX_HARTS, timer::get_time_ms};
+
+/// 启动 hart 的 id。初始值不为 0，所以它位于 .data 段而不会被 `clear_bss` 清除
+static BOOT_HART: AtomicUsize = AtomicUsize::new(usize::MAX);
+
+/// 内核是否已经初始化完毕
+static KERNEL_READY: AtomicBool = AtomicBool::new(false);
+
+/// 内核地址空间的 token，从 hart 直接写入 satp 即可开启分页
+static KERNEL_SATP: AtomicUsize = AtomicUsize::new(0);
+
+/// 已经进入调度循环的 hart 的数量
+static ONLINE_HARTS: AtomicUsize = AtomicUsize::new(0);
+
+/// 已经进入调度循环的 hart，第 n 个 bit 对应 hart n
+static ONLINE_MASK: AtomicUsize = AtomicUsize::new(0);
+
+/// 等待从 hart 启动的最长时间
+const HART_START_TIMEOUT_MS: usize = 1000;
+
+/// 当前 hart 的 id，进入内核时被保存在 tp 寄存器（见 `entry.asm` 和 `trap.S`）
+pub fn hart_id() -> usize {
+    let id: usize;
+    unsafe {
+        asm!("mv {}, tp", out(reg) id);
+    }
+    id
+}
+
+/// 抽签：第一个调用者成为启动 hart 并返回 true
+pub fn claim_boot_hart(hart_id: usize) -> bool {
+    BOOT_HART
+        .compare_exchange(usize::MAX, hart_id, Ordering::AcqRel, Ordering::Acquire)
+        .is_ok()
+}
+
+/// 没有抽中的 hart 在此等待启动 hart 完成内核的初始化
+pub fn wait_for_boot() {
+    while !KERNEL_READY.load(Ordering::Acquire) {
+        spin_loop();
+    }
+}
+
+/// 启动 hart 在内核初始化完毕之后调用，启动其余的 hart，返回在线的 hart 的数量
+pub fn start_secondary_harts(boot_hart: usize) -> usize {
+    extern "C" {
+        fn _start_secondary();
+    }
+
+    KERNEL_SATP.store(satp::read().bits(), Ordering::Relaxed);
+    ONLINE_HARTS.store(1, Ordering::Relaxed);
+    ONLINE_MASK.store(1 << boot_hart, Ordering::Relaxed);
+    KERNEL_READY.store(true, Ordering::Release);
+
+    // QEMU virt 的 hart id 是连续的，不存在的 hart 会使 hart_start 返回错误
+    let mut started = 1;
+    for hart_id in (0..MAX_HARTS).filter(|id| *id != boot_hart) {
+        if hart_start(hart_id, _start_secondary as usize, 0).is_ok() {
+            started += 1;
+        }
+    }
+
+    let deadline = get_time_ms() + HART_START_TIMEOUT_MS;
+    while ONLINE_HARTS.load(Ordering::Acquire) < started && get_time_ms() < deadline {
+        spin_loop();
+    }
+
+    ONLINE_HARTS.load(Ordering::Acquire)
+}
+
+/// 从 hart 开启分页，使用跟启动 hart 相同的内核地址空间
+pub fn activate_kernel_space() {
+    unsafe {
+        satp::write(KERNEL_SATP.load(Ordering::Relaxed));
+        asm!("sfence.vma");
+    }
+}
+
+/// 从 hart 初始化完毕
+pub fn mark_online() {
+    ONLINE_MASK.fetch_or(1 << hart_id(), Ordering::AcqRel);
+    ONLINE_HARTS.fetch_add(1, Ordering::AcqRel);
+}
+
+/// 在线的 hart
+pub fn online_hart_mask() -> usize {
+    ONLINE_MASK.load(Ordering::Acquire)
+}
+
+/// 内核地址空间的 token，启动从 hart 之前为 0
+pub fn kernel_satp() -> usize {
+    KERNEL_SATP.load(Ordering::Relaxed)
+}
@@ -0,0 +1,48 @@
+// 多核之间的同步原语
+//
+// 之前的 `UPSafeCell` 只适用于单核：它依靠 "同一时刻只有一个控制流在运行" 来保证安全，
+// 一旦被重入（比如在持有 `TASK_MANAGER` 的期间发生了 trap）就会因为重复借用而 panic。
+// 启用多个 hart 以及内核态中断之后，共享的数据统一使用自旋锁保护：
+//
+// - `SpinLock::lock()`：普通的自旋锁；
+// - `SpinLock::lock_irqsave()`：获取锁之前关闭当前 hart 的中断（sstatus.SIE），
+//   释放锁之后恢复，避免持有锁的期间被中断处理程序重入而导致死锁。
+//
+// 开启 `lock-debug` feature 之后会检查加锁顺序，并在疑似死锁时打印警告，见 `sync/debug.rs`。
+//
+// 另外，`Mutex`、`Semaphore` 和 `Condvar` 是提供给应用程序（通过系统调用）使用的同步原语，
+// 等待它们的任务会被阻塞（TaskStatus::Blocked）并放入等待队列，而不是自旋或者反复 yield。
+// `futex` 则让应用程序可以在用户态实现锁，只在发生争用时才进入内核。
+
+mod condvar;
+#[cfg(feature = "lock-debug")]
+mod debug;
+mod futex;
+mod irq;
+mod mutex;
+mod semaphore;
+mod spin;
+
+pub use self::{
+    condvar::Condvar,
+    futex::{futex_wait, futex_wake},
+    mutex::Mutex,
+    semaphore::Semaphore,
+    spin::{SpinLock, SpinLockGuard},
+};
+
+/// 各个锁的加锁顺序：持有某个锁的期间只能再获取顺序值更大（或者相同）的锁。
+/// 0 表示不参与检查。
+///
+/// UART 排在最后，因为持有其他任何锁的期间都可能调用 `println!`。
+pub mod lock_order {
+    pub const USER_SYNC: usize = 1; // Mutex、Semaphore、Condvar 内部的锁，以及 futex 的等待队列
+    pub const TIMERS: usize = 2;
+    pub const PLIC: usize = 3;
+    pub const BLOCK_DEVICE: usize = 4;
+    pub const TASK_MANAGER: usize = 5;
+    pub const PROCESSOR: usize = 6;
+    pub const KERNEL_SPACE: usize = 7;
+    pub const FRAME_ALLOCATOR: usize = 8;
+    pub const UART: usize = 9;
+}
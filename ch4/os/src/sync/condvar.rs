@@ -0,0 +1,82 @@
+// 提供给应用程序的条件变量
+//
+// `wait` 先把当前任务放入等待队列，再释放互斥锁并阻塞，
+// 所以在释放互斥锁之后、阻塞之前发生的 `signal` 不会丢失（见 `block_current_and_run_next`）。
+// 跟通常的条件变量一样，`wait` 返回之后条件不一定成立，调用者需要循环检查条件。
+
+use alloc::collections::VecDeque;
+
+use crate::{
+    syscall::errno::Errno,
+    task::{block_current_and_run_next, current_has_pending_signal, current_task_id, wakeup_task},
+};
+
+use super::{lock_order, Mutex, SpinLock};
+
+pub struct Condvar {
+    inner: SpinLock<CondvarInner>,
+}
+
+struct CondvarInner {
+    wait_queue: VecDeque<usize>,
+}
+
+impl Default for Condvar {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Condvar {
+    pub fn new() -> Self {
+        Self {
+            inner: SpinLock::named(
+                CondvarInner {
+                    wait_queue: VecDeque::new(),
+                },
+                "USER_CONDVAR",
+                lock_order::USER_SYNC,
+            ),
+        }
+    }
+
+    /// 唤醒一个等待的任务
+    pub fn signal(&self) {
+        let waiter = self.inner.lock_irqsave().wait_queue.pop_front();
+        if let Some(task_id) = waiter {
+            wakeup_task(task_id);
+        }
+    }
+
+    /// 释放 `mutex` 并等待 `signal`，返回之前重新获取 `mutex`
+    ///
+    /// 被信号打断时返回 `EINTR`，此时同样已经重新获取了 `mutex`，调用者仍然需要释放它。
+    pub fn wait(&self, mutex: &Mutex) -> Result<(), Errno> {
+        let current = current_task_id();
+        self.inner.lock_irqsave().wait_queue.push_back(current);
+
+        // 没有持有 `mutex`
+        if let Err(errno) = mutex.unlock() {
+            self.inner.lock_irqsave().wait_queue.retain(|id| *id != current);
+            return Err(errno);
+        }
+
+        block_current_and_run_next();
+
+        // 被 `signal` 唤醒时已经不在队列里了，因为其他原因醒来时则需要自己移除
+        let still_queued = {
+            let mut inner = self.inner.lock_irqsave();
+            let len = inner.wait_queue.len();
+            inner.wait_queue.retain(|id| *id != current);
+            inner.wait_queue.len() < len
+        };
+        let interrupted = still_queued && current_has_pending_signal();
+
+        // 即使被信号打断也要等到重新获取 `mutex` 才返回
+        mutex.lock_uninterruptible()?;
+        if interrupted {
+            return Err(Errno::EINTR);
+        }
+        Ok(())
+    }
+}
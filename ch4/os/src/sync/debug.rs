@@ -0,0 +1,105 @@
+// 自旋锁的调试诊断（`lock-debug` feature）
+//
+// - 重复加锁：同一个 hart 获取自己已经持有的锁，必然死锁，直接 panic；
+// - 加锁顺序：每个 hart 记录自己持有的锁的顺序值（见 `sync::lock_order`），
+//   获取的锁的顺序值不能小于任何已持有的锁，否则两个 hart 以相反的顺序加锁时可能死锁，直接 panic；
+//   同一顺序值的锁（比如多个 USER_SYNC 锁）可以同时持有，所以每个顺序值都记录持有的数量；
+// - 疑似死锁：等待同一个锁的时间超过 `DEADLOCK_WARN_MS`，打印该锁的名称以及持有它的 hart。
+//   输出本身需要获取 PRINT_LOCK 和 UART 的锁，所以等待这两个锁时不打印。
+
+use core::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+
+use crate::{config::MAX_HARTS, smp::hart_id, timer::get_time_ms};
+
+use super::lock_order;
+
+/// 等待一个锁超过该时间则认为疑似死锁
+pub const DEADLOCK_WARN_MS: usize = 1000;
+
+/// `console::PRINT_LOCK` 的名称
+pub const PRINT_LOCK_NAME: &str = "PRINT_LOCK";
+
+/// 顺序值的上限，即 `HELD_ORDERS` 的位数
+const MAX_ORDER: usize = 64;
+
+#[allow(clippy::declare_interior_mutable_const)]
+const EMPTY: AtomicU64 = AtomicU64::new(0);
+#[allow(clippy::declare_interior_mutable_const)]
+const ZERO: AtomicU32 = AtomicU32::new(0);
+#[allow(clippy::declare_interior_mutable_const)]
+const ZEROS: [AtomicU32; MAX_ORDER] = [ZERO; MAX_ORDER];
+
+/// 每个 hart 当前持有的锁的顺序值，第 n 个 bit 对应顺序值 n
+static HELD_ORDERS: [AtomicU64; MAX_HARTS] = [EMPTY; MAX_HARTS];
+
+/// 每个 hart 持有的各个顺序值的锁的数量，数量降为 0 时才清除 `HELD_ORDERS` 里对应的 bit
+static HELD_COUNTS: [[AtomicU32; MAX_ORDER]; MAX_HARTS] = [ZEROS; MAX_HARTS];
+
+/// 获取锁之前检查
+pub fn check_acquire(name: &str, order: usize, owner: usize) {
+    let hart = hart_id();
+    if owner == hart {
+        panic!("lock `{}` acquired twice on hart {}", name, hart);
+    }
+
+    if order == 0 {
+        return;
+    }
+
+    let held = HELD_ORDERS[hart].load(Ordering::Relaxed);
+    if held >> (order + 1) != 0 {
+        panic!(
+            "lock order violation on hart {}: acquiring `{}` (order {}) while holding locks of order {:#b}",
+            hart, name, order, held
+        );
+    }
+}
+
+pub fn on_acquired(order: usize) {
+    if order != 0 {
+        let hart = hart_id();
+        HELD_COUNTS[hart][order].fetch_add(1, Ordering::Relaxed);
+        HELD_ORDERS[hart].fetch_or(1 << order, Ordering::Relaxed);
+    }
+}
+
+pub fn on_release(order: usize) {
+    if order != 0 {
+        let hart = hart_id();
+        if HELD_COUNTS[hart][order].fetch_sub(1, Ordering::Relaxed) == 1 {
+            HELD_ORDERS[hart].fetch_and(!(1 << order), Ordering::Relaxed);
+        }
+    }
+}
+
+/// 记录开始等待锁的时间，等待过久时打印一次警告
+pub struct SpinWatch {
+    start_ms: usize,
+    warned: bool,
+}
+
+impl SpinWatch {
+    pub fn new() -> Self {
+        Self {
+            start_ms: get_time_ms(),
+            warned: false,
+        }
+    }
+
+    pub fn check(&mut self, name: &str, order: usize, owner: usize) {
+        // 输出警告需要获取这两个锁，如果正在等待的就是它们，输出只会一起卡住
+        if name == PRINT_LOCK_NAME || order == lock_order::UART {
+            return;
+        }
+
+        if !self.warned && get_time_ms() - self.start_ms > DEADLOCK_WARN_MS {
+            self.warned = true;
+            println!(
+                "[kernel] possible deadlock: hart {} has been waiting for lock `{}` held by hart {}",
+                hart_id(),
+                name,
+                owner
+            );
+        }
+    }
+}
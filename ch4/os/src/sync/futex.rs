@@ -0,0 +1,90 @@
+// futex（fast userspace mutex）
+//
+// 应用程序在用户态通过原子操作实现锁，只有发生争用时才通过 `sys_futex` 进入内核等待或者唤醒。
+// 等待队列以用户地址对应的物理地址为键，所以即使不同的虚拟地址映射到同一个物理页面，
+// 它们也会使用同一个等待队列。
+//
+// `futex_wait` 在持有 `FUTEX_QUEUES` 锁的期间检查用户内存里的值并加入等待队列，
+// `futex_wake` 也需要先获取该锁，所以 "检查值 -> 等待" 和 "修改值 -> 唤醒" 之间不会丢失唤醒。
+
+use alloc::collections::{BTreeMap, VecDeque};
+use core::sync::atomic::{AtomicU32, Ordering};
+use lazy_static::lazy_static;
+
+use crate::{
+    mm::address::PhysAddr,
+    syscall::errno::Errno,
+    task::{
+        block_current_and_run_next, current_has_pending_signal, current_task_id, wakeup_task,
+    },
+};
+
+use super::{lock_order, SpinLock};
+
+lazy_static! {
+    /// 物理地址 -> 在该地址上等待的任务
+    static ref FUTEX_QUEUES: SpinLock<BTreeMap<usize, VecDeque<usize>>> =
+        SpinLock::named(BTreeMap::new(), "FUTEX", lock_order::USER_SYNC);
+}
+
+/// 如果物理地址 `pa` 处的值等于 `expected` 则阻塞，直到被 `futex_wake` 唤醒
+///
+/// 值不相等时返回 `EAGAIN`，等待期间收到信号时返回 `EINTR`。
+/// 跟 Linux 一样，调用者需要在返回之后重新检查值（可能是因为其他原因醒来的）。
+pub fn futex_wait(pa: PhysAddr, expected: u32) -> Result<(), Errno> {
+    let key = usize::from(pa);
+    let current = current_task_id();
+
+    let mut queues = FUTEX_QUEUES.lock_irqsave();
+    let value = unsafe { (*(key as *const AtomicU32)).load(Ordering::SeqCst) };
+    if value != expected {
+        return Err(Errno::EAGAIN);
+    }
+    queues.entry(key).or_default().push_back(current);
+    drop(queues);
+
+    if !current_has_pending_signal() {
+        block_current_and_run_next();
+    }
+
+    // 被 `futex_wake` 唤醒时已经不在队列里了，因为其他原因醒来时则需要自己移除
+    remove_waiter(key, current);
+
+    if current_has_pending_signal() {
+        return Err(Errno::EINTR);
+    }
+    Ok(())
+}
+
+/// 唤醒最多 `count` 个在物理地址 `pa` 上等待的任务，返回被唤醒的任务数量
+pub fn futex_wake(pa: PhysAddr, count: usize) -> usize {
+    let key = usize::from(pa);
+    let mut queues = FUTEX_QUEUES.lock_irqsave();
+    let waiters: VecDeque<usize> = match queues.get_mut(&key) {
+        Some(queue) => {
+            let n = count.min(queue.len());
+            let waiters = queue.drain(..n).collect();
+            if queue.is_empty() {
+                queues.remove(&key);
+            }
+            waiters
+        }
+        None => VecDeque::new(),
+    };
+    drop(queues);
+
+    for task_id in waiters.iter() {
+        wakeup_task(*task_id);
+    }
+    waiters.len()
+}
+
+fn remove_waiter(key: usize, task_id: usize) {
+    let mut queues = FUTEX_QUEUES.lock_irqsave();
+    if let Some(queue) = queues.get_mut(&key) {
+        queue.retain(|id| *id != task_id);
+        if queue.is_empty() {
+            queues.remove(&key);
+        }
+    }
+}
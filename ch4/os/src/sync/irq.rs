@@ -0,0 +1,48 @@
+// 关闭/恢复当前 hart 的中断
+//
+// 获取多个 IRQ-saving 锁时会嵌套地关闭中断，所以记录嵌套的层数，
+// 只有最外层释放时才恢复最初的中断状态。
+
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use riscv::register::sstatus;
+
+use crate::{config::MAX_HARTS, smp::hart_id};
+
+#[allow(clippy::declare_interior_mutable_const)]
+const ZERO: AtomicUsize = AtomicUsize::new(0);
+#[allow(clippy::declare_interior_mutable_const)]
+const FALSE: AtomicBool = AtomicBool::new(false);
+
+/// 每个 hart 关闭中断的嵌套层数
+static DEPTH: [AtomicUsize; MAX_HARTS] = [ZERO; MAX_HARTS];
+
+/// 每个 hart 在最外层关闭中断之前，中断是否是开启的
+static WAS_ENABLED: [AtomicBool; MAX_HARTS] = [FALSE; MAX_HARTS];
+
+/// 关闭当前 hart 的中断
+pub fn push_off() {
+    let enabled = sstatus::read().sie();
+    unsafe {
+        sstatus::clear_sie();
+    }
+
+    // 中断关闭之后当前 hart 的这两个变量不会再被其他控制流修改
+    let hart = hart_id();
+    if DEPTH[hart].fetch_add(1, Ordering::Relaxed) == 0 {
+        WAS_ENABLED[hart].store(enabled, Ordering::Relaxed);
+    }
+}
+
+/// 与 `push_off` 配对，最外层的 `pop_off` 恢复原来的中断状态
+pub fn pop_off() {
+    let hart = hart_id();
+    let depth = DEPTH[hart].fetch_sub(1, Ordering::Relaxed);
+    assert!(depth > 0, "pop_off without push_off");
+
+    if depth == 1 && WAS_ENABLED[hart].load(Ordering::Relaxed) {
+        unsafe {
+            sstatus::set_sie();
+        }
+    }
+}
@@ -0,0 +1,102 @@
+// 提供给应用程序的互斥锁
+//
+// 获取锁失败的任务被放入等待队列并阻塞，锁被释放时唤醒队首的任务，
+// 被唤醒的任务重新尝试获取锁（锁可能已经被其他任务抢先获取了，此时再次阻塞）。
+//
+// 任务可能因为其他原因（比如收到信号）而醒来，所以一个任务在队列里最多只出现一次，
+// 并且不再等待时（获取了锁或者放弃等待）要把自己从队列里移除，
+// 否则释放锁时可能唤醒一个并没有在等待的任务，而真正等待的任务则一直阻塞下去。
+
+use alloc::collections::VecDeque;
+
+use crate::{
+    syscall::errno::Errno,
+    task::{
+        block_current_and_run_next, current_has_pending_signal, current_task_id, wakeup_task,
+    },
+};
+
+use super::{lock_order, SpinLock};
+
+pub struct Mutex {
+    inner: SpinLock<MutexInner>,
+}
+
+struct MutexInner {
+    owner: Option<usize>,        // 持有锁的任务
+    wait_queue: VecDeque<usize>, // 等待获取锁的任务
+}
+
+impl Default for Mutex {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Mutex {
+    pub fn new() -> Self {
+        Self {
+            inner: SpinLock::named(
+                MutexInner {
+                    owner: None,
+                    wait_queue: VecDeque::new(),
+                },
+                "USER_MUTEX",
+                lock_order::USER_SYNC,
+            ),
+        }
+    }
+
+    /// 获取锁，等待期间收到信号时返回 `EINTR`，重复获取自己已经持有的锁时返回 `EDEADLK`
+    pub fn lock(&self) -> Result<(), Errno> {
+        self.lock_inner(true)
+    }
+
+    /// 获取锁，不会因为信号而放弃等待，用于必须持有锁才能返回的场合（见 `Condvar::wait`）
+    pub fn lock_uninterruptible(&self) -> Result<(), Errno> {
+        self.lock_inner(false)
+    }
+
+    fn lock_inner(&self, interruptible: bool) -> Result<(), Errno> {
+        let current = current_task_id();
+        loop {
+            let mut inner = self.inner.lock_irqsave();
+            match inner.owner {
+                None => {
+                    inner.owner = Some(current);
+                    inner.wait_queue.retain(|id| *id != current);
+                    return Ok(());
+                }
+                Some(owner) if owner == current => return Err(Errno::EDEADLK),
+                Some(_) => {
+                    if !inner.wait_queue.contains(&current) {
+                        inner.wait_queue.push_back(current);
+                    }
+                }
+            }
+            drop(inner);
+
+            if interruptible && current_has_pending_signal() {
+                self.inner.lock_irqsave().wait_queue.retain(|id| *id != current);
+                return Err(Errno::EINTR);
+            }
+            block_current_and_run_next();
+        }
+    }
+
+    /// 释放锁，只有持有锁的任务才能释放，否则返回 `EPERM`
+    pub fn unlock(&self) -> Result<(), Errno> {
+        let mut inner = self.inner.lock_irqsave();
+        if inner.owner != Some(current_task_id()) {
+            return Err(Errno::EPERM);
+        }
+        inner.owner = None;
+        let waiter = inner.wait_queue.pop_front();
+        drop(inner);
+
+        if let Some(task_id) = waiter {
+            wakeup_task(task_id);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,73 @@
+// 提供给应用程序的信号量
+//
+// `down`（P 操作）在计数为 0 时阻塞，`up`（V 操作）增加计数并唤醒一个等待的任务。
+// 等待队列的维护方式跟 `Mutex` 相同：一个任务在队列里最多只出现一次，不再等待时从队列里移除。
+
+use alloc::collections::VecDeque;
+
+use crate::{
+    syscall::errno::Errno,
+    task::{
+        block_current_and_run_next, current_has_pending_signal, current_task_id, wakeup_task,
+    },
+};
+
+use super::{lock_order, SpinLock};
+
+pub struct Semaphore {
+    inner: SpinLock<SemaphoreInner>,
+}
+
+struct SemaphoreInner {
+    count: usize,                // 剩余的资源数量
+    wait_queue: VecDeque<usize>, // 等待资源的任务
+}
+
+impl Semaphore {
+    pub fn new(count: usize) -> Self {
+        Self {
+            inner: SpinLock::named(
+                SemaphoreInner {
+                    count,
+                    wait_queue: VecDeque::new(),
+                },
+                "USER_SEMAPHORE",
+                lock_order::USER_SYNC,
+            ),
+        }
+    }
+
+    pub fn up(&self) {
+        let mut inner = self.inner.lock_irqsave();
+        inner.count += 1;
+        let waiter = inner.wait_queue.pop_front();
+        drop(inner);
+
+        if let Some(task_id) = waiter {
+            wakeup_task(task_id);
+        }
+    }
+
+    /// 等待期间收到信号时返回 `EINTR`
+    pub fn down(&self) -> Result<(), Errno> {
+        let current = current_task_id();
+        loop {
+            let mut inner = self.inner.lock_irqsave();
+            if inner.count > 0 {
+                inner.count -= 1;
+                inner.wait_queue.retain(|id| *id != current);
+                return Ok(());
+            }
+            if !inner.wait_queue.contains(&current) {
+                inner.wait_queue.push_back(current);
+            }
+            drop(inner);
+
+            if current_has_pending_signal() {
+                self.inner.lock_irqsave().wait_queue.retain(|id| *id != current);
+                return Err(Errno::EINTR);
+            }
+            block_current_and_run_next();
+        }
+    }
+}
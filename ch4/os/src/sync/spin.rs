@@ -0,0 +1,160 @@
+// 自旋锁
+//
+// 获取锁失败的 hart 会一直循环等待（自旋），直到锁被释放。
+// 持有锁的期间不能切换任务（`__switch`），否则其他 hart 可能会一直等待下去。
+
+use core::{
+    cell::UnsafeCell,
+    hint::spin_loop,
+    ops::{Deref, DerefMut},
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+};
+
+use crate::smp::hart_id;
+
+use super::irq::{pop_off, push_off};
+
+#[cfg(feature = "lock-debug")]
+use super::debug;
+
+/// 没有被任何 hart 持有
+const NO_OWNER: usize = usize::MAX;
+
+pub struct SpinLock<T> {
+    locked: AtomicBool,
+    owner: AtomicUsize, // 持有锁的 hart 的 id，用于诊断
+    #[cfg_attr(not(feature = "lock-debug"), allow(dead_code))]
+    name: &'static str,
+    #[cfg_attr(not(feature = "lock-debug"), allow(dead_code))]
+    order: usize, // 加锁顺序，见 `sync::lock_order`
+    data: UnsafeCell<T>,
+}
+
+unsafe impl<T: Send> Sync for SpinLock<T> {}
+unsafe impl<T: Send> Send for SpinLock<T> {}
+
+pub struct SpinLockGuard<'a, T> {
+    lock: &'a SpinLock<T>,
+    irq_saved: bool, // 获取锁时是否关闭了中断，释放时需要恢复
+}
+
+impl<T> SpinLock<T> {
+    /// 不参与加锁顺序检查的锁
+    pub const fn new(value: T) -> Self {
+        Self::named(value, "unnamed", 0)
+    }
+
+    /// 带有名称和加锁顺序的锁，名称用于诊断信息
+    pub const fn named(value: T, name: &'static str, order: usize) -> Self {
+        Self {
+            locked: AtomicBool::new(false),
+            owner: AtomicUsize::new(NO_OWNER),
+            name,
+            order,
+            data: UnsafeCell::new(value),
+        }
+    }
+
+    /// 获取锁，不改变中断状态
+    pub fn lock(&self) -> SpinLockGuard<'_, T> {
+        self.acquire();
+        SpinLockGuard {
+            lock: self,
+            irq_saved: false,
+        }
+    }
+
+    /// 关闭当前 hart 的中断之后再获取锁，guard 被释放时恢复原来的中断状态
+    ///
+    /// 会在中断处理程序里使用的数据必须使用这种方式加锁。
+    pub fn lock_irqsave(&self) -> SpinLockGuard<'_, T> {
+        push_off();
+        self.acquire();
+        SpinLockGuard {
+            lock: self,
+            irq_saved: true,
+        }
+    }
+
+    fn acquire(&self) {
+        #[cfg(feature = "lock-debug")]
+        debug::check_acquire(self.name, self.order, self.owner.load(Ordering::Relaxed));
+        #[cfg(feature = "lock-debug")]
+        let mut watch = debug::SpinWatch::new();
+
+        while self
+            .locked
+            .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            // 先以只读的方式等待，减少缓存行在 hart 之间的争用
+            while self.locked.load(Ordering::Relaxed) {
+                #[cfg(feature = "lock-debug")]
+                watch.check(self.name, self.order, self.owner.load(Ordering::Relaxed));
+                spin_loop();
+            }
+        }
+
+        self.owner.store(hart_id(), Ordering::Relaxed);
+
+        #[cfg(feature = "lock-debug")]
+        debug::on_acquired(self.order);
+    }
+
+    fn release(&self) {
+        #[cfg(feature = "lock-debug")]
+        debug::on_release(self.order);
+
+        self.owner.store(NO_OWNER, Ordering::Relaxed);
+        self.locked.store(false, Ordering::Release);
+    }
+
+    /// 跟 `lock_irqsave` 一样，但锁已经被持有时立即返回 None，不会自旋等待
+    ///
+    /// 仅用于 panic 时（见 `console::enter_panic_mode`）：持有锁的可能正是 panic 的 hart 自己，
+    /// 所以也不做加锁顺序检查。
+    pub fn try_lock_irqsave(&self) -> Option<SpinLockGuard<'_, T>> {
+        push_off();
+        if self
+            .locked
+            .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            pop_off();
+            return None;
+        }
+
+        self.owner.store(hart_id(), Ordering::Relaxed);
+
+        #[cfg(feature = "lock-debug")]
+        debug::on_acquired(self.order);
+
+        Some(SpinLockGuard {
+            lock: self,
+            irq_saved: true,
+        })
+    }
+}
+
+impl<'a, T> Deref for SpinLockGuard<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<'a, T> DerefMut for SpinLockGuard<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+impl<'a, T> Drop for SpinLockGuard<'a, T> {
+    fn drop(&mut self) {
+        self.lock.release();
+        if self.irq_saved {
+            pop_off();
+        }
+    }
+}
@@ -1,21 +1,132 @@
-use self::{fs::sys_write, process::{sys_exit, sys_yield, sys_get_time}};
+use self::{
+    errno::{Errno, SyscallResult},
+    fs::{sys_close, sys_dup, sys_fstat, sys_lseek, sys_openat, sys_pipe, sys_read, sys_write},
+    process::{
+        sys_clock_gettime, sys_exit, sys_getpid, sys_getrlimit, sys_gettimeofday, sys_kill,
+        sys_nanosleep, sys_setrlimit, sys_sigaction, sys_sigprocmask, sys_sigreturn, sys_spawn,
+        sys_task_info, sys_waitpid, sys_yield,
+    },
+    sync::{
+        sys_condvar_create, sys_condvar_signal, sys_condvar_wait, sys_futex, sys_mutex_create,
+        sys_mutex_lock, sys_mutex_unlock, sys_semaphore_create, sys_semaphore_down,
+        sys_semaphore_up,
+    },
+    thread::{sys_gettid, sys_thread_create, sys_waittid},
+};
+use crate::task::record_syscall;
 
+pub mod errno;
 mod fs;
 mod process;
+mod sync;
+mod thread;
 
+const SYSCALL_DUP: usize = 23;
+const SYSCALL_OPENAT: usize = 56;
+const SYSCALL_CLOSE: usize = 57;
+const SYSCALL_PIPE: usize = 59;
+const SYSCALL_LSEEK: usize = 62;
+const SYSCALL_READ: usize = 63;
 const SYSCALL_WRITE: usize = 64;
+const SYSCALL_FSTAT: usize = 80;
 const SYSCALL_EXIT: usize = 93;
+const SYSCALL_FUTEX: usize = 98;
+const SYSCALL_NANOSLEEP: usize = 101;
+const SYSCALL_CLOCK_GETTIME: usize = 113;
 
 // Ch3 新增
 const SYSCALL_YIELD: usize = 124;
-const SYSCALL_GET_TIME: usize = 169;
+const SYSCALL_GETTIMEOFDAY: usize = 169;
+const SYSCALL_GETPID: usize = 172;
 
-pub fn syscall(syscall_id: usize, args: [usize; 3]) -> isize {
-    match syscall_id {
+// 进程
+const SYSCALL_WAITPID: usize = 260;
+const SYSCALL_SPAWN: usize = 400;
+
+// 信号
+const SYSCALL_KILL: usize = 129;
+const SYSCALL_SIGACTION: usize = 134;
+const SYSCALL_SIGPROCMASK: usize = 135;
+const SYSCALL_SIGRETURN: usize = 139;
+
+// 资源限制
+const SYSCALL_GETRLIMIT: usize = 163;
+const SYSCALL_SETRLIMIT: usize = 164;
+
+// 统计信息
+const SYSCALL_TASK_INFO: usize = 410;
+
+// 线程
+const SYSCALL_GETTID: usize = 178;
+const SYSCALL_THREAD_CREATE: usize = 1000;
+const SYSCALL_WAITTID: usize = 1002;
+
+// 同步原语
+const SYSCALL_MUTEX_CREATE: usize = 1010;
+const SYSCALL_MUTEX_LOCK: usize = 1011;
+const SYSCALL_MUTEX_UNLOCK: usize = 1012;
+const SYSCALL_SEMAPHORE_CREATE: usize = 1020;
+const SYSCALL_SEMAPHORE_UP: usize = 1021;
+const SYSCALL_SEMAPHORE_DOWN: usize = 1022;
+const SYSCALL_CONDVAR_CREATE: usize = 1030;
+const SYSCALL_CONDVAR_SIGNAL: usize = 1031;
+const SYSCALL_CONDVAR_WAIT: usize = 1032;
+
+/// 系统调用的分发
+///
+/// 按照 RISC-V Linux 的约定，系统调用号放在 a7，参数依次放在 a0~a5。
+/// 成功时返回非负数，失败时返回错误码的相反数；不支持的系统调用返回 `-ENOSYS`。
+pub fn syscall(syscall_id: usize, args: [usize; 6]) -> isize {
+    record_syscall(syscall_id);
+
+    let result: SyscallResult = match syscall_id {
+        SYSCALL_OPENAT => sys_openat(
+            args[0] as isize,
+            args[1] as *const u8,
+            args[2] as u32,
+            args[3] as u32,
+        ),
+        SYSCALL_DUP => sys_dup(args[0]),
+        SYSCALL_CLOSE => sys_close(args[0]),
+        SYSCALL_PIPE => sys_pipe(args[0] as *mut _),
+        SYSCALL_LSEEK => sys_lseek(args[0], args[1] as isize, args[2]),
+        SYSCALL_READ => sys_read(args[0], args[1] as *mut u8, args[2]),
         SYSCALL_WRITE => sys_write(args[0], args[1] as *const u8, args[2]),
+        SYSCALL_FSTAT => sys_fstat(args[0], args[1] as *mut _),
         SYSCALL_EXIT => sys_exit(args[0] as i32),
+        SYSCALL_FUTEX => sys_futex(args[0], args[1], args[2]),
+        SYSCALL_NANOSLEEP => sys_nanosleep(args[0] as *const _, args[1] as *mut _),
+        SYSCALL_CLOCK_GETTIME => sys_clock_gettime(args[0], args[1] as *mut _),
         SYSCALL_YIELD => sys_yield(),
-        SYSCALL_GET_TIME => sys_get_time(),
-        _ => panic!("Unsupported syscall_id: {}", syscall_id),
+        SYSCALL_GETTIMEOFDAY => sys_gettimeofday(args[0] as *mut _, args[1]),
+        SYSCALL_GETPID => sys_getpid(),
+        SYSCALL_KILL => sys_kill(args[0], args[1]),
+        SYSCALL_WAITPID => sys_waitpid(args[0] as isize, args[1] as *mut i32),
+        SYSCALL_SPAWN => sys_spawn(args[0] as *const u8),
+        SYSCALL_SIGACTION => sys_sigaction(args[0], args[1] as *const _, args[2] as *mut _),
+        SYSCALL_SIGPROCMASK => sys_sigprocmask(args[0] as u32),
+        SYSCALL_SIGRETURN => sys_sigreturn(),
+        SYSCALL_GETRLIMIT => sys_getrlimit(args[0], args[1] as *mut _),
+        SYSCALL_SETRLIMIT => sys_setrlimit(args[0], args[1] as *const _),
+        SYSCALL_TASK_INFO => sys_task_info(args[0] as *mut _),
+        SYSCALL_GETTID => sys_gettid(),
+        SYSCALL_THREAD_CREATE => sys_thread_create(args[0], args[1]),
+        SYSCALL_WAITTID => sys_waittid(args[0], args[1] as *mut i32),
+        SYSCALL_MUTEX_CREATE => sys_mutex_create(),
+        SYSCALL_MUTEX_LOCK => sys_mutex_lock(args[0]),
+        SYSCALL_MUTEX_UNLOCK => sys_mutex_unlock(args[0]),
+        SYSCALL_SEMAPHORE_CREATE => sys_semaphore_create(args[0]),
+        SYSCALL_SEMAPHORE_UP => sys_semaphore_up(args[0]),
+        SYSCALL_SEMAPHORE_DOWN => sys_semaphore_down(args[0]),
+        SYSCALL_CONDVAR_CREATE => sys_condvar_create(),
+        SYSCALL_CONDVAR_SIGNAL => sys_condvar_signal(args[0]),
+        SYSCALL_CONDVAR_WAIT => sys_condvar_wait(args[0], args[1]),
+        // 不打印信息，否则应用程序可以通过不断调用不存在的系统调用刷屏
+        _ => Err(Errno::ENOSYS),
+    };
+
+    match result {
+        Ok(value) => value as isize,
+        Err(errno) => -(errno as isize),
     }
 }
@@ -0,0 +1,33 @@
+// 系统调用的错误码，数值跟 Linux 一致
+//
+// 系统调用成功时返回一个非负数，失败时返回错误码的相反数，比如 `-ENOSYS`。
+// 内核里的系统调用函数统一返回 `SyscallResult`，由 `syscall()` 转换为 isize。
+//
+// https://man7.org/linux/man-pages/man3/errno.3.html
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[repr(isize)]
+#[allow(clippy::upper_case_acronyms, dead_code)]
+pub enum Errno {
+    EPERM = 1,         // Operation not permitted
+    ENOENT = 2,        // No such file or directory
+    ESRCH = 3,         // No such process
+    EINTR = 4,         // Interrupted system call
+    EIO = 5,           // I/O error
+    ENOEXEC = 8,       // Exec format error
+    EBADF = 9,         // Bad file descriptor
+    ECHILD = 10,       // No child processes
+    EAGAIN = 11,       // Resource temporarily unavailable
+    ENOMEM = 12,       // Out of memory
+    EFAULT = 14,       // Bad address
+    ENOTDIR = 20,      // Not a directory
+    EINVAL = 22,       // Invalid argument
+    ENOSPC = 28,       // No space left on device
+    ESPIPE = 29,       // Illegal seek
+    EPIPE = 32,        // Broken pipe
+    EDEADLK = 35,      // Resource deadlock avoided
+    ENAMETOOLONG = 36, // File name too long
+    ENOSYS = 38,       // Function not implemented
+}
+
+pub type SyscallResult = Result<usize, Errno>;
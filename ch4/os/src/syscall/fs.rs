@@ -1,28 +1,208 @@
-use crate::{mm::page_table::translated_byte_buffer, task::current_user_token};
-
-const FD_STDOUT: usize = 1;
-
-pub fn sys_write(fd: usize, buf: *const u8, len: usize) -> isize {
-    match fd {
-        FD_STDOUT => {
-            // ch4 MODIFY:
-            //
-            // 因为内核和应用程序的内存空间不一样，这里传过来的 buf 指针是应用程序空间的地址。
-            // let slice = unsafe { core::slice::from_raw_parts(buf, len) };
-            // let str = core::str::from_utf8(slice).unwrap();
-            // print!("{}", str);
-
-            // 不过内核可以访问应用程序的内存空间的任何角落，只需获取应用程序的 root ppn，然后通过查表就能得到
-            // 物理地址，然后内核的内存空间是根物理空间一一对应的，所以可以直接访问物理空间的数据
-            let buffers = translated_byte_buffer(current_user_token(), buf, len);
-            for buffer in buffers {
-                print!("{}", core::str::from_utf8(buffer).unwrap());
-            }
-
-            len as isize
+use alloc::{string::String, vec};
+
+use crate::{
+    config::PAGE_SIZE,
+    fs::{make_pipe, open_file, OpenFlags, Stat},
+    mm::page_table::{copy_str_from_user, copy_to_user, UserAccess, UserBuffer},
+    task::{current_user_token, with_current_process},
+};
+
+use super::errno::{Errno, SyscallResult};
+
+/// `openat` 的 dirfd 为该值时，相对路径从当前目录（即根目录）开始
+const AT_FDCWD: isize = -100;
+
+/// 路径的最大长度，包括末尾的 `\0`
+const PATH_MAX: usize = 256;
+
+/// `read`/`write` 每次在内核缓冲区和应用程序的缓冲区之间复制的最大字节数
+const IO_CHUNK_SIZE: usize = PAGE_SIZE;
+
+// ch4 MODIFY:
+// 不再只支持标准输入输出，而是通过当前进程的文件描述符表找到对应的文件（见 `fs::File`）。
+//
+// 因为内核和应用程序的内存空间不一样，这里传过来的 buf 指针是应用程序空间的地址。
+// 不过内核可以访问应用程序的内存空间的任何角落，只需获取应用程序的 root ppn，然后通过查表就能得到
+// 物理地址，然后内核的内存空间是根物理空间一一对应的，所以可以直接访问物理空间的数据
+//
+// 缓冲区的某一部分没有映射时返回 EFAULT，此时不读写任何数据
+//
+// 读写文件可能会阻塞（比如管道），阻塞期间同一个进程的其他线程可能退出或者取消映射，
+// 缓冲区所在的页帧随之被回收。所以文件只读写内核的缓冲区，每次最多 `IO_CHUNK_SIZE` 字节，
+// 在持有 TaskManager 的锁（取消映射也需要这个锁）的期间重新查页表，再跟应用程序的缓冲区互相复制。
+// 开始读写之后才发现缓冲区无效时，返回已经读写的字节数。
+
+pub fn sys_write(fd: usize, buf: *const u8, len: usize) -> SyscallResult {
+    // 先复制出文件再释放 TaskManager 的锁，因为写入的过程中可能会阻塞（比如管道）
+    let file = with_current_process(|process| process.get_file(fd)).ok_or(Errno::EBADF)?;
+    if !file.writable() {
+        return Err(Errno::EBADF);
+    }
+    UserBuffer::from_user(current_user_token(), buf, len, UserAccess::Read).ok_or(Errno::EFAULT)?;
+
+    let mut chunk = vec![0u8; len.min(IO_CHUNK_SIZE)];
+    let mut count = 0;
+    while count < len {
+        let size = chunk.len().min(len - count);
+        let copied = with_current_process(|process| {
+            let user_buf = UserBuffer::from_user(
+                process.memory_set.token(),
+                buf.wrapping_add(count),
+                size,
+                UserAccess::Read,
+            )?;
+            user_buf.copy_to_slice(&mut chunk[..size]);
+            Some(())
+        });
+        if copied.is_none() {
+            return partial(count, Errno::EFAULT);
         }
-        _ => {
-            panic!("Unsupported fd in sys_write!");
+
+        let n = match file.write(&chunk[..size]) {
+            Ok(n) => n,
+            Err(errno) => return partial(count, errno),
+        };
+        count += n;
+        if n < size {
+            break;
         }
     }
+    Ok(count)
+}
+
+/// 从文件读取数据，返回实际读取的字节数
+///
+/// 标准输入至少读取一个字符：没有输入时当前任务会被阻塞，直到 UART 中断将其唤醒；
+/// 等待期间收到信号时返回 EINTR。
+pub fn sys_read(fd: usize, buf: *mut u8, len: usize) -> SyscallResult {
+    let file = with_current_process(|process| process.get_file(fd)).ok_or(Errno::EBADF)?;
+    if !file.readable() {
+        return Err(Errno::EBADF);
+    }
+    // 阻塞之前先检查缓冲区是否有效，避免读取了数据之后才发现无处存放
+    UserBuffer::from_user(current_user_token(), buf, len, UserAccess::Write)
+        .ok_or(Errno::EFAULT)?;
+
+    let mut chunk = vec![0u8; len.min(IO_CHUNK_SIZE)];
+    let mut count = 0;
+    while count < len {
+        let size = chunk.len().min(len - count);
+        let n = match file.read(&mut chunk[..size]) {
+            Ok(n) => n,
+            Err(errno) => return partial(count, errno),
+        };
+        let copied = with_current_process(|process| {
+            let mut user_buf = UserBuffer::from_user(
+                process.memory_set.token(),
+                buf.wrapping_add(count),
+                n,
+                UserAccess::Write,
+            )?;
+            user_buf.copy_from_slice(&chunk[..n]);
+            Some(())
+        });
+        if copied.is_none() {
+            return partial(count, Errno::EFAULT);
+        }
+
+        count += n;
+        // 管道和标准输入的缓冲区都比 `IO_CHUNK_SIZE` 小，一次读不满，不会在这里继续读取而阻塞；
+        // 只有普通文件会继续读取下一块
+        if n < size {
+            break;
+        }
+    }
+    Ok(count)
+}
+
+/// 已经读写了 `count` 字节时返回 `count`，否则返回 `errno`
+fn partial(count: usize, errno: Errno) -> SyscallResult {
+    if count > 0 {
+        Ok(count)
+    } else {
+        Err(errno)
+    }
+}
+
+/// 复制文件描述符 `fd`，返回新的文件描述符（编号最小的空闲描述符），两者指向同一个打开的文件
+pub fn sys_dup(fd: usize) -> SyscallResult {
+    with_current_process(|process| {
+        let file = process.get_file(fd)?;
+        Some(process.alloc_fd(file))
+    })
+    .ok_or(Errno::EBADF)
+}
+
+/// 关闭文件描述符 `fd`
+pub fn sys_close(fd: usize) -> SyscallResult {
+    let file = with_current_process(|process| process.fd_table.get_mut(fd)?.take());
+    // 在释放 TaskManager 的锁之后才 drop，关闭管道时会唤醒另一端的任务
+    drop(file.ok_or(Errno::EBADF)?);
+    Ok(0)
+}
+
+/// 创建一个管道，读端和写端的文件描述符依次写入 `fds`
+pub fn sys_pipe(fds: *mut [u32; 2]) -> SyscallResult {
+    let (read_end, write_end) = make_pipe();
+    let pair = with_current_process(|process| {
+        [
+            process.alloc_fd(read_end) as u32,
+            process.alloc_fd(write_end) as u32,
+        ]
+    });
+
+    if copy_to_user(current_user_token(), fds, &pair).is_none() {
+        let files =
+            with_current_process(|process| pair.map(|fd| process.fd_table[fd as usize].take()));
+        drop(files);
+        return Err(Errno::EFAULT);
+    }
+    Ok(0)
+}
+
+/// 打开（或者创建）文件，返回文件描述符
+///
+/// 文件系统只有根目录，所以路径只能是 `name` 或者 `/name`。
+/// 相对路径要求 `dirfd` 为 `AT_FDCWD`，因为没有其他描述符指向目录。
+/// 文件没有权限的概念，`mode` 被忽略。
+pub fn sys_openat(dirfd: isize, path: *const u8, flags: u32, _mode: u32) -> SyscallResult {
+    let path = copy_path_from_user(path)?;
+    let name = match path.strip_prefix('/') {
+        Some(name) => name,
+        None if dirfd == AT_FDCWD => path.as_str(),
+        None => {
+            let valid = dirfd >= 0
+                && with_current_process(|process| process.get_file(dirfd as usize)).is_some();
+            return Err(if valid { Errno::ENOTDIR } else { Errno::EBADF });
+        }
+    };
+    if name.contains('/') {
+        return Err(Errno::ENOENT);
+    }
+
+    let file = open_file(name, OpenFlags::from_bits_truncate(flags))?;
+    Ok(with_current_process(|process| process.alloc_fd(file)))
+}
+
+/// 从应用程序的内存复制以 `\0` 结尾的路径
+pub(super) fn copy_path_from_user(path: *const u8) -> Result<String, Errno> {
+    let path = copy_str_from_user(current_user_token(), path, PATH_MAX).ok_or(Errno::EFAULT)?;
+    if path.len() == PATH_MAX {
+        return Err(Errno::ENAMETOOLONG);
+    }
+    String::from_utf8(path).map_err(|_| Errno::ENOENT)
+}
+
+/// 移动文件的读写位置，返回新的位置；管道以及标准输入输出返回 ESPIPE
+pub fn sys_lseek(fd: usize, offset: isize, whence: usize) -> SyscallResult {
+    let file = with_current_process(|process| process.get_file(fd)).ok_or(Errno::EBADF)?;
+    file.seek(offset, whence)
+}
+
+/// 把文件的状态写入 `st`
+pub fn sys_fstat(fd: usize, st: *mut Stat) -> SyscallResult {
+    let file = with_current_process(|process| process.get_file(fd)).ok_or(Errno::EBADF)?;
+    let stat = file.stat()?;
+    copy_to_user(current_user_token(), st, &stat).ok_or(Errno::EFAULT)?;
+    Ok(0)
 }
@@ -1,20 +1,199 @@
 // use crate::batch::run_next_app;
 
-use crate::{task::{exit_current_and_run_next, suspend_current_and_run_next}, timer::get_time_ms};
+use core::mem::size_of;
+
+use crate::{
+    config::MAX_SYSCALL_NUM,
+    drivers::RTC,
+    loader::load_app,
+    mm::page_table::{copy_from_user, copy_to_user},
+    task::{
+        block_current_and_run_next, current_has_pending_signal, current_pid,
+        current_set_signal_mask, current_sigreturn, current_swap_signal_action, current_task_id,
+        current_task_info, current_user_token, current_waitpid, exit_current_and_run_next,
+        kill_task,
+        rlimit::RLimit,
+        signal::{SignalAction, SignalFlags},
+        spawn_current, suspend_current_and_run_next, with_current_process, TaskInfo,
+    },
+    timer::{add_timer, cancel_timers, get_time, TimeSpec, TimeVal},
+};
+
+use super::{
+    errno::{Errno, SyscallResult},
+    fs::copy_path_from_user,
+};
+
+// 时钟，跟 Linux 一致
+const CLOCK_REALTIME: usize = 0;
+const CLOCK_MONOTONIC: usize = 1;
 
 pub fn sys_exit(exit_code: i32) -> ! {
     println!("[kernel] Application exited with code {}", exit_code);
-    exit_current_and_run_next();
+    exit_current_and_run_next(exit_code);
     panic!("Unreachable in sys_exit!");
 }
 
 /// current task gives up resources for other tasks
-pub fn sys_yield() -> isize {
+pub fn sys_yield() -> SyscallResult {
     suspend_current_and_run_next();
-    0
+    Ok(0)
+}
+
+/// 获取当前任务的状态、用户态和内核态的运行时间以及各个系统调用的次数
+pub fn sys_task_info(info: *mut TaskInfo) -> SyscallResult {
+    let token = current_user_token();
+    let (task_info, syscall_times) = current_task_info();
+    copy_to_user(token, info, &task_info).ok_or(Errno::EFAULT)?;
+
+    // 用户态的 syscall_times 数组紧跟在 TaskInfo 的字段之后
+    let dst = (info as usize + size_of::<TaskInfo>()) as *mut [u32; MAX_SYSCALL_NUM];
+    let src: &[u32; MAX_SYSCALL_NUM] = syscall_times.as_slice().try_into().unwrap();
+    copy_to_user(token, dst, src).ok_or(Errno::EFAULT)?;
+    Ok(0)
+}
+
+/// 获取当前进程对资源 `resource` 的限制
+pub fn sys_getrlimit(resource: usize, rlim: *mut RLimit) -> SyscallResult {
+    let limit = with_current_process(|process| process.rlimits.get(resource))?;
+    copy_to_user(current_user_token(), rlim, &limit).ok_or(Errno::EFAULT)?;
+    Ok(0)
+}
+
+/// 设置当前进程对资源 `resource` 的限制
+///
+/// 资源无效或者软限制大于硬限制时返回 `EINVAL`，提高硬限制时返回 `EPERM`。
+pub fn sys_setrlimit(resource: usize, rlim: *const RLimit) -> SyscallResult {
+    let limit = copy_from_user(current_user_token(), rlim).ok_or(Errno::EFAULT)?;
+    with_current_process(|process| process.set_rlimit(resource, limit))?;
+    Ok(0)
+}
+
+/// 获取当前时间（目前是从启动开始经过的时间），精确到微秒
+///
+/// `tz` 已经过时，跟 Linux 一样被忽略。
+pub fn sys_gettimeofday(tv: *mut TimeVal, _tz: usize) -> SyscallResult {
+    let time = TimeVal::from_ticks(get_time());
+    copy_to_user(current_user_token(), tv, &time).ok_or(Errno::EFAULT)?;
+    Ok(0)
+}
+
+/// 获取时钟 `clock_id` 的时间，支持 `CLOCK_REALTIME`（RTC 提供的 Unix 时间）
+/// 和 `CLOCK_MONOTONIC`（从启动开始经过的时间）
+pub fn sys_clock_gettime(clock_id: usize, tp: *mut TimeSpec) -> SyscallResult {
+    let time = match clock_id {
+        CLOCK_REALTIME => TimeSpec::from_nanos(RTC.read_ns()),
+        CLOCK_MONOTONIC => TimeSpec::from_ticks(get_time()),
+        _ => return Err(Errno::EINVAL),
+    };
+    copy_to_user(current_user_token(), tp, &time).ok_or(Errno::EFAULT)?;
+    Ok(0)
+}
+
+/// 睡眠 `req` 指定的时间
+///
+/// 任务在睡眠期间被阻塞，不参与调度，由时钟中断在到期时唤醒。
+/// 被信号打断时返回 `EINTR`，`rem` 不为空时剩余的时间会被写入其中。
+pub fn sys_nanosleep(req: *const TimeSpec, rem: *mut TimeSpec) -> SyscallResult {
+    let token = current_user_token();
+    let req: TimeSpec = copy_from_user(token, req).ok_or(Errno::EFAULT)?;
+    let duration = req.to_ticks().ok_or(Errno::EINVAL)?;
+    let deadline = get_time().checked_add(duration).ok_or(Errno::EINVAL)?;
+
+    let current = current_task_id();
+    add_timer(deadline, current);
+
+    // 可能因为其他原因（比如收到信号）而醒来，所以循环检查是否已经到期
+    loop {
+        let now = get_time();
+        if now >= deadline {
+            return Ok(0);
+        }
+
+        if current_has_pending_signal() {
+            cancel_timers(current);
+            if !rem.is_null() {
+                copy_to_user(token, rem, &TimeSpec::from_ticks(deadline - now))
+                    .ok_or(Errno::EFAULT)?;
+            }
+            return Err(Errno::EINTR);
+        }
+
+        block_current_and_run_next();
+    }
+}
+
+/// 返回当前进程的 id，同一个进程的所有线程返回相同的值
+pub fn sys_getpid() -> SyscallResult {
+    Ok(current_pid())
+}
+
+/// 创建子进程运行根目录里的应用程序 `path`，返回子进程的 pid
+///
+/// 子进程继承当前进程打开的文件：调用者可以先用 `dup` 和 `close` 把标准输入输出换成管道，
+/// 创建子进程之后再换回来，shell 就是这样实现 `a | b` 的。
+pub fn sys_spawn(path: *const u8) -> SyscallResult {
+    let path = copy_path_from_user(path)?;
+    let name = path.strip_prefix('/').unwrap_or(&path);
+    let elf_data = load_app(name)?;
+    spawn_current(&elf_data)
+}
+
+/// 等待子进程 `pid`（为 -1 时表示任意一个子进程）结束，
+/// `exit_code` 不为空时子进程的退出码会被写入其中，返回子进程的 pid；
+/// `exit_code` 无效时返回 `EFAULT`，子进程不会被回收，可以再次等待
+pub fn sys_waitpid(pid: isize, exit_code: *mut i32) -> SyscallResult {
+    current_waitpid(pid, exit_code)
+}
+
+/// 向进程 `pid` 发送信号 `signum`
+pub fn sys_kill(pid: usize, signum: usize) -> SyscallResult {
+    let signal = SignalFlags::from_signum(signum).ok_or(Errno::EINVAL)?;
+    if kill_task(pid, signal) {
+        Ok(0)
+    } else {
+        Err(Errno::ESRCH)
+    }
+}
+
+/// 设置信号 `signum` 的处理方式
+///
+/// `action` 为空指针时只读取原来的处理方式；`old_action` 不为空时，原来的处理方式会被写入其中。
+pub fn sys_sigaction(
+    signum: usize,
+    action: *const SignalAction,
+    old_action: *mut SignalAction,
+) -> SyscallResult {
+    let signal = SignalFlags::from_signum(signum).ok_or(Errno::EINVAL)?;
+
+    // SIGKILL 和 SIGSTOP 不允许被捕获或者忽略
+    if SignalFlags::uncatchable().contains(signal) {
+        return Err(Errno::EINVAL);
+    }
+
+    let token = current_user_token();
+    let action = if action.is_null() {
+        None
+    } else {
+        Some(copy_from_user(token, action).ok_or(Errno::EFAULT)?)
+    };
+
+    // 先检查 old_action 是否可写，避免处理方式已经被修改了才返回错误
+    if !old_action.is_null() {
+        let prev_action = current_swap_signal_action(signum, None);
+        copy_to_user(token, old_action, &prev_action).ok_or(Errno::EFAULT)?;
+    }
+
+    current_swap_signal_action(signum, action);
+    Ok(0)
+}
+
+/// 设置信号屏蔽集合，返回原来的屏蔽集合
+pub fn sys_sigprocmask(mask: u32) -> SyscallResult {
+    Ok(current_set_signal_mask(SignalFlags::from_bits_truncate(mask)).bits() as usize)
 }
 
-/// get time in milliseconds
-pub fn sys_get_time() -> isize {
-    get_time_ms() as isize
+/// 从信号处理函数返回
+pub fn sys_sigreturn() -> SyscallResult {
+    current_sigreturn()
 }
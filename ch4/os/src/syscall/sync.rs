@@ -0,0 +1,120 @@
+// 同步原语相关的系统调用
+//
+// 互斥锁、信号量和条件变量由内核管理，属于创建它们的进程，
+// 通过进程内的 id（即在 ProcessControlBlock 的相应列表里的下标）访问。
+// futex 则直接以用户内存里的一个 u32 作为锁，内核只负责等待和唤醒。
+
+use alloc::sync::Arc;
+
+use crate::{
+    mm::page_table::{translate_user_va, UserAccess},
+    sync::{futex_wait, futex_wake, Condvar, Mutex, Semaphore},
+    task::{current_user_token, with_current_process},
+};
+
+use super::errno::{Errno, SyscallResult};
+
+/// 创建一个互斥锁，返回它的 id
+pub fn sys_mutex_create() -> SyscallResult {
+    Ok(with_current_process(|process| {
+        process.mutex_list.push(Arc::new(Mutex::new()));
+        process.mutex_list.len() - 1
+    }))
+}
+
+pub fn sys_mutex_lock(mutex_id: usize) -> SyscallResult {
+    let mutex = get_mutex(mutex_id)?;
+    mutex.lock()?;
+    Ok(0)
+}
+
+pub fn sys_mutex_unlock(mutex_id: usize) -> SyscallResult {
+    let mutex = get_mutex(mutex_id)?;
+    mutex.unlock()?;
+    Ok(0)
+}
+
+/// 创建一个初始计数为 `count` 的信号量，返回它的 id
+pub fn sys_semaphore_create(count: usize) -> SyscallResult {
+    Ok(with_current_process(|process| {
+        process.semaphore_list.push(Arc::new(Semaphore::new(count)));
+        process.semaphore_list.len() - 1
+    }))
+}
+
+pub fn sys_semaphore_up(sem_id: usize) -> SyscallResult {
+    let semaphore = get_semaphore(sem_id)?;
+    semaphore.up();
+    Ok(0)
+}
+
+pub fn sys_semaphore_down(sem_id: usize) -> SyscallResult {
+    let semaphore = get_semaphore(sem_id)?;
+    semaphore.down()?;
+    Ok(0)
+}
+
+/// 创建一个条件变量，返回它的 id
+pub fn sys_condvar_create() -> SyscallResult {
+    Ok(with_current_process(|process| {
+        process.condvar_list.push(Arc::new(Condvar::new()));
+        process.condvar_list.len() - 1
+    }))
+}
+
+pub fn sys_condvar_signal(condvar_id: usize) -> SyscallResult {
+    let condvar = get_condvar(condvar_id)?;
+    condvar.signal();
+    Ok(0)
+}
+
+/// 释放互斥锁 `mutex_id` 并等待条件变量 `condvar_id`，返回之前重新获取互斥锁
+pub fn sys_condvar_wait(condvar_id: usize, mutex_id: usize) -> SyscallResult {
+    let condvar = get_condvar(condvar_id)?;
+    let mutex = get_mutex(mutex_id)?;
+    condvar.wait(&mutex)?;
+    Ok(0)
+}
+
+// futex 的操作，跟 Linux 一致
+const FUTEX_WAIT: usize = 0;
+const FUTEX_WAKE: usize = 1;
+const FUTEX_PRIVATE_FLAG: usize = 128;
+
+/// `FUTEX_WAIT`：如果 `uaddr` 处的值等于 `val` 则阻塞，直到被 `FUTEX_WAKE` 唤醒，值不相等时返回 `EAGAIN`；
+/// `FUTEX_WAKE`：唤醒最多 `val` 个在 `uaddr` 上等待的任务，返回被唤醒的任务数量。
+///
+/// 等待队列以物理地址为键，所以 `FUTEX_PRIVATE_FLAG` 不影响结果，只是被接受并忽略。
+pub fn sys_futex(uaddr: usize, op: usize, val: usize) -> SyscallResult {
+    if uaddr % core::mem::size_of::<u32>() != 0 {
+        return Err(Errno::EINVAL);
+    }
+    let pa =
+        translate_user_va(current_user_token(), uaddr, UserAccess::Read).ok_or(Errno::EFAULT)?;
+
+    match op & !FUTEX_PRIVATE_FLAG {
+        FUTEX_WAIT => {
+            futex_wait(pa, val as u32)?;
+            Ok(0)
+        }
+        FUTEX_WAKE => Ok(futex_wake(pa, val)),
+        _ => Err(Errno::ENOSYS),
+    }
+}
+
+// 先从进程里取出同步原语（增加引用计数）再操作它们，
+// 因为操作的过程中可能会阻塞，此时不能持有 TaskManager 的锁
+
+fn get_mutex(mutex_id: usize) -> Result<Arc<Mutex>, Errno> {
+    with_current_process(|process| process.mutex_list.get(mutex_id).cloned()).ok_or(Errno::EINVAL)
+}
+
+fn get_semaphore(sem_id: usize) -> Result<Arc<Semaphore>, Errno> {
+    with_current_process(|process| process.semaphore_list.get(sem_id).cloned())
+        .ok_or(Errno::EINVAL)
+}
+
+fn get_condvar(condvar_id: usize) -> Result<Arc<Condvar>, Errno> {
+    with_current_process(|process| process.condvar_list.get(condvar_id).cloned())
+        .ok_or(Errno::EINVAL)
+}
@@ -0,0 +1,31 @@
+// 线程相关的系统调用
+//
+// 同一个应用（进程）里的线程共享地址空间，各自有独立的用户栈和内核栈，由 TaskManager 统一调度。
+// 线程函数不会 "返回"，它需要在结束时调用 `sys_exit`：
+// 非主线程调用 `sys_exit` 只会结束该线程，主线程调用 `sys_exit` 则会结束整个进程。
+
+use crate::{
+    mm::page_table::copy_to_user,
+    task::{create_thread, current_tid, current_user_token, current_waittid},
+};
+
+use super::errno::{Errno, SyscallResult};
+
+/// 创建一个线程，从 `entry` 开始运行，`arg` 通过 a0 传给线程函数，返回新线程的 tid
+pub fn sys_thread_create(entry: usize, arg: usize) -> SyscallResult {
+    create_thread(entry, arg)
+}
+
+/// 返回当前线程的 tid，主线程的 tid 为 0
+pub fn sys_gettid() -> SyscallResult {
+    Ok(current_tid())
+}
+
+/// 等待当前进程的线程 `tid` 退出，`exit_code` 不为空时线程的退出码会被写入其中，返回 `tid`
+pub fn sys_waittid(tid: usize, exit_code: *mut i32) -> SyscallResult {
+    let code = current_waittid(tid)?;
+    if !exit_code.is_null() {
+        copy_to_user(current_user_token(), exit_code, &code).ok_or(Errno::EFAULT)?;
+    }
+    Ok(tid)
+}
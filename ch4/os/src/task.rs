@@ -1,57 +1,124 @@
 use crate::{
-    config::{kernel_stack_position, TRAP_CONTEXT},
-    loader::{get_app_data, get_num_app},
+    config::{
+        kernel_stack_position, thread_user_stack_position, trap_context_position, CLOCK_FREQ,
+        MAX_SYSCALL_NUM, PAGE_SIZE, TRAP_CONTEXT, USER_STACK_SIZE,
+    },
+    console,
+    fs::{File, Stdin, Stdout},
+    loader::{get_app_data, get_app_names},
     mm::{
         address::{PhysPageNum, VirtAddr},
-        memory_set::{MapPermission, MemorySet, KERNEL_SPACE},
+        memory_set::{MapError, MapPermission, MapType, MemorySet, KERNEL_SPACE},
+        page_table::{copy_from_user, copy_to_user},
     },
-    trap::{context::TrapContext, trap_handler},
-    up::UPSafeCell,
+    power::{shutdown, ExitCode},
+    smp::hart_id,
+    sync::{lock_order, Condvar, Mutex, Semaphore, SpinLock},
+    syscall::errno::{Errno, SyscallResult},
+    timer::{get_time, ticks_to_us},
+    trap::{context::TrapContext, trap_handler, wait_for_interrupt},
 };
 
-use self::{context::TaskContext, switch::__switch};
+use self::{
+    context::TaskContext,
+    fp::FpContext,
+    processor::idle_task_cx_ptr,
+    rlimit::{
+        RLimit, RLimits, EXIT_RLIMIT_CPU, EXIT_RLIMIT_RSS, RLIMIT_CPU, RLIMIT_RSS, RLIMIT_STACK,
+        RLIM_INFINITY,
+    },
+    signal::{
+        signal_exit_code, SignalAction, SignalActions, SignalDelivery, SignalFlags, SignalFrame,
+        MAX_SIG, SIG_DFL, SIG_IGN,
+    },
+    switch::__switch,
+};
 
-use alloc::vec::Vec;
+use alloc::{sync::Arc, vec, vec::Vec};
+use core::{
+    ops::{Index, IndexMut},
+    sync::atomic::{AtomicBool, Ordering},
+};
 use lazy_static::lazy_static;
+use riscv::register::sstatus::FS;
 
 mod context;
+mod fp;
+pub mod processor;
+pub mod rlimit;
+pub mod signal;
 mod switch;
 
 #[derive(Copy, Clone, PartialEq)]
 pub enum TaskStatus {
-    UnInit,  // 未初始化
+    UnInit,  // 未初始化，或者线程已经被回收（该任务 id 可以分配给新的线程）
     Ready,   // 准备运行
     Running, // 正在运行
+    Blocked, // 正在等待某个事件（比如控制台输入），不参与调度
     Exited,  // 已退出
 }
 
-// #[derive(Copy, Clone)]
-pub struct TaskControlBlock {
-    pub task_status: TaskStatus,
-    pub task_cx: TaskContext,
+// 进程和线程
+//
+// 一个应用程序对应一个进程（ProcessControlBlock），进程拥有地址空间；
+// 进程里可以有多个线程，每个线程是一个 TaskControlBlock，由 TaskManager 统一调度。
+// 同一个进程的线程共享地址空间，但各自有独立的用户栈、TrapContext 页和内核栈。
+//
+// - 进程 id（pid）是该进程主线程的任务 id，启动时加载的应用的 pid 从 0 开始依次编号（加载失败的应用被跳过）；
+// - 线程 id（tid）在进程内从 0 开始编号，主线程的 tid 为 0；
+// - 任务 id 是线程在 TaskManager 里的下标，前 num_app 个任务是启动时加载的各个应用的主线程。
+//
+// 线程退出时释放它的用户栈和 TrapContext 页；被 `sys_waittid` 回收之后再释放它的内核栈，
+// 它的 tid 和任务 id 随后可以分配给新创建的线程。
+//
+// 进程可以通过 `sys_spawn` 创建子进程，子进程的所有线程都退出之后由父进程通过 `sys_waitpid` 回收，
+// 释放它的地址空间、内核栈和任务 id（pid）；没有父进程（或者父进程已经退出）的进程在结束时直接回收。
+pub struct ProcessControlBlock {
+    pub memory_set: MemorySet, // 应用的地址空间
+    // 统计了应用数据的大小，也就是在应用地址空间中从开始到（主线程的）用户栈结束一共包含
+    // 多少字节。它后续还应该包含用于应用动态内存分配的堆空间的大小，但目前暂不支持。
+    pub base_size: usize,
+    pub threads: Vec<Option<usize>>, // 各个线程的任务 id，下标为 tid，None 表示该线程已经被回收
+    pub reaped_time: usize,          // 已经被回收的线程的运行时间（时钟周期），计入进程的 CPU 时间
 
-    // ch4 新增
-    pub memory_set: MemorySet,    // 应用的地址空间
-    pub trap_cx_ppn: PhysPageNum, // 位于应用地址空间次高页的 TrapContext 被实际存放在物理页帧的物理页号
-    pub base_size: usize, // 统计了应用数据的大小，也就是在应用地址空间中从开始到用户栈结束一共包含
-                          // 多少字节。它后续还应该包含用于应用动态内存分配的堆空间的大小，但目前暂不支持。
-}
+    // 应用程序创建的同步原语，下标即系统调用使用的 id
+    pub mutex_list: Vec<Arc<Mutex>>,
+    pub semaphore_list: Vec<Arc<Semaphore>>,
+    pub condvar_list: Vec<Arc<Condvar>>,
 
-impl TaskControlBlock {
+    // 打开的文件，下标即文件描述符，None 表示该描述符已关闭。
+    // 关闭文件（比如管道）可能会唤醒其他任务，所以要先从表里取出来，释放 TaskManager 的锁之后再 drop
+    pub fd_table: Vec<Option<Arc<dyn File>>>,
 
-    pub fn get_trap_cx(&self) -> &'static mut TrapContext {
-        self.trap_cx_ppn.get_mut()
-    }
+    // 资源限制
+    pub rlimits: RLimits,
+    pub forced_exit_code: Option<i32>, // 进程被内核终止（比如超出资源限制）时的退出码，优先于主线程自己的退出码
 
-    pub fn get_user_token(&self) -> usize {
-        self.memory_set.token()
-    }
+    // 父子进程
+    pub parent: Option<usize>,    // 父进程的 pid，没有时为 None
+    pub children: Vec<usize>,     // 还没有被回收的子进程的 pid
+    pub wait_waiters: Vec<usize>, // 等待子进程结束（`sys_waitpid`）的任务
+}
 
-    pub fn new(elf_data: &[u8], app_id: usize) -> Self {
+impl ProcessControlBlock {
+    /// 加载应用程序，返回进程以及它的主线程，ELF 数据格式不正确或者物理页帧耗尽时失败
+    pub fn new(elf_data: &[u8], pid: usize) -> Result<(Self, TaskControlBlock), Errno> {
         // memory_set with elf program headers/trampoline/trap context/user stack
-        println!("------ mapping app {}", app_id);
-        let (memory_set, user_sp, entry_point) = MemorySet::from_elf(elf_data);
+        println!("------ mapping app {}", pid);
+        // 启动时加载的应用程序还没有资源限制
+        let (memory_set, user_sp, entry_point) = MemorySet::from_elf(elf_data, usize::MAX)?;
+        Self::with_memory_set(memory_set, user_sp, entry_point, pid)
+    }
 
+    // ch4 新增
+    /// 用已经加载了应用程序的地址空间（见 `MemorySet::from_elf`）创建进程以及它的主线程，
+    /// `pid` 同时也是主线程的任务 id；为内核栈分配页帧失败时返回 `ENOMEM`，地址空间随之释放
+    fn with_memory_set(
+        memory_set: MemorySet,
+        user_sp: usize,
+        entry_point: usize,
+        pid: usize,
+    ) -> Result<(Self, TaskControlBlock), Errno> {
         // 应用程序看到的内存地址空间
         // application address space (high)
         // |--------------| 2^64
@@ -67,22 +134,291 @@ impl TaskControlBlock {
             .unwrap()
             .ppn();
 
+        let main_thread = TaskControlBlock::new(
+            pid,
+            0,
+            pid,
+            memory_set.token(),
+            trap_cx_ppn,
+            entry_point,
+            user_sp,
+        )
+        .map_err(|_| Errno::ENOMEM)?;
+
+        let mut process = Self {
+            memory_set,
+            base_size: user_sp,
+            threads: vec![Some(pid)],
+            reaped_time: 0,
+            mutex_list: Vec::new(),
+            semaphore_list: Vec::new(),
+            condvar_list: Vec::new(),
+            fd_table: vec![
+                Some(Arc::new(Stdin)),  // 0 标准输入
+                Some(Arc::new(Stdout)), // 1 标准输出
+                Some(Arc::new(Stdout)), // 2 标准错误
+            ],
+            rlimits: RLimits::default(),
+            forced_exit_code: None,
+            parent: None,
+            children: Vec::new(),
+            wait_waiters: Vec::new(),
+        };
+        process.sync_frame_limit();
+
+        Ok((process, main_thread))
+    }
+
+    /// 文件描述符 `fd` 对应的文件，描述符无效时返回 None
+    pub fn get_file(&self, fd: usize) -> Option<Arc<dyn File>> {
+        self.fd_table.get(fd).cloned().flatten()
+    }
+
+    /// 为文件分配一个文件描述符，优先使用编号最小的空闲描述符
+    pub fn alloc_fd(&mut self, file: Arc<dyn File>) -> usize {
+        match self.fd_table.iter().position(|f| f.is_none()) {
+            Some(fd) => {
+                self.fd_table[fd] = Some(file);
+                fd
+            }
+            None => {
+                self.fd_table.push(Some(file));
+                self.fd_table.len() - 1
+            }
+        }
+    }
+
+    /// 设置资源 `resource` 的限制
+    pub fn set_rlimit(&mut self, resource: usize, limit: RLimit) -> Result<(), Errno> {
+        self.rlimits.set(resource, limit)?;
+        if resource == RLIMIT_RSS {
+            self.sync_frame_limit();
+        }
+        Ok(())
+    }
+
+    /// 把 RLIMIT_RSS 换算为地址空间的页帧数量上限
+    fn frame_limit(&self) -> usize {
+        self.rlimits.cur(RLIMIT_RSS) / PAGE_SIZE
+    }
+
+    fn sync_frame_limit(&mut self) {
+        self.memory_set.set_frame_limit(self.frame_limit());
+    }
+
+    /// 为任务 `task_id` 分配一个线程 id（优先使用已经被回收的最小的 tid），并映射它的用户栈和 TrapContext 页，
+    /// 返回 (tid, 用户栈栈顶, TrapContext 的物理页号)；分配页帧失败时进程保持不变
+    fn alloc_thread(&mut self, task_id: usize) -> Result<(usize, usize, PhysPageNum), MapError> {
+        let tid = self
+            .threads
+            .iter()
+            .position(|t| t.is_none())
+            .unwrap_or(self.threads.len());
+
+        // 用户栈位于栈槽的顶部，大小受 RLIMIT_STACK 限制，但不超过栈槽的大小
+        let (_, ustack_top) = thread_user_stack_position(self.base_size, tid);
+        let ustack_bottom = ustack_top - self.rlimits.cur(RLIMIT_STACK).min(USER_STACK_SIZE);
+        self.memory_set.insert_framed_area(
+            ustack_bottom.into(),
+            ustack_top.into(),
+            MapPermission::R | MapPermission::W | MapPermission::U,
+        )?;
+
+        let trap_cx_va = trap_context_position(tid);
+        if let Err(err) = self.memory_set.insert_framed_area(
+            trap_cx_va.into(),
+            (trap_cx_va + PAGE_SIZE).into(),
+            MapPermission::R | MapPermission::W,
+        ) {
+            self.memory_set.remove_area(ustack_bottom.into());
+            return Err(err);
+        }
+        let trap_cx_ppn = self
+            .memory_set
+            .translate(VirtAddr::from(trap_cx_va).into())
+            .unwrap()
+            .ppn();
+
+        if tid == self.threads.len() {
+            self.threads.push(Some(task_id));
+        } else {
+            self.threads[tid] = Some(task_id);
+        }
+        Ok((tid, ustack_top, trap_cx_ppn))
+    }
+
+    /// 线程 `tid` 退出时调用，释放它的用户栈和 TrapContext 页
+    ///
+    /// 此时内核运行在内核地址空间里，并且已经不会再访问该线程的 TrapContext。
+    fn release_thread_user_area(&mut self, tid: usize) {
+        let ustack_top = if tid == 0 {
+            self.base_size
+        } else {
+            thread_user_stack_position(self.base_size, tid).1
+        };
+        // 用户栈的大小受创建线程时的 RLIMIT_STACK 限制，所以通过栈顶查找它所在的内存段
+        let ustack_bottom = self
+            .memory_set
+            .find_area(VirtAddr::from(ustack_top - 1))
+            .map(|area| area.va_range().0);
+        if let Some(ustack_bottom) = ustack_bottom {
+            self.memory_set.remove_area(ustack_bottom);
+        }
+        self.memory_set
+            .remove_area(trap_context_position(tid).into());
+    }
+}
+
+// #[derive(Copy, Clone)]
+pub struct TaskControlBlock {
+    pub task_status: TaskStatus,
+    pub task_cx: TaskContext,
+
+    // ch4 新增
+    pub trap_cx_ppn: PhysPageNum, // 该线程的 TrapContext 被实际存放在物理页帧的物理页号
+    pub user_token: usize,        // 所属进程的地址空间的 token
+    pub exit_code: i32,
+
+    // 线程
+    pub pid: usize,               // 所属进程的 id
+    pub tid: usize,               // 进程内的线程 id
+    pub join_waiters: Vec<usize>, // 等待该线程退出（`sys_waittid`）的任务
+
+    // 浮点寄存器
+    pub fp_cx: FpContext, // 任务被切换出去时保存的浮点寄存器
+    pub fp_used: bool,    // 任务是否修改过浮点寄存器
+
+    // 信号
+    pub signals: SignalFlags,          // 待处理的信号
+    pub signal_mask: SignalFlags,      // 被屏蔽的信号
+    pub signal_actions: SignalActions, // 每个信号的处理方式
+    pub signal_frame: Option<usize>,   // 正在执行信号处理函数时，SignalFrame 在用户栈上的地址
+
+    // 多核
+    pub on_cpu: bool,           // 是否还在某个 hart 上运行（包括正在切换出去）
+    pub wakeup_pending: bool,   // 运行时收到的唤醒，避免 "先唤醒后阻塞" 导致唤醒丢失
+    pub fp_hart: Option<usize>, // 浮点寄存器数据最后被加载到了哪个 hart
+
+    // 统计，时间的单位都是时钟周期（`time` CSR 的值）
+    pub user_time: usize,        // 在用户态运行的时间
+    pub kernel_time: usize,      // 在内核态运行的时间，不包括被切换出去（等待调度或者阻塞）的时间
+    pub last_timestamp: usize,   // 上一次统计运行时间的时刻
+    pub trap_count: usize,       // 从用户态陷入内核的次数
+    pub syscall_times: Vec<u32>, // 每个系统调用的次数，下标为系统调用号
+}
+
+/// `sys_task_info` 返回的任务信息，时间的单位都是微秒
+///
+/// 用户态的结构体在这些字段之后还有 `syscall_times: [u32; MAX_SYSCALL_NUM]`，
+/// 它有 4KB 多，所以不放在这里（避免占用内核栈），而是由 `sys_task_info` 单独复制。
+#[repr(C)]
+pub struct TaskInfo {
+    pub status: usize, // TaskStatus：0 UnInit、1 Ready、2 Running、3 Blocked、4 Exited
+    pub user_time: usize,
+    pub kernel_time: usize,
+    pub time: usize, // 总运行时间，即 user_time + kernel_time
+    pub trap_count: usize,
+}
+
+impl TaskControlBlock {
+    pub fn get_trap_cx(&self) -> &'static mut TrapContext {
+        self.trap_cx_ppn.get_mut()
+    }
+
+    pub fn get_user_token(&self) -> usize {
+        self.user_token
+    }
+
+    /// 由异常引起的信号能否交给用户的处理函数：
+    /// 需要设置了处理函数、没有被屏蔽，并且任务不在执行信号处理函数
+    fn can_catch_signal(&self, signal: SignalFlags) -> bool {
+        let signum = signal.bits().trailing_zeros() as usize;
+        let handler = self.signal_actions.table[signum].handler;
+
+        self.signal_frame.is_none()
+            && !self.signal_mask.contains(signal)
+            && handler != SIG_DFL
+            && handler != SIG_IGN
+    }
+
+    /// 唤醒任务：被阻塞的任务重新参与调度；
+    /// 正在运行的任务（可能正准备阻塞）记下这次唤醒，之后的阻塞会立即返回
+    fn wakeup(&mut self) {
+        match self.task_status {
+            TaskStatus::Blocked => self.task_status = TaskStatus::Ready,
+            TaskStatus::Running => self.wakeup_pending = true,
+            _ => {}
+        }
+    }
+
+    /// 把上一次统计以来经过的时间计入用户态（`in_user` 为 true）或者内核态的运行时间
+    fn update_time(&mut self, in_user: bool) {
+        let now = get_time();
+        let elapsed = now.saturating_sub(self.last_timestamp);
+        if in_user {
+            self.user_time += elapsed;
+        } else {
+            self.kernel_time += elapsed;
+        }
+        self.last_timestamp = now;
+    }
+
+    /// 任务被切换出去之前调用：只有浮点寄存器被修改过（FS 为 Dirty）才保存它们
+    fn save_fp_if_dirty(&mut self) {
+        let trap_cx = self.get_trap_cx();
+        if trap_cx.fs() == FS::Dirty {
+            self.fp_cx.save();
+            self.fp_used = true;
+            trap_cx.set_fs(FS::Clean);
+        }
+    }
+
+    /// 创建一个线程：映射它的内核栈，并初始化它的 TrapContext，
+    /// 返回用户态之后从 `entry` 开始运行，栈指针为 `user_sp`；为内核栈分配页帧失败时返回 `OutOfMemory`
+    fn new(
+        pid: usize,
+        tid: usize,
+        task_id: usize,
+        user_token: usize,
+        trap_cx_ppn: PhysPageNum,
+        entry: usize,
+        user_sp: usize,
+    ) -> Result<Self, MapError> {
         let task_status = TaskStatus::Ready;
 
         // map a kernel-stack in kernel space
-        let (kernel_stack_bottom, kernel_stack_top) = kernel_stack_position(app_id);
-        KERNEL_SPACE.exclusive_access().insert_framed_area(
-            kernel_stack_bottom.into(),
-            kernel_stack_top.into(),
-            MapPermission::R | MapPermission::W,
-        );
+        let (kernel_stack_bottom, kernel_stack_top) = kernel_stack_position(task_id);
+        KERNEL_SPACE
+            .lock_irqsave()
+            .insert_framed_area(
+                kernel_stack_bottom.into(),
+                kernel_stack_top.into(),
+                MapPermission::R | MapPermission::W,
+            )?;
 
         let task_control_block = Self {
             task_status,
             task_cx: TaskContext::goto_trap_return(kernel_stack_top),
-            memory_set,
             trap_cx_ppn,
-            base_size: user_sp,
+            user_token,
+            exit_code: 0,
+            pid,
+            tid,
+            join_waiters: Vec::new(),
+            fp_cx: FpContext::zero_init(),
+            fp_used: false,
+            signals: SignalFlags::empty(),
+            signal_mask: SignalFlags::empty(),
+            signal_actions: SignalActions::default(),
+            signal_frame: None,
+            on_cpu: false,
+            wakeup_pending: false,
+            fp_hart: None,
+            user_time: 0,
+            kernel_time: 0,
+            last_timestamp: 0,
+            trap_count: 0,
+            syscall_times: vec![0; MAX_SYSCALL_NUM],
         };
 
         // prepare TrapContext in user space
@@ -90,32 +426,128 @@ impl TaskControlBlock {
 
         // Trap Context 也被储存进一个 page 里，所以可以这样赋值
         *trap_cx = TrapContext::app_init_context(
-            entry_point,
+            entry,
             user_sp,
-            KERNEL_SPACE.exclusive_access().token(),
+            KERNEL_SPACE.lock_irqsave().token(),
             kernel_stack_top,
             trap_handler as usize,
         );
 
-        task_control_block
+        Ok(task_control_block)
     }
 }
 
+// ch4 MODIFY:
+// 多个 hart 会同时访问 TaskManager（比如同时选择下一个任务），所以使用自旋锁保护，
+// 而 "当前任务" 则移到了每个 hart 私有的 Processor 里（见 `task/processor.rs`）。
 pub struct TaskManager {
-    num_app: usize,
-    inner: UPSafeCell<TaskManagerInner>,
+    inner: SpinLock<TaskManagerInner>,
 }
 
 struct TaskManagerInner {
     // tasks: [TaskControlBlock; MAX_APP_NUM],
-    tasks: Vec<TaskControlBlock>,
-    current_task: usize,
+    processes: ProcessTable,      // 下标为 pid
+    tasks: Vec<TaskControlBlock>, // 下标为任务 id
+    next_task: usize,             // 下一次从哪个任务开始查找就绪的任务，用于轮转调度
+}
+
+// ch4 新增
+/// 所有的进程，下标为 pid（即主线程的任务 id）；
+/// 不是主线程的任务 id，以及已经被回收的进程，对应的位置为 None
+struct ProcessTable(Vec<Option<ProcessControlBlock>>);
+
+impl ProcessTable {
+    fn get(&self, pid: usize) -> Option<&ProcessControlBlock> {
+        self.0.get(pid)?.as_ref()
+    }
+
+    fn insert(&mut self, pid: usize, process: ProcessControlBlock) {
+        if pid >= self.0.len() {
+            self.0.resize_with(pid + 1, || None);
+        }
+        self.0[pid] = Some(process);
+    }
+
+    fn remove(&mut self, pid: usize) -> Option<ProcessControlBlock> {
+        self.0.get_mut(pid)?.take()
+    }
+}
+
+impl Index<usize> for ProcessTable {
+    type Output = ProcessControlBlock;
+
+    fn index(&self, pid: usize) -> &ProcessControlBlock {
+        self.get(pid).expect("no such process")
+    }
+}
+
+impl IndexMut<usize> for ProcessTable {
+    fn index_mut(&mut self, pid: usize) -> &mut ProcessControlBlock {
+        self.0[pid].as_mut().expect("no such process")
+    }
+}
+
+impl TaskManagerInner {
+    /// 为新的线程分配任务 id，优先使用已经被回收的任务 id
+    fn alloc_task_id(&self) -> usize {
+        self.tasks
+            .iter()
+            .position(|task| task.task_status == TaskStatus::UnInit)
+            .unwrap_or(self.tasks.len())
+    }
+
+    /// 把新的线程放到 `alloc_task_id` 分配的位置
+    fn install_task(&mut self, task_id: usize, task: TaskControlBlock) {
+        if task_id == self.tasks.len() {
+            self.tasks.push(task);
+        } else {
+            self.tasks[task_id] = task;
+        }
+    }
+
+    /// 释放已经退出并且切换出去的线程的内核栈，它的任务 id 可以分配给新的线程
+    fn release_task(&mut self, task_id: usize) {
+        self.tasks[task_id].task_status = TaskStatus::UnInit;
+        let (kernel_stack_bottom, _) = kernel_stack_position(task_id);
+        KERNEL_SPACE
+            .lock_irqsave()
+            .remove_area(kernel_stack_bottom.into());
+    }
+
+    /// 进程是否已经结束：所有线程都已经退出，并且都已经切换出去（不再使用内核栈）
+    fn is_process_finished(&self, pid: usize) -> bool {
+        self.processes[pid]
+            .threads
+            .iter()
+            .flatten()
+            .all(|&task_id| {
+                let task = &self.tasks[task_id];
+                task.task_status == TaskStatus::Exited && !task.on_cpu
+            })
+    }
+
+    /// 回收已经结束的进程：释放所有线程的内核栈和任务 id，并把它从父进程的子进程列表里移除
+    ///
+    /// 返回的进程在 drop 时释放地址空间等资源，需要在释放 TaskManager 的锁之后再 drop。
+    fn reap_process(&mut self, pid: usize) -> ProcessControlBlock {
+        let process = self.processes.remove(pid).unwrap();
+        for &task_id in process.threads.iter().flatten() {
+            self.release_task(task_id);
+        }
+        if let Some(parent) = process.parent {
+            self.processes[parent]
+                .children
+                .retain(|&child| child != pid);
+        }
+        process
+    }
 }
 
 lazy_static! {
     pub static ref TASK_MANAGER: TaskManager = {
         println!("init TASK_MANAGER");
-        let num_app = get_num_app();
+        let app_names = get_app_names();
+        let num_app = app_names.len();
         println!("num_app = {}", num_app);
 
 //         let mut tasks = [TaskControlBlock {
@@ -138,122 +570,973 @@ lazy_static! {
 //             },
 //         }
 
+        let mut processes = ProcessTable(Vec::new());
         let mut tasks: Vec<TaskControlBlock> = Vec::new();
 
-        for i in 0..num_app {
-            tasks.push(TaskControlBlock::new(get_app_data(i), i));
+        // ch4 MODIFY:
+        // 从文件系统读取应用程序，ELF 数据在创建地址空间之后即被释放。
+        // 加载失败的应用程序被跳过，后面的应用程序的 pid 依次前移。
+        for (i, name) in app_names.iter().enumerate() {
+            let pid = tasks.len();
+            match ProcessControlBlock::new(&get_app_data(name), pid) {
+                Ok((process, main_thread)) => {
+                    processes.insert(pid, process);
+                    tasks.push(main_thread);
+                }
+                Err(errno) => println!("[kernel] failed to load app {} ({}): {:?}", i, name, errno),
+            }
         }
 
         TaskManager {
-            num_app,
-            inner: unsafe {
-                UPSafeCell::new(TaskManagerInner {
+            inner: SpinLock::named(
+                TaskManagerInner {
+                    processes,
                     tasks,
-                    current_task: 0,
-                })
-            },
+                    next_task: 0,
+                },
+                "TASK_MANAGER",
+                lock_order::TASK_MANAGER,
+            ),
         }
 
     };
 }
 
+/// 创建所有任务，由启动 hart 在启动其他 hart 之前调用
+pub fn init() {
+    lazy_static::initialize(&TASK_MANAGER);
+}
+
 pub fn suspend_current_and_run_next() {
-    mark_current_suspended();
-    run_next_task();
+    let task_cx_ptr = TASK_MANAGER.mark_current_suspended();
+    schedule(task_cx_ptr);
+}
+
+pub fn exit_current_and_run_next(exit_code: i32) {
+    // ch4 新增
+    // 主线程退出时关闭进程打开的所有文件，比如让管道的读者读到 EOF
+    if current_tid() == 0 {
+        let files = with_current_process(|process| core::mem::take(&mut process.fd_table));
+        drop(files);
+    }
+
+    let (task_cx_ptr, orphans) = TASK_MANAGER.mark_current_exited(exit_code);
+    // 在释放 TaskManager 的锁之后才释放已经结束的子进程的资源
+    drop(orphans);
+    schedule(task_cx_ptr);
 }
 
-pub fn exit_current_and_run_next() {
-    mark_current_exited();
-    run_next_task();
+/// 阻塞当前任务并切换到下一个任务
+///
+/// 调用者需要事先把当前任务的 id 记录在某个等待队列里，
+/// 以便事件发生时通过 `wakeup_task` 唤醒它。
+/// 如果在此之前任务已经被唤醒了，则不会被阻塞，所以调用者需要循环检查等待的条件。
+pub fn block_current_and_run_next() {
+    let task_cx_ptr = TASK_MANAGER.mark_current_blocked();
+    schedule(task_cx_ptr);
 }
 
-fn mark_current_suspended() {
-    TASK_MANAGER.mark_current_suspended();
+/// 唤醒一个被阻塞的任务，让其重新参与调度
+pub fn wakeup_task(task_id: usize) {
+    TASK_MANAGER.wakeup_task(task_id);
 }
 
-fn mark_current_exited() {
-    TASK_MANAGER.mark_current_exited();
+/// 从当前任务切换回当前 hart 的调度循环
+fn schedule(switched_task_cx_ptr: *mut TaskContext) {
+    let idle_task_cx_ptr = idle_task_cx_ptr();
+    unsafe {
+        __switch(switched_task_cx_ptr, idle_task_cx_ptr);
+    }
+}
+
+/// 所有任务是否都已经退出，只让一个 hart 报告
+static ALL_COMPLETED: AtomicBool = AtomicBool::new(false);
+
+/// 每个 hart 的调度循环（idle 控制流），运行在该 hart 的启动栈上
+///
+/// 不断选择一个就绪的任务并切换过去，任务让出 CPU 时会切换回这里。
+pub fn run_tasks() -> ! {
+    loop {
+        if let Some((task_id, next_task_cx_ptr)) = TASK_MANAGER.fetch_task() {
+            processor::set_current_task(Some(task_id));
+
+            unsafe {
+                __switch(idle_task_cx_ptr(), next_task_cx_ptr);
+            }
+
+            // 任务已经切换出去，它的 TaskContext 已经保存完毕，
+            // 现在才允许其他 hart 运行它
+            let prev = processor::set_current_task(None).unwrap();
+            // 在释放 TaskManager 的锁之后才释放已经结束的进程的资源
+            drop(TASK_MANAGER.finish_switch(prev));
+            continue;
+        }
+
+        if TASK_MANAGER.all_exited() {
+            if !ALL_COMPLETED.swap(true, Ordering::Relaxed) {
+                println!("[kernel] All applications completed!");
+                console::flush();
+                shutdown(ExitCode::Success);
+            }
+        }
+
+        // 暂时没有就绪的任务（其他任务可能在等待事件，或者正在其他 hart 上运行），
+        // 在此等待中断的到来，中断处理程序会唤醒相应的任务；
+        // 其他 hart 唤醒的任务则在下一次时钟中断之后被发现。
+        wait_for_interrupt();
+    }
 }
 
 impl TaskManager {
-    fn mark_current_suspended(&self) {
-        let mut inner = self.inner.exclusive_access();
-        let current = inner.current_task;
-        inner.tasks[current].task_status = TaskStatus::Ready;
+    /// 返回当前任务的 TaskContext 的地址，用于 `__switch` 保存当前任务的上下文
+    fn mark_current_suspended(&self) -> *mut TaskContext {
+        let current = current_task_id();
+        let mut inner = self.inner.lock_irqsave();
+        let task = &mut inner.tasks[current];
+        task.update_time(false);
+        task.save_fp_if_dirty();
+        task.task_status = TaskStatus::Ready;
+        &mut task.task_cx as *mut TaskContext
     }
 
-    fn mark_current_exited(&self) {
-        let mut inner = self.inner.exclusive_access();
-        let current = inner.current_task;
-        inner.tasks[current].task_status = TaskStatus::Exited;
+    /// 返回当前任务的 TaskContext 的地址，以及当前进程退出时随之被回收的子进程
+    fn mark_current_exited(&self, exit_code: i32) -> (*mut TaskContext, Vec<ProcessControlBlock>) {
+        let current = current_task_id();
+        let mut inner = self.inner.lock_irqsave();
+        let inner = &mut *inner;
+        let task = &mut inner.tasks[current];
+        task.update_time(false);
+        task.task_status = TaskStatus::Exited;
+        task.exit_code = exit_code;
+
+        let (pid, tid) = (task.pid, task.tid);
+        if tid == 0 {
+            if let Some(code) = inner.processes[pid].forced_exit_code {
+                task.exit_code = code;
+            }
+        }
+        inner.processes[pid].release_thread_user_area(tid);
+
+        // 主线程退出意味着整个进程退出，向其余的线程发送 SIGKILL，
+        // 它们在返回用户态之前（或者从阻塞中醒来之后）就会退出
+        if tid == 0 {
+            for &task_id in inner.processes[pid].threads[1..].iter().flatten() {
+                let thread = &mut inner.tasks[task_id];
+                if thread.task_status != TaskStatus::Exited {
+                    thread.signals.insert(SignalFlags::SIGKILL);
+                    thread.wakeup();
+                }
+            }
+        }
+
+        // 进程退出之后不会再回收它的子进程，已经结束的子进程直接回收，其余的在结束时回收
+        let mut orphans = Vec::new();
+        if tid == 0 {
+            for child in core::mem::take(&mut inner.processes[pid].children) {
+                inner.processes[child].parent = None;
+                if inner.is_process_finished(child) {
+                    orphans.push(inner.reap_process(child));
+                }
+            }
+        }
+
+        (
+            &mut inner.tasks[current].task_cx as *mut TaskContext,
+            orphans,
+        )
+    }
+
+    fn mark_current_blocked(&self) -> *mut TaskContext {
+        let current = current_task_id();
+        let mut inner = self.inner.lock_irqsave();
+        let task = &mut inner.tasks[current];
+        task.update_time(false);
+        task.save_fp_if_dirty();
+        if task.wakeup_pending {
+            // 阻塞之前已经被唤醒了，只让出 CPU
+            task.wakeup_pending = false;
+            task.task_status = TaskStatus::Ready;
+        } else {
+            task.task_status = TaskStatus::Blocked;
+        }
+        &mut task.task_cx as *mut TaskContext
+    }
+
+    fn wakeup_task(&self, task_id: usize) {
+        let mut inner = self.inner.lock_irqsave();
+        inner.tasks[task_id].wakeup();
     }
 
-    fn run_next_task(&self) {
-        if let Some(next) = self.find_next_task() {
-            let mut inner = self.inner.exclusive_access();
-            let current = inner.current_task;
-            inner.tasks[next].task_status = TaskStatus::Running;
-            inner.current_task = next;
+    /// 选择下一个就绪的任务，返回任务 id 以及它的 TaskContext 的地址
+    ///
+    /// 状态为 Ready 但 `on_cpu` 仍为 true 的任务刚刚让出 CPU，
+    /// 它所在的 hart 还没有保存完它的 TaskContext，所以暂时不能选择。
+    fn fetch_task(&self) -> Option<(usize, *const TaskContext)> {
+        let mut inner = self.inner.lock_irqsave();
+        let num_task = inner.tasks.len();
+        let start = inner.next_task;
+        let next = (start..start + num_task)
+            .map(|id| id % num_task)
+            .find(|id| {
+                let task = &inner.tasks[*id];
+                task.task_status == TaskStatus::Ready && !task.on_cpu
+            })?;
 
-            let current_task_cx_ptr = &mut inner.tasks[current].task_cx as *mut TaskContext;
-            let next_task_cx_ptr = &inner.tasks[next].task_cx as *const TaskContext;
-            drop(inner);
-            // before this, we should drop local variables that must be dropped manually
+        inner.next_task = (next + 1) % num_task;
 
-            unsafe {
-                __switch(current_task_cx_ptr, next_task_cx_ptr);
+        let task = &mut inner.tasks[next];
+        task.task_status = TaskStatus::Running;
+        task.on_cpu = true;
+        task.wakeup_pending = false;
+        task.last_timestamp = get_time(); // 被切换出去的时间不计入运行时间
+        Some((next, &task.task_cx as *const TaskContext))
+    }
+
+    /// 任务已经从当前 hart 切换出去
+    ///
+    /// 已经退出的线程此时才不再使用它的内核栈，所以在这里（而不是退出时）唤醒等待回收它的任务。
+    /// 它是进程的最后一个线程时，通知父进程回收该进程；没有父进程时直接回收，返回被回收的进程。
+    fn finish_switch(&self, task_id: usize) -> Option<ProcessControlBlock> {
+        let mut inner = self.inner.lock_irqsave();
+        let inner = &mut *inner;
+        let task = &mut inner.tasks[task_id];
+        task.on_cpu = false;
+        if task.task_status != TaskStatus::Exited {
+            return None;
+        }
+
+        let pid = task.pid;
+        for waiter in core::mem::take(&mut task.join_waiters) {
+            inner.tasks[waiter].wakeup();
+        }
+
+        if !inner.is_process_finished(pid) {
+            return None;
+        }
+        match inner.processes[pid].parent {
+            Some(parent) => {
+                for waiter in core::mem::take(&mut inner.processes[parent].wait_waiters) {
+                    inner.tasks[waiter].wakeup();
+                }
+                None
+            }
+            None => Some(inner.reap_process(pid)),
+        }
+    }
+
+    fn all_exited(&self) -> bool {
+        let inner = self.inner.lock_irqsave();
+        inner.tasks.iter().all(|task| {
+            task.task_status == TaskStatus::Exited || task.task_status == TaskStatus::UnInit
+        })
+    }
+
+    fn kill_task(&self, pid: usize, signal: SignalFlags) -> bool {
+        let mut inner = self.inner.lock_irqsave();
+        if inner.processes.get(pid).is_none() {
+            return false;
+        }
+
+        let task = &mut inner.tasks[pid];
+        if task.task_status == TaskStatus::Exited {
+            return false;
+        }
+
+        task.signals.insert(signal);
+
+        // 唤醒被阻塞的任务，让它有机会处理信号
+        task.wakeup();
+        true
+    }
+
+    fn can_current_catch_signal(&self, signal: SignalFlags) -> bool {
+        let inner = self.inner.lock_irqsave();
+        inner.tasks[current_task_id()].can_catch_signal(signal)
+    }
+
+    fn force_current_signal(&self, signal: SignalFlags) -> bool {
+        let current = current_task_id();
+        let mut inner = self.inner.lock_irqsave();
+        let task = &mut inner.tasks[current];
+
+        let catchable = task.can_catch_signal(signal);
+        if catchable {
+            task.signals.insert(signal);
+        }
+        catchable
+    }
+
+    fn has_current_pending_signal(&self) -> bool {
+        let inner = self.inner.lock_irqsave();
+        let task = &inner.tasks[current_task_id()];
+        !(task.signals - task.signal_mask).is_empty()
+    }
+
+    fn swap_current_signal_action(
+        &self,
+        signum: usize,
+        action: Option<SignalAction>,
+    ) -> SignalAction {
+        let current = current_task_id();
+        let mut inner = self.inner.lock_irqsave();
+        let table = &mut inner.tasks[current].signal_actions.table;
+
+        let old_action = table[signum];
+        if let Some(mut action) = action {
+            action.mask = SignalFlags::from_bits_truncate(action.mask.bits());
+            table[signum] = action;
+        }
+        old_action
+    }
+
+    fn set_current_signal_mask(&self, mask: SignalFlags) -> SignalFlags {
+        let current = current_task_id();
+        let mut inner = self.inner.lock_irqsave();
+        let task = &mut inner.tasks[current];
+
+        let old_mask = task.signal_mask;
+        task.signal_mask = mask - SignalFlags::uncatchable();
+        old_mask
+    }
+
+    fn check_current_signals(&self) -> Option<SignalDelivery> {
+        let current = current_task_id();
+        let mut inner = self.inner.lock_irqsave();
+        let task = &mut inner.tasks[current];
+
+        for signum in 1..=MAX_SIG {
+            let signal = SignalFlags::from_signum(signum).unwrap();
+            if !task.signals.contains(signal) {
+                continue;
+            }
+
+            if signal == SignalFlags::SIGKILL {
+                task.signals.remove(signal);
+                return Some(SignalDelivery::Terminate(signum));
             }
-            // go back to user mode
+
+            // 被屏蔽的信号，以及正在执行处理函数期间收到的信号，继续保持待处理状态
+            if task.signal_mask.contains(signal) || task.signal_frame.is_some() {
+                continue;
+            }
+
+            task.signals.remove(signal);
+
+            let action = task.signal_actions.table[signum];
+            match action.handler {
+                SIG_DFL => {
+                    if !SignalFlags::default_ignored().contains(signal) {
+                        return Some(SignalDelivery::Terminate(signum));
+                    }
+                }
+                SIG_IGN => {}
+                _ => return Some(SignalDelivery::Handle(signum, action)),
+            }
+        }
+
+        None
+    }
+
+    /// 用户栈不可写（比如栈指针已经被破坏）时返回 false
+    fn deliver_current_signal(&self, signum: usize, action: SignalAction) -> bool {
+        let current = current_task_id();
+        let fp_owner = processor::fp_owner();
+        let mut inner = self.inner.lock_irqsave();
+        let task = &mut inner.tasks[current];
+        let fp_loaded = fp_owner == Some(current) && task.fp_hart == Some(hart_id());
+
+        let token = task.get_user_token();
+        let trap_cx = task.get_trap_cx();
+
+        // 浮点寄存器里可能还是其他任务的数据（当前任务刚被切换进来，尚未恢复）
+        let fp = if fp_loaded {
+            let mut fp = FpContext::zero_init();
+            fp.save();
+            fp
         } else {
-            panic!("All applications completed!");
+            task.fp_cx
+        };
+
+        // 在用户栈上构建 SignalFrame，保存原来的 trap 上下文和信号屏蔽集合
+        let frame = SignalFrame {
+            x: trap_cx.x,
+            sepc: trap_cx.sepc,
+            fp,
+            mask: task.signal_mask,
+        };
+
+        // 按照调用约定，栈指针需要 16 字节对齐
+        let frame_addr = trap_cx.x[2].wrapping_sub(core::mem::size_of::<SignalFrame>()) & !0xf;
+        if copy_to_user(token, frame_addr as *mut SignalFrame, &frame).is_none() {
+            return false;
         }
+        task.signal_frame = Some(frame_addr);
+
+        // 执行处理函数期间屏蔽该信号本身以及 action 指定的信号
+        task.signal_mask |= action.mask | SignalFlags::from_signum(signum).unwrap();
+        task.signal_mask.remove(SignalFlags::uncatchable());
+
+        // 返回用户态之后，相当于调用了 `handler(signum)`，
+        // 处理函数返回时跳转到 restorer
+        trap_cx.sepc = action.handler;
+        trap_cx.x[1] = action.restorer; // ra
+        trap_cx.x[2] = frame_addr; // sp
+        trap_cx.x[10] = signum; // a0
+        true
     }
 
-    fn find_next_task(&self) -> Option<usize> {
-        let inner = self.inner.exclusive_access();
-        let current = inner.current_task;
-        (current + 1..current + self.num_app + 1)
-            .map(|id| id % self.num_app)
-            .find(|id| inner.tasks[*id].task_status == TaskStatus::Ready)
+    fn sigreturn_current(&self) -> SyscallResult {
+        let current = current_task_id();
+        let mut inner = self.inner.lock_irqsave();
+        let task = &mut inner.tasks[current];
+
+        let frame_addr = task.signal_frame.take().ok_or(Errno::EINVAL)?;
+        let frame: SignalFrame =
+            copy_from_user(task.get_user_token(), frame_addr as *const SignalFrame)
+                .ok_or(Errno::EFAULT)?;
+
+        let trap_cx = task.get_trap_cx();
+        trap_cx.x = frame.x;
+        trap_cx.sepc = frame.sepc;
+        task.signal_mask =
+            SignalFlags::from_bits_truncate(frame.mask.bits()) - SignalFlags::uncatchable();
+
+        // 浮点寄存器在返回用户态之前（`load_current_fp`）恢复
+        task.fp_cx = frame.fp;
+        task.fp_used = true;
+        task.fp_hart = None;
+
+        // trap_handler 会把系统调用的返回值写入 a0，所以这里返回原来的 a0
+        Ok(trap_cx.x[10])
     }
 
-    fn run_first_task(&self) -> ! {
-        let mut inner = self.inner.exclusive_access();
-        let task0 = &mut inner.tasks[0];
-        task0.task_status = TaskStatus::Running;
+    fn load_current_fp(&self) {
+        let current = current_task_id();
+        let hart = hart_id();
+        let fp_owner = processor::fp_owner();
+        let mut inner = self.inner.lock_irqsave();
+        let task = &mut inner.tasks[current];
+
+        // 当前 hart 的浮点寄存器里就是该任务的数据，并且任务在此期间没有在其他 hart 上运行过
+        if fp_owner == Some(current) && task.fp_hart == Some(hart) {
+            return;
+        }
 
-        let next_task_cx_ptr = &task0.task_cx as *const TaskContext;
+        // 浮点寄存器里是其他任务的数据，需要恢复当前任务的浮点寄存器
+        task.fp_cx.restore();
+        task.get_trap_cx()
+            .set_fs(if task.fp_used { FS::Clean } else { FS::Initial });
+        task.fp_hart = Some(hart);
         drop(inner);
-        // before this, we should drop local variables that must be dropped manually
+        processor::set_fp_owner(Some(current));
+    }
+
+    fn get_current_area(
+        &self,
+        va: VirtAddr,
+    ) -> Option<(VirtAddr, VirtAddr, MapType, MapPermission)> {
+        let inner = self.inner.lock_irqsave();
+        let pid = inner.tasks[current_task_id()].pid;
+        inner.processes[pid].memory_set.find_area(va).map(|area| {
+            let (start_va, end_va) = area.va_range();
+            (start_va, end_va, area.map_type(), area.map_perm())
+        })
+    }
+
+    /// 在当前进程里创建一个线程，返回它的 tid
+    fn create_thread(&self, entry: usize, arg: usize) -> Result<usize, MapError> {
+        let current = current_task_id();
+        let mut inner = self.inner.lock_irqsave();
+        let inner = &mut *inner;
+        let task_id = inner.alloc_task_id();
+
+        let parent = &inner.tasks[current];
+        let (pid, user_token) = (parent.pid, parent.user_token);
+        let (signal_actions, signal_mask) = (parent.signal_actions, parent.signal_mask);
+
+        let process = &mut inner.processes[pid];
+        let (tid, user_sp, trap_cx_ppn) = process.alloc_thread(task_id)?;
+        let mut thread =
+            match TaskControlBlock::new(pid, tid, task_id, user_token, trap_cx_ppn, entry, user_sp)
+            {
+                Ok(thread) => thread,
+                Err(err) => {
+                    // 撤销 `alloc_thread`：释放用户栈和 TrapContext 页，tid 可以重新分配
+                    process.release_thread_user_area(tid);
+                    process.threads[tid] = None;
+                    return Err(err);
+                }
+            };
+
+        // 线程函数的参数通过 a0 传递，信号的处理方式和屏蔽集合继承自创建者
+        thread.get_trap_cx().x[10] = arg;
+        thread.signal_actions = signal_actions;
+        thread.signal_mask = signal_mask;
+
+        inner.install_task(task_id, thread);
+        Ok(tid)
+    }
+
+    /// 用已经加载了应用程序的地址空间创建当前进程的子进程，返回它的 pid
+    ///
+    /// 子进程继承当前进程打开的文件和资源限制，信号的处理方式为默认。
+    fn spawn_current(
+        &self,
+        memory_set: MemorySet,
+        user_sp: usize,
+        entry_point: usize,
+    ) -> Result<usize, Errno> {
+        let current = current_task_id();
+        let mut inner = self.inner.lock_irqsave();
+        let inner = &mut *inner;
+        let pid = inner.alloc_task_id();
+        let parent_pid = inner.tasks[current].pid;
+        // 主线程已经退出的进程不会再回收子进程（见 `mark_current_exited`）
+        let parent_alive = inner.tasks[parent_pid].task_status != TaskStatus::Exited;
+
+        // 失败时任务 id 还没有被占用，不需要撤销
+        let (mut process, main_thread) =
+            ProcessControlBlock::with_memory_set(memory_set, user_sp, entry_point, pid)?;
+        let parent = &mut inner.processes[parent_pid];
+        process.fd_table = parent.fd_table.clone();
+        process.rlimits = parent.rlimits;
+        process.sync_frame_limit();
+        if parent_alive {
+            process.parent = Some(parent_pid);
+            parent.children.push(pid);
+        }
+
+        inner.install_task(pid, main_thread);
+        inner.processes.insert(pid, process);
+        Ok(pid)
+    }
 
-        let mut _unused = TaskContext::zero_init();
-        unsafe {
-            __switch(&mut _unused as *mut TaskContext, next_task_cx_ptr);
+    /// 检查当前进程的子进程 `pid`（为 -1 时表示任意一个子进程）是否已经结束：
+    /// 已经结束时把退出码写入 `exit_code`（不为空时）并回收它，返回 (pid, 被回收的进程)，
+    /// 否则把当前任务加入等待队列并返回 None
+    ///
+    /// 先写入退出码再回收：`exit_code` 无效时返回 `EFAULT`，子进程留给下一次 `waitpid`。
+    fn try_waitpid_current(
+        &self,
+        pid: isize,
+        exit_code: *mut i32,
+    ) -> Result<Option<(usize, ProcessControlBlock)>, Errno> {
+        let current = current_task_id();
+        let mut inner = self.inner.lock_irqsave();
+        let inner = &mut *inner;
+
+        let current_pid = inner.tasks[current].pid;
+        let children = &inner.processes[current_pid].children;
+        let matches = |child: usize| pid == -1 || pid == child as isize;
+        if !children.iter().any(|&child| matches(child)) {
+            return Err(Errno::ECHILD);
+        }
+
+        let finished = children
+            .iter()
+            .copied()
+            .find(|&child| matches(child) && inner.is_process_finished(child));
+        if let Some(child) = finished {
+            if !exit_code.is_null() {
+                let token = inner.processes[current_pid].memory_set.token();
+                copy_to_user(token, exit_code, &inner.tasks[child].exit_code)
+                    .ok_or(Errno::EFAULT)?;
+            }
+            let process = inner.reap_process(child);
+            return Ok(Some((child, process)));
+        }
+
+        let waiters = &mut inner.processes[current_pid].wait_waiters;
+        if !waiters.contains(&current) {
+            waiters.push(current);
         }
-        panic!("unreachable in run_first_task!");
+        Ok(None)
+    }
+
+    /// 放弃等待子进程，把当前任务从等待队列里移除
+    fn cancel_waitpid_current(&self) {
+        let current = current_task_id();
+        let mut inner = self.inner.lock_irqsave();
+        let pid = inner.tasks[current].pid;
+        inner.processes[pid]
+            .wait_waiters
+            .retain(|id| *id != current);
+    }
+
+    /// 当前进程（所有线程）使用的 CPU 时间是否已经达到了上限
+    fn is_current_over_cpu_limit(&self) -> bool {
+        let current = current_task_id();
+        let inner = self.inner.lock_irqsave();
+        let process = &inner.processes[inner.tasks[current].pid];
+
+        let limit = process.rlimits.cur(RLIMIT_CPU);
+        if limit == RLIM_INFINITY {
+            return false;
+        }
+
+        let ticks: usize = process
+            .threads
+            .iter()
+            .flatten()
+            .map(|&task_id| inner.tasks[task_id].user_time + inner.tasks[task_id].kernel_time)
+            .sum();
+        ticks + process.reaped_time >= limit.saturating_mul(CLOCK_FREQ)
+    }
+
+    /// 记录当前进程被内核终止时的退出码，并让主线程退出（主线程退出时会终止其余的线程）
+    fn mark_current_process_killed(&self, exit_code: i32) {
+        let current = current_task_id();
+        let mut inner = self.inner.lock_irqsave();
+        let inner = &mut *inner;
+        let pid = inner.tasks[current].pid;
+        inner.processes[pid]
+            .forced_exit_code
+            .get_or_insert(exit_code);
+
+        let main_thread = &mut inner.tasks[pid];
+        if pid != current && main_thread.task_status != TaskStatus::Exited {
+            main_thread.signals.insert(SignalFlags::SIGKILL);
+            main_thread.wakeup();
+        }
+    }
+
+    fn get_current_forced_exit_code(&self) -> Option<i32> {
+        let inner = self.inner.lock_irqsave();
+        inner.processes[inner.tasks[current_task_id()].pid].forced_exit_code
+    }
+
+    /// 检查当前进程的线程 `tid` 是否已经退出：
+    /// 已经退出并且切换出去时回收它，返回它的退出码，否则把当前任务加入它的等待队列并返回 None
+    fn try_waittid_current(&self, tid: usize) -> Result<Option<i32>, Errno> {
+        let current = current_task_id();
+        let mut inner = self.inner.lock_irqsave();
+        let inner = &mut *inner;
+
+        let pid = inner.tasks[current].pid;
+        let task_id = inner.processes[pid]
+            .threads
+            .get(tid)
+            .copied()
+            .flatten()
+            .ok_or(Errno::ESRCH)?;
+        if task_id == current {
+            return Err(Errno::EDEADLK);
+        }
+
+        let thread = &mut inner.tasks[task_id];
+        if thread.task_status == TaskStatus::Exited && !thread.on_cpu {
+            let exit_code = thread.exit_code;
+
+            // 回收线程：释放它的内核栈，tid 和任务 id 可以分配给新的线程；
+            // 主线程的任务 id 即 pid，跟进程一起回收（见 `reap_process`）
+            if tid != 0 {
+                let process = &mut inner.processes[pid];
+                process.reaped_time += thread.user_time + thread.kernel_time;
+                process.threads[tid] = None;
+                inner.release_task(task_id);
+            }
+            return Ok(Some(exit_code));
+        }
+
+        // 被唤醒之后重新检查时不要重复加入
+        if !thread.join_waiters.contains(&current) {
+            thread.join_waiters.push(current);
+        }
+        Ok(None)
+    }
+
+    /// 放弃等待当前进程的线程 `tid`，把当前任务从它的等待队列里移除
+    fn cancel_waittid_current(&self, tid: usize) {
+        let current = current_task_id();
+        let mut inner = self.inner.lock_irqsave();
+        let inner = &mut *inner;
+
+        let pid = inner.tasks[current].pid;
+        if let Some(&Some(task_id)) = inner.processes[pid].threads.get(tid) {
+            inner.tasks[task_id]
+                .join_waiters
+                .retain(|id| *id != current);
+        }
+    }
+
+    fn with_current_process<T>(&self, f: impl FnOnce(&mut ProcessControlBlock) -> T) -> T {
+        let current = current_task_id();
+        let mut inner = self.inner.lock_irqsave();
+        let pid = inner.tasks[current].pid;
+        f(&mut inner.processes[pid])
+    }
+
+    fn account_current_trap(&self) {
+        let current = current_task_id();
+        let mut inner = self.inner.lock_irqsave();
+        let task = &mut inner.tasks[current];
+        task.update_time(true);
+        task.trap_count += 1;
+    }
+
+    fn account_current_kernel_time(&self) {
+        let current = current_task_id();
+        let mut inner = self.inner.lock_irqsave();
+        inner.tasks[current].update_time(false);
+    }
+
+    fn record_current_syscall(&self, syscall_id: usize) {
+        let current = current_task_id();
+        let mut inner = self.inner.lock_irqsave();
+        if let Some(count) = inner.tasks[current].syscall_times.get_mut(syscall_id) {
+            *count += 1;
+        }
+    }
+
+    fn get_current_task_info(&self) -> (TaskInfo, Vec<u32>) {
+        let current = current_task_id();
+        let mut inner = self.inner.lock_irqsave();
+        let task = &mut inner.tasks[current];
+
+        // 把本次系统调用到目前为止的时间也计算在内
+        task.update_time(false);
+
+        let info = TaskInfo {
+            status: task.task_status as usize,
+            user_time: ticks_to_us(task.user_time),
+            kernel_time: ticks_to_us(task.kernel_time),
+            time: ticks_to_us(task.user_time + task.kernel_time),
+            trap_count: task.trap_count,
+        };
+        (info, task.syscall_times.clone())
+    }
+
+    fn get_current_ids(&self) -> (usize, usize) {
+        let inner = self.inner.lock_irqsave();
+        let task = &inner.tasks[current_task_id()];
+        (task.pid, task.tid)
     }
 
     // ch4 新增
     fn get_current_token(&self) -> usize {
-        let inner = self.inner.exclusive_access();
-        let current = inner.current_task;
+        let current = current_task_id();
+        let inner = self.inner.lock_irqsave();
         inner.tasks[current].get_user_token()
     }
 
     // ch4 新增
     fn get_current_trap_cx(&self) -> &mut TrapContext {
-        let inner = self.inner.exclusive_access();
-        let current = inner.current_task;
+        let current = current_task_id();
+        let inner = self.inner.lock_irqsave();
         inner.tasks[current].get_trap_cx()
     }
 }
 
-fn run_next_task() {
-    TASK_MANAGER.run_next_task();
+/// 当前 hart 上正在运行的任务的 id
+pub fn current_task_id() -> usize {
+    processor::current_task().expect("no task is running on this hart")
+}
+
+// ch4 新增
+/// 当前 hart 上是否有正在运行的任务（内核初始化阶段以及调度循环里没有）
+pub fn has_current_task() -> bool {
+    processor::current_task().is_some()
+}
+
+/// 返回用户态之前调用，确保浮点寄存器里是当前任务的数据
+pub fn load_current_fp() {
+    TASK_MANAGER.load_current_fp();
+}
+
+/// 向进程 `pid` 的主线程发送信号，进程不存在或者已经退出时返回 false
+pub fn kill_task(pid: usize, signal: SignalFlags) -> bool {
+    TASK_MANAGER.kill_task(pid, signal)
 }
 
-pub fn run_first_task() {
-    TASK_MANAGER.run_first_task();
+/// 当前任务能否在用户态处理由异常引起的信号 `signal`，
+/// 不能处理时 `current_force_signal` 会直接终止任务
+pub fn current_can_catch_signal(signal: SignalFlags) -> bool {
+    TASK_MANAGER.can_current_catch_signal(signal)
+}
+
+/// 向当前任务发送由异常引起的信号（比如 SIGSEGV）
+///
+/// 如果当前任务没有为该信号设置处理函数，或者该信号被屏蔽，又或者任务正在执行信号处理函数，
+/// 那么返回用户态之后会再次触发同样的异常，所以直接终止任务。
+pub fn current_force_signal(signal: SignalFlags) {
+    if !TASK_MANAGER.force_current_signal(signal) {
+        exit_current_killed(signal.bits().trailing_zeros() as usize);
+    }
+}
+
+/// 当前任务是否有未被屏蔽的待处理信号，用于让阻塞中的系统调用提前返回
+pub fn current_has_pending_signal() -> bool {
+    TASK_MANAGER.has_current_pending_signal()
+}
+
+/// 设置当前任务对信号 `signum` 的处理方式（`action` 为 None 时只读取），返回原来的处理方式
+pub fn current_swap_signal_action(signum: usize, action: Option<SignalAction>) -> SignalAction {
+    TASK_MANAGER.swap_current_signal_action(signum, action)
+}
+
+/// 设置当前任务的信号屏蔽集合，返回原来的屏蔽集合
+pub fn current_set_signal_mask(mask: SignalFlags) -> SignalFlags {
+    TASK_MANAGER.set_current_signal_mask(mask)
+}
+
+/// 从信号处理函数返回，恢复投递信号之前的 trap 上下文；
+/// 当前任务并没有在执行信号处理函数时返回 `EINVAL`，
+/// 用户栈上的 SignalFrame 无法读取时向任务发送 SIGSEGV 并返回 `EFAULT`
+pub fn current_sigreturn() -> SyscallResult {
+    let result = TASK_MANAGER.sigreturn_current();
+    if result == Err(Errno::EFAULT) {
+        current_force_signal(SignalFlags::SIGSEGV);
+    }
+    result
+}
+
+/// 在返回用户态之前检查并处理当前任务待处理的信号
+pub fn handle_signals() {
+    match TASK_MANAGER.check_current_signals() {
+        Some(SignalDelivery::Terminate(signum)) => exit_current_killed(signum),
+        Some(SignalDelivery::Handle(signum, action)) => {
+            // 无法在用户栈上构建 SignalFrame，只能终止任务
+            if !TASK_MANAGER.deliver_current_signal(signum, action) {
+                exit_current_killed(SignalFlags::SIGSEGV.bits().trailing_zeros() as usize);
+            }
+        }
+        None => {}
+    }
+}
+
+fn exit_current_killed(signum: usize) {
+    // 进程已经被内核终止（比如其他线程超出了资源限制）时，使用内核指定的退出码
+    let exit_code = TASK_MANAGER
+        .get_current_forced_exit_code()
+        .unwrap_or_else(|| signal_exit_code(signum));
+    println!(
+        "[kernel] Application killed by signal {}, exit code {}",
+        signum, exit_code
+    );
+    exit_current_and_run_next(exit_code);
+}
+
+/// 当前任务地址空间中包含 `va` 的内存段：起止地址、映射方式和访问权限
+pub fn current_area_of(va: VirtAddr) -> Option<(VirtAddr, VirtAddr, MapType, MapPermission)> {
+    TASK_MANAGER.get_current_area(va)
+}
+
+/// 从用户态陷入内核时调用，把这段时间计入当前任务在用户态运行的时间
+pub fn account_trap_entry() {
+    TASK_MANAGER.account_current_trap();
+}
+
+/// 返回用户态之前调用，把这段时间计入当前任务在内核态运行的时间
+pub fn account_trap_exit() {
+    TASK_MANAGER.account_current_kernel_time();
+}
+
+/// 统计当前任务调用系统调用 `syscall_id` 的次数
+pub fn record_syscall(syscall_id: usize) {
+    TASK_MANAGER.record_current_syscall(syscall_id);
+}
+
+/// 当前任务的状态、运行时间以及各个系统调用的次数（下标为系统调用号）
+pub fn current_task_info() -> (TaskInfo, Vec<u32>) {
+    TASK_MANAGER.get_current_task_info()
+}
+
+/// 当前任务所属进程的 id
+pub fn current_pid() -> usize {
+    TASK_MANAGER.get_current_ids().0
+}
+
+/// 当前任务在进程内的线程 id
+pub fn current_tid() -> usize {
+    TASK_MANAGER.get_current_ids().1
+}
+
+/// 访问当前进程的 ProcessControlBlock
+///
+/// `f` 执行期间持有 TaskManager 的锁，所以不能在其中阻塞或者再次访问 TaskManager。
+pub fn with_current_process<T>(f: impl FnOnce(&mut ProcessControlBlock) -> T) -> T {
+    TASK_MANAGER.with_current_process(f)
+}
+
+/// 在当前进程里创建一个从 `entry` 开始运行的线程，`arg` 作为线程函数的参数，返回新线程的 tid
+///
+/// 物理页帧耗尽时返回 `ENOMEM`；超出驻留内存上限（RLIMIT_RSS）时终止当前进程。
+pub fn create_thread(entry: usize, arg: usize) -> SyscallResult {
+    match TASK_MANAGER.create_thread(entry, arg) {
+        Ok(tid) => Ok(tid),
+        Err(MapError::OutOfMemory) => Err(Errno::ENOMEM),
+        Err(MapError::OverLimit) => {
+            exit_current_over_limit("resident memory", EXIT_RLIMIT_RSS);
+            unreachable!()
+        }
+    }
+}
+
+/// 在时钟中断里调用：当前进程的 CPU 时间超出上限（RLIMIT_CPU）时终止它
+pub fn check_current_cpu_limit() {
+    if TASK_MANAGER.is_current_over_cpu_limit() {
+        exit_current_over_limit("CPU time", EXIT_RLIMIT_CPU);
+    }
+}
+
+/// 当前进程超出了资源限制，以 `exit_code` 终止整个进程
+fn exit_current_over_limit(resource: &str, exit_code: i32) {
+    println!(
+        "[kernel] Application exceeded its {} limit, exit code {}",
+        resource, exit_code
+    );
+    TASK_MANAGER.mark_current_process_killed(exit_code);
+    exit_current_and_run_next(exit_code);
+}
+
+/// 等待当前进程的线程 `tid` 退出，返回它的退出码
+///
+/// 线程不存在时返回 `ESRCH`，等待自己时返回 `EDEADLK`，等待期间收到信号时返回 `EINTR`。
+/// 返回 `EINTR` 之前把当前任务从线程的等待队列里移除，避免之后被错误地唤醒。
+pub fn current_waittid(tid: usize) -> Result<i32, Errno> {
+    loop {
+        if let Some(exit_code) = TASK_MANAGER.try_waittid_current(tid)? {
+            return Ok(exit_code);
+        }
+        if current_has_pending_signal() {
+            TASK_MANAGER.cancel_waittid_current(tid);
+            return Err(Errno::EINTR);
+        }
+        block_current_and_run_next();
+    }
+}
+
+/// 用应用程序的 ELF 数据创建当前进程的子进程，返回它的 pid
+///
+/// 子进程继承当前进程打开的文件，调用者可以事先调整标准输入输出（比如换成管道）。
+/// ELF 数据格式不正确时返回 `ENOEXEC`，
+/// 物理页帧耗尽或者超出继承的驻留内存上限（RLIMIT_RSS）时返回 `ENOMEM`。
+pub fn spawn_current(elf_data: &[u8]) -> Result<usize, Errno> {
+    // 子进程继承 RLIMIT_RSS，所以加载 ELF 时占用的页帧也受它限制
+    let frame_limit = with_current_process(|process| process.frame_limit());
+    // 加载 ELF 的过程中会打印信息，所以不能持有 TaskManager 的锁
+    let (memory_set, user_sp, entry_point) = MemorySet::from_elf(elf_data, frame_limit)?;
+    TASK_MANAGER.spawn_current(memory_set, user_sp, entry_point)
+}
+
+/// 等待当前进程的子进程 `pid`（为 -1 时表示任意一个子进程）结束，
+/// 把它的退出码写入 `exit_code`（不为空时）并回收它，返回它的 pid
+///
+/// 没有符合条件的子进程时返回 `ECHILD`，等待期间收到信号时返回 `EINTR`，
+/// `exit_code` 无效时返回 `EFAULT`，此时子进程不会被回收。
+pub fn current_waitpid(pid: isize, exit_code: *mut i32) -> Result<usize, Errno> {
+    loop {
+        if let Some((child, process)) = TASK_MANAGER.try_waitpid_current(pid, exit_code)? {
+            // 在释放 TaskManager 的锁之后才释放子进程的资源
+            drop(process);
+            return Ok(child);
+        }
+        if current_has_pending_signal() {
+            TASK_MANAGER.cancel_waitpid_current();
+            return Err(Errno::EINTR);
+        }
+        block_current_and_run_next();
+    }
 }
 
 // ch4 新增
@@ -265,3 +1548,8 @@ pub fn current_user_token() -> usize {
 pub fn current_trap_cx() -> &'static mut TrapContext {
     TASK_MANAGER.get_current_trap_cx()
 }
+
+/// 当前线程的 TrapContext 在应用地址空间中的虚拟地址，用于返回用户态
+pub fn current_trap_cx_user_va() -> usize {
+    trap_context_position(current_tid())
+}
@@ -0,0 +1,50 @@
+// 浮点寄存器上下文
+//
+// 用户程序的编译目标是 riscv64gc，编译器可能会使用 F/D 扩展的寄存器 f0~f31 以及 fcsr，
+// 而内核自身并不使用浮点寄存器，所以 trap 的时候不需要保存它们，
+// 只有在切换到另一个任务之前才需要保存，并且是 "惰性" 的：
+//
+// - sstatus.FS 记录了浮点寄存器的状态：Off/Initial/Clean/Dirty，
+//   用户程序修改浮点寄存器之后硬件会自动把 FS 设置为 Dirty；
+// - 任务被切换出去时，只有 FS 为 Dirty 才保存浮点寄存器，并把 FS 设置为 Clean；
+// - 任务被切换进来时，如果浮点寄存器里保存的不是该任务的数据，则恢复它们，
+//   并根据任务是否使用过浮点寄存器把 FS 设置为 Initial 或者 Clean。
+
+use core::arch::global_asm;
+
+global_asm!(include_str!("fp.S"));
+
+extern "C" {
+    fn __save_fp(fp_cx: *mut FpContext);
+    fn __restore_fp(fp_cx: *const FpContext);
+}
+
+#[derive(Copy, Clone)]
+#[repr(C)]
+pub struct FpContext {
+    f: [usize; 32], // f0~f31
+    fcsr: usize,
+}
+
+impl FpContext {
+    pub fn zero_init() -> Self {
+        Self {
+            f: [0; 32],
+            fcsr: 0,
+        }
+    }
+
+    /// 把当前的浮点寄存器保存到 self
+    pub fn save(&mut self) {
+        unsafe {
+            __save_fp(self as *mut Self);
+        }
+    }
+
+    /// 从 self 恢复浮点寄存器
+    pub fn restore(&self) {
+        unsafe {
+            __restore_fp(self as *const Self);
+        }
+    }
+}
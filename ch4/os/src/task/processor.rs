@@ -0,0 +1,71 @@
+// 处理器（hart）的私有状态
+//
+// 启用多个 hart 之后，"当前任务" 不再是全局唯一的，而是每个 hart 各有一个，
+// 所以把它从 TaskManager 移到了每个 hart 私有的 `Processor` 里：
+//
+// - current：当前正在该 hart 上运行的任务；
+// - idle_task_cx：该 hart 的调度循环（`run_tasks`）的任务上下文，
+//   任务让出 CPU 时先切换回调度循环，再由调度循环选择下一个任务；
+// - fp_owner：该 hart 的浮点寄存器里当前是哪个任务的数据。
+//
+// 每个 `Processor` 只会被它所属的 hart 访问，所以它的锁不会发生争用，
+// 使用 IRQ-saving 的方式加锁只是为了防止被中断处理程序重入。
+
+use alloc::vec::Vec;
+use lazy_static::lazy_static;
+
+use crate::{
+    config::MAX_HARTS,
+    smp::hart_id,
+    sync::{lock_order, SpinLock},
+};
+
+use super::context::TaskContext;
+
+pub struct Processor {
+    current: Option<usize>,
+    idle_task_cx: TaskContext,
+    fp_owner: Option<usize>,
+}
+
+impl Processor {
+    fn new() -> Self {
+        Self {
+            current: None,
+            idle_task_cx: TaskContext::zero_init(),
+            fp_owner: None,
+        }
+    }
+}
+
+lazy_static! {
+    static ref PROCESSORS: Vec<SpinLock<Processor>> = (0..MAX_HARTS)
+        .map(|_| SpinLock::named(Processor::new(), "PROCESSOR", lock_order::PROCESSOR))
+        .collect();
+}
+
+fn processor() -> &'static SpinLock<Processor> {
+    &PROCESSORS[hart_id()]
+}
+
+/// 当前 hart 上正在运行的任务
+pub fn current_task() -> Option<usize> {
+    processor().lock_irqsave().current
+}
+
+pub fn set_current_task(task_id: Option<usize>) -> Option<usize> {
+    core::mem::replace(&mut processor().lock_irqsave().current, task_id)
+}
+
+/// 调度循环的任务上下文，它位于 `PROCESSORS` 里，地址不会改变
+pub fn idle_task_cx_ptr() -> *mut TaskContext {
+    &mut processor().lock_irqsave().idle_task_cx as *mut TaskContext
+}
+
+pub fn fp_owner() -> Option<usize> {
+    processor().lock_irqsave().fp_owner
+}
+
+pub fn set_fp_owner(task_id: Option<usize>) {
+    processor().lock_irqsave().fp_owner = task_id;
+}
@@ -0,0 +1,85 @@
+// 资源限制（resource limit），跟 Linux 的 getrlimit/setrlimit 一致
+//
+// 资源限制属于进程，由进程的所有线程共同计算：
+// - RLIMIT_CPU：CPU 时间（秒，包括用户态和内核态），在时钟中断里检查（见 `trap_handler`）
+// - RLIMIT_STACK：新线程的用户栈大小（字节），超出的访问触发 SIGSEGV
+// - RLIMIT_RSS：驻留内存的大小（字节），在为地址空间分配页帧时检查（见 `MemorySet`）
+//
+// 除了 RLIMIT_STACK，默认都没有上限（RLIM_INFINITY）。
+//
+// 超出 CPU 时间或者驻留内存上限的进程被内核终止，退出码分别为
+// `EXIT_RLIMIT_CPU` 和 `EXIT_RLIMIT_RSS`。
+//
+// 注：
+// 目前只检查软限制（rlim_cur），硬限制（rlim_max）只用于约束 setrlimit：
+// 应用程序可以降低硬限制，但不能提高它。
+
+use crate::{config::USER_STACK_SIZE, syscall::errno::Errno};
+
+pub const RLIMIT_CPU: usize = 0;
+pub const RLIMIT_STACK: usize = 3;
+pub const RLIMIT_RSS: usize = 5;
+pub const RLIM_NLIMITS: usize = 16;
+
+pub const RLIM_INFINITY: usize = usize::MAX;
+
+/// 超出 CPU 时间上限的退出码，跟 Linux 一样相当于被 SIGXCPU 终止（128 + 24）
+pub const EXIT_RLIMIT_CPU: i32 = 152;
+
+/// 超出驻留内存上限的退出码，Linux 没有对应的信号，
+/// 这里使用一个不会跟 "128 + 信号编号" 冲突的值
+pub const EXIT_RLIMIT_RSS: i32 = 160;
+
+/// 该结构体由应用程序通过 `getrlimit`/`setrlimit` 传递，所以布局需要跟用户库保持一致。
+#[derive(Copy, Clone)]
+#[repr(C)]
+pub struct RLimit {
+    pub rlim_cur: usize, // 软限制，即实际生效的上限
+    pub rlim_max: usize, // 硬限制，即软限制所能设置的最大值
+}
+
+impl RLimit {
+    const fn new(rlim_cur: usize) -> Self {
+        Self {
+            rlim_cur,
+            rlim_max: RLIM_INFINITY,
+        }
+    }
+}
+
+#[derive(Copy, Clone)]
+pub struct RLimits {
+    table: [RLimit; RLIM_NLIMITS],
+}
+
+impl Default for RLimits {
+    fn default() -> Self {
+        let mut table = [RLimit::new(RLIM_INFINITY); RLIM_NLIMITS];
+        table[RLIMIT_STACK] = RLimit::new(USER_STACK_SIZE);
+        Self { table }
+    }
+}
+
+impl RLimits {
+    pub fn get(&self, resource: usize) -> Result<RLimit, Errno> {
+        self.table.get(resource).copied().ok_or(Errno::EINVAL)
+    }
+
+    /// 软限制大于硬限制时返回 `EINVAL`，提高硬限制时返回 `EPERM`
+    pub fn set(&mut self, resource: usize, limit: RLimit) -> Result<(), Errno> {
+        let old = self.table.get_mut(resource).ok_or(Errno::EINVAL)?;
+        if limit.rlim_cur > limit.rlim_max {
+            return Err(Errno::EINVAL);
+        }
+        if limit.rlim_max > old.rlim_max {
+            return Err(Errno::EPERM);
+        }
+        *old = limit;
+        Ok(())
+    }
+
+    /// 软限制，即实际生效的上限
+    pub fn cur(&self, resource: usize) -> usize {
+        self.table[resource].rlim_cur
+    }
+}
@@ -0,0 +1,152 @@
+// 信号（signal）
+//
+// 信号的编号与 Linux（RISC-V）一致，每个信号在 `SignalFlags` 里占一个 bit，
+// 第 n 个 bit 对应编号为 n 的信号（bit 0 不使用）。
+//
+// 每个任务都有：
+// - 待处理的信号集合（pending）
+// - 被屏蔽的信号集合（mask），被屏蔽的信号会一直处于待处理状态，直到解除屏蔽
+// - 每个信号的处理方式（action），包括默认处理、忽略以及用户自定义的处理函数
+//
+// 内核在返回用户态之前检查待处理的信号，如果该信号设置了处理函数，
+// 则在用户栈上构建一个 `SignalFrame` 保存原来的 trap 上下文，然后让任务 "返回" 到处理函数；
+// 处理函数返回时会跳转到 `restorer`（由用户库提供），
+// 由它执行 `sigreturn` 系统调用恢复原来的 trap 上下文。
+
+use bitflags::bitflags;
+
+use super::fp::FpContext;
+
+pub const MAX_SIG: usize = 31;
+
+/// 使用默认的处理方式
+pub const SIG_DFL: usize = 0;
+
+/// 忽略该信号
+pub const SIG_IGN: usize = 1;
+
+bitflags! {
+    pub struct SignalFlags: u32 {
+        const SIGHUP    = 1 << 1;
+        const SIGINT    = 1 << 2;
+        const SIGQUIT   = 1 << 3;
+        const SIGILL    = 1 << 4;
+        const SIGTRAP   = 1 << 5;
+        const SIGABRT   = 1 << 6;
+        const SIGBUS    = 1 << 7;
+        const SIGFPE    = 1 << 8;
+        const SIGKILL   = 1 << 9;
+        const SIGUSR1   = 1 << 10;
+        const SIGSEGV   = 1 << 11;
+        const SIGUSR2   = 1 << 12;
+        const SIGPIPE   = 1 << 13;
+        const SIGALRM   = 1 << 14;
+        const SIGTERM   = 1 << 15;
+        const SIGSTKFLT = 1 << 16;
+        const SIGCHLD   = 1 << 17;
+        const SIGCONT   = 1 << 18;
+        const SIGSTOP   = 1 << 19;
+        const SIGTSTP   = 1 << 20;
+        const SIGTTIN   = 1 << 21;
+        const SIGTTOU   = 1 << 22;
+        const SIGURG    = 1 << 23;
+        const SIGXCPU   = 1 << 24;
+        const SIGXFSZ   = 1 << 25;
+        const SIGVTALRM = 1 << 26;
+        const SIGPROF   = 1 << 27;
+        const SIGWINCH  = 1 << 28;
+        const SIGIO     = 1 << 29;
+        const SIGPWR    = 1 << 30;
+        const SIGSYS    = 1 << 31;
+    }
+}
+
+impl SignalFlags {
+    /// 编号为 `signum` 的信号，编号无效时返回 None
+    pub fn from_signum(signum: usize) -> Option<Self> {
+        if signum == 0 || signum > MAX_SIG {
+            return None;
+        }
+        Self::from_bits(1 << signum)
+    }
+
+    /// 无法被捕获、忽略或者屏蔽的信号
+    pub fn uncatchable() -> Self {
+        Self::SIGKILL | Self::SIGSTOP
+    }
+
+    /// 默认处理方式为忽略的信号
+    ///
+    /// 注：
+    /// 目前没有实现任务的暂停和继续，所以 SIGSTOP/SIGTSTP/SIGTTIN/SIGTTOU/SIGCONT
+    /// 的默认处理方式也是忽略。
+    pub fn default_ignored() -> Self {
+        Self::SIGCHLD
+            | Self::SIGCONT
+            | Self::SIGURG
+            | Self::SIGWINCH
+            | Self::SIGSTOP
+            | Self::SIGTSTP
+            | Self::SIGTTIN
+            | Self::SIGTTOU
+    }
+}
+
+/// 被信号终止的任务的退出码，跟 shell 的约定一致：128 + 信号编号
+pub fn signal_exit_code(signum: usize) -> i32 {
+    128 + signum as i32
+}
+
+/// 信号的处理方式
+///
+/// 该结构体由应用程序通过 `sigaction` 传入，所以布局需要跟用户库保持一致。
+#[derive(Copy, Clone)]
+#[repr(C)]
+pub struct SignalAction {
+    pub handler: usize,    // 处理函数的地址，或者 SIG_DFL/SIG_IGN
+    pub mask: SignalFlags, // 执行处理函数期间额外屏蔽的信号
+    pub restorer: usize,   // 处理函数返回之后跳转到的地址，负责调用 sigreturn
+}
+
+impl Default for SignalAction {
+    fn default() -> Self {
+        Self {
+            handler: SIG_DFL,
+            mask: SignalFlags::empty(),
+            restorer: 0,
+        }
+    }
+}
+
+#[derive(Copy, Clone)]
+pub struct SignalActions {
+    pub table: [SignalAction; MAX_SIG + 1],
+}
+
+impl Default for SignalActions {
+    fn default() -> Self {
+        Self {
+            table: [SignalAction::default(); MAX_SIG + 1],
+        }
+    }
+}
+
+/// 投递信号时在用户栈上保存的数据
+///
+/// 只保存通用寄存器、浮点寄存器和 sepc，sstatus 不允许应用程序修改，所以不保存也不恢复。
+#[repr(C)]
+pub struct SignalFrame {
+    pub x: [usize; 32],
+    pub sepc: usize,
+    pub fp: FpContext,
+    pub mask: SignalFlags, // 投递信号之前的屏蔽集合
+}
+
+/// 检查待处理的信号之后内核应采取的动作
+pub enum SignalDelivery {
+    /// 使用默认的处理方式终止任务
+    Terminate(usize),
+
+    /// 跳转到用户的处理函数
+    Handle(usize, SignalAction),
+}
@@ -1,10 +1,29 @@
-use crate::{config::CLOCK_FREQ, sbi::set_timer};
+use alloc::collections::BinaryHeap;
+use core::cmp::Ordering;
+use lazy_static::lazy_static;
+
+use crate::{
+    config::CLOCK_FREQ,
+    sync::{lock_order, SpinLock},
+    task::wakeup_task,
+};
 
 use riscv::register::time;
+use sbi::set_timer;
 
-const MICRO_PER_SEC: usize = 1000; // 1 秒 = 1000 毫秒
+// ch4 MODIFY: 原来的 MICRO_PER_SEC 实际上表示的是每秒的毫秒数
+const MSEC_PER_SEC: usize = 1000; // 1 秒 = 1000 毫秒
+const USEC_PER_SEC: usize = 1_000_000; // 1 秒 = 1000000 微秒
+const NSEC_PER_SEC: usize = 1_000_000_000; // 1 秒 = 1000000000 纳秒
 const TICKS_PER_SEC: usize = 1000; // 设定每 1/1000 秒触发一次中断
 
+// 时钟周期和微秒、纳秒之间按毫秒换算：
+// QEMU 的时钟频率是 12.5MHz，每微秒不是整数个时钟周期，而每毫秒是；
+// 同时可以避免中间结果溢出
+const TICKS_PER_MSEC: usize = CLOCK_FREQ / MSEC_PER_SEC;
+const USEC_PER_MSEC: usize = USEC_PER_SEC / MSEC_PER_SEC;
+const NSEC_PER_MSEC: usize = NSEC_PER_SEC / MSEC_PER_SEC;
+
 /// read the `mtime` register
 pub fn get_time() -> usize {
     time::read()
@@ -12,10 +31,136 @@ pub fn get_time() -> usize {
 
 /// get current time in milliseconds
 pub fn get_time_ms() -> usize {
-    time::read() / (CLOCK_FREQ / MICRO_PER_SEC)
+    time::read() / TICKS_PER_MSEC
+}
+
+/// 把时钟周期数换算为微秒
+pub fn ticks_to_us(ticks: usize) -> usize {
+    ticks * USEC_PER_MSEC / TICKS_PER_MSEC
 }
 
 /// set the next timer interrupt
+///
+/// ch4 MODIFY: 下一次时钟中断的时间取下一个时钟周期（tick）和最近的定时器到期时间两者中较早的一个
 pub fn set_next_trigger() {
-    set_timer(get_time() + CLOCK_FREQ / TICKS_PER_SEC);
+    let next_tick = get_time() + CLOCK_FREQ / TICKS_PER_SEC;
+    let next = match TIMERS.lock_irqsave().peek() {
+        Some(timer) => timer.expire.min(next_tick),
+        None => next_tick,
+    };
+    set_timer(next);
+}
+
+/// 时间，跟 Linux 的 `struct timeval` 一致
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct TimeVal {
+    pub sec: usize,
+    pub usec: usize,
+}
+
+impl TimeVal {
+    pub fn from_ticks(ticks: usize) -> Self {
+        Self {
+            sec: ticks / CLOCK_FREQ,
+            usec: ticks_to_us(ticks % CLOCK_FREQ),
+        }
+    }
+}
+
+/// 时间，跟 Linux 的 `struct timespec` 一致
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct TimeSpec {
+    pub tv_sec: usize,
+    pub tv_nsec: usize,
+}
+
+impl TimeSpec {
+    /// 纳秒部分超出范围时返回 None
+    pub fn to_ticks(&self) -> Option<usize> {
+        if self.tv_nsec >= NSEC_PER_SEC {
+            return None;
+        }
+        let ticks = self.tv_sec.checked_mul(CLOCK_FREQ)?;
+        ticks.checked_add(self.tv_nsec * TICKS_PER_MSEC / NSEC_PER_MSEC)
+    }
+
+    pub fn from_ticks(ticks: usize) -> Self {
+        Self {
+            tv_sec: ticks / CLOCK_FREQ,
+            tv_nsec: ticks % CLOCK_FREQ * NSEC_PER_MSEC / TICKS_PER_MSEC,
+        }
+    }
+
+    pub fn from_nanos(nanos: u64) -> Self {
+        let nanos = nanos as usize;
+        Self {
+            tv_sec: nanos / NSEC_PER_SEC,
+            tv_nsec: nanos % NSEC_PER_SEC,
+        }
+    }
+}
+
+// 定时器队列
+//
+// 睡眠的任务被阻塞，同时在定时器队列里记录它的唤醒时间；
+// 每次时钟中断时检查队列，唤醒已经到期的任务。
+// 队列是一个按到期时间排列的最小堆，堆顶就是最早到期的定时器。
+
+struct Timer {
+    expire: usize, // 到期时间（时钟周期数，即 `get_time()` 的值）
+    task_id: usize,
+}
+
+// BinaryHeap 是最大堆，所以反过来比较，让到期时间最早的定时器位于堆顶
+impl Ord for Timer {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.expire.cmp(&self.expire)
+    }
+}
+
+impl PartialOrd for Timer {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Timer {
+    fn eq(&self, other: &Self) -> bool {
+        self.expire == other.expire
+    }
+}
+
+impl Eq for Timer {}
+
+lazy_static! {
+    static ref TIMERS: SpinLock<BinaryHeap<Timer>> =
+        SpinLock::named(BinaryHeap::new(), "TIMERS", lock_order::TIMERS);
+}
+
+/// 在时刻 `expire` 唤醒任务 `task_id`
+pub fn add_timer(expire: usize, task_id: usize) {
+    TIMERS.lock_irqsave().push(Timer { expire, task_id });
+}
+
+/// 取消任务 `task_id` 的所有定时器
+pub fn cancel_timers(task_id: usize) {
+    TIMERS.lock_irqsave().retain(|timer| timer.task_id != task_id);
+}
+
+/// 唤醒所有到期的任务，在时钟中断时调用
+pub fn check_timers() {
+    let now = get_time();
+    loop {
+        let mut timers = TIMERS.lock_irqsave();
+        match timers.peek() {
+            Some(timer) if timer.expire <= now => {
+                let task_id = timers.pop().unwrap().task_id;
+                drop(timers);
+                wakeup_task(task_id);
+            }
+            _ => return,
+        }
+    }
 }
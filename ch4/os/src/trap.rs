@@ -2,22 +2,32 @@ use core::arch::global_asm;
 
 use riscv::register::{
     scause::{self, Exception, Interrupt, Trap},
-    sie, stval, stvec,
+    sepc, sie, sstatus, stval, stvec,
     utvec::TrapMode,
 };
 
 // use crate::{batch::run_next_app, syscall::syscall};
 
 use crate::{
-    config::{TRAMPOLINE, TRAP_CONTEXT},
+    config::TRAMPOLINE,
+    drivers,
+    mm::tlb::{clear_active_token, set_active_token},
     syscall::syscall,
-    task::{current_trap_cx, current_user_token, suspend_current_and_run_next, exit_current_and_run_next},
-    timer::set_next_trigger,
+    task::{
+        account_trap_entry, account_trap_exit, check_current_cpu_limit, current_can_catch_signal,
+        current_force_signal, current_trap_cx, current_trap_cx_user_va, current_user_token,
+        handle_signals, load_current_fp, signal::SignalFlags, suspend_current_and_run_next,
+    },
+    timer::{check_timers, set_next_trigger},
 };
 
+use self::context::TrapContext;
+
 pub mod context;
+mod fault;
 
 global_asm!(include_str!("trap/trap.S"));
+global_asm!(include_str!("trap/kernel_trap.S"));
 
 pub fn init() {
     set_kernel_trap_entry();
@@ -31,8 +41,11 @@ pub fn enable_timer_interrupt() {
 }
 
 fn set_kernel_trap_entry() {
+    extern "C" {
+        fn __kernel_trap();
+    }
     unsafe {
-        stvec::write(trap_from_kernel as usize, TrapMode::Direct);
+        stvec::write(__kernel_trap as usize, TrapMode::Direct);
     }
 }
 
@@ -46,11 +59,43 @@ fn set_user_trap_entry() {
     }
 }
 
-/// Unimplement: traps/interrupts/exceptions from kernel mode
-/// Todo: Chapter 9: I/O device
+/// 没有可运行的任务时，内核在此等待中断
+///
+/// 内核态平时不开启中断（sstatus.SIE 为 0），只在这里短暂地开启，
+/// 中断到来时经 `__kernel_trap` 进入 `kernel_trap_handler` 处理，返回之后再关闭中断。
+/// 调用者不能持有任何 IRQ-saving 的锁（见 `sync::irq`）。
+pub fn wait_for_interrupt() {
+    unsafe {
+        sstatus::set_sie();
+        asm!("wfi");
+        sstatus::clear_sie();
+    }
+}
+
+/// 内核态的陷入处理
+///
+/// 只有在 `wait_for_interrupt` 开启中断的窗口里才会发生中断，
+/// 其他的陷入（比如内核访问了无效的地址）都是内核的错误。
 #[no_mangle]
-pub fn trap_from_kernel() -> ! {
-    panic!("a trap from kernel!");
+pub fn kernel_trap_handler() {
+    let scause = scause::read();
+    match scause.cause() {
+        Trap::Interrupt(Interrupt::SupervisorExternal) => {
+            drivers::handle_irq();
+        }
+        Trap::Interrupt(Interrupt::SupervisorTimer) => {
+            check_timers();
+            set_next_trigger();
+        }
+        _ => {
+            panic!(
+                "a trap {:?} from kernel, stval = {:#x}, sepc = {:#x}",
+                scause.cause(),
+                stval::read(),
+                sepc::read()
+            );
+        }
+    }
 }
 
 #[no_mangle]
@@ -61,28 +106,41 @@ pub fn trap_handler() -> ! {
     let cx = current_trap_cx();
     // ch4 新增 --- /
 
+    // 已经切换回内核地址空间，当前 hart 不再缓存应用地址空间的页表项（见 `mm/tlb.rs`）
+    clear_active_token();
+    account_trap_entry();
+
     let scause = scause::read();
     let stval = stval::read();
     match scause.cause() {
         Trap::Exception(Exception::UserEnvCall) => {
             cx.sepc += 4;
-            cx.x[10] = syscall(cx.x[17], [cx.x[10], cx.x[11], cx.x[12]]) as usize;
+            cx.x[10] = syscall(
+                cx.x[17],
+                [cx.x[10], cx.x[11], cx.x[12], cx.x[13], cx.x[14], cx.x[15]],
+            ) as usize;
         }
         Trap::Exception(Exception::StoreFault)
         | Trap::Exception(Exception::StorePageFault)
         | Trap::Exception(Exception::LoadFault)
-        | Trap::Exception(Exception::LoadPageFault) => {
-            println!("[kernel] PageFault in application, bad addr = {:#x}, bad instruction = {:#x}, kernel killed it.", stval, cx.sepc);
-            exit_current_and_run_next();
+        | Trap::Exception(Exception::LoadPageFault)
+        | Trap::Exception(Exception::InstructionFault)
+        | Trap::Exception(Exception::InstructionPageFault) => {
+            user_fault(scause.cause(), stval, cx, SignalFlags::SIGSEGV);
         }
         Trap::Exception(Exception::IllegalInstruction) => {
-            println!("[kernel] IllegalInstruction in application, kernel killed it.");
-            exit_current_and_run_next();
+            user_fault(scause.cause(), stval, cx, SignalFlags::SIGILL);
         }
         Trap::Interrupt(Interrupt::SupervisorTimer) => {
+            // 唤醒睡眠时间已到的任务
+            check_timers();
             set_next_trigger();
+            check_current_cpu_limit();
             suspend_current_and_run_next();
         }
+        Trap::Interrupt(Interrupt::SupervisorExternal) => {
+            drivers::handle_irq();
+        }
         _ => {
             panic!(
                 "Unsupported trap {:?}, stval = {:#x}!",
@@ -91,17 +149,34 @@ pub fn trap_handler() -> ! {
             );
         }
     }
+
+    // 返回用户态之前处理待处理的信号
+    handle_signals();
+
     // cx
     trap_return();
 }
 
+/// 应用程序触发了异常：如果应用程序设置了相应的信号处理函数，则交给它处理；
+/// 否则打印异常报告，然后终止应用程序
+fn user_fault(cause: Trap, stval: usize, cx: &TrapContext, signal: SignalFlags) {
+    if !current_can_catch_signal(signal) {
+        fault::report_user_fault(cause, stval, cx);
+    }
+    current_force_signal(signal);
+}
+
 use core::arch::asm;
 
 #[no_mangle]
 pub fn trap_return() -> ! {
+    load_current_fp();
+    account_trap_exit();
     set_user_trap_entry();
-    let trap_cx_ptr = TRAP_CONTEXT;
+    // ch4 MODIFY: 每个线程的 TrapContext 位于不同的页
+    let trap_cx_ptr = current_trap_cx_user_va();
     let user_satp = current_user_token();
+    set_active_token(user_satp);
 
     extern "C" {
         fn __alltraps();
@@ -1,4 +1,4 @@
-use riscv::register::sstatus::{self, Sstatus, SPP};
+use riscv::register::sstatus::{self, Sstatus, FS, SPP};
 
 #[repr(C)]
 pub struct TrapContext {
@@ -12,6 +12,9 @@ pub struct TrapContext {
     pub kernel_satp: usize, // 表示内核地址空间的 token ，即内核页表的起始物理地址；显然所有 trap context 的该成员值都一样
     pub kernel_sp: usize,   // 表示当前应用在内核地址空间中的内核栈栈顶的虚拟地址；
     pub trap_handler: usize, // 表示内核中 trap handler 入口点的虚拟地址。
+
+    // 多核
+    pub kernel_tp: usize, // 返回用户态之前由 `__restore` 写入当前 hart 的 id，trap 时恢复到 tp
 }
 
 impl TrapContext {
@@ -19,6 +22,18 @@ impl TrapContext {
         self.x[2] = sp;
     }
 
+    /// 读取保存的 sstatus 里的 FS 字段，即用户程序的浮点寄存器状态
+    pub fn fs(&self) -> FS {
+        self.sstatus.fs()
+    }
+
+    /// 设置保存的 sstatus 里的 FS 字段（bit 13~14），`__restore` 时生效
+    pub fn set_fs(&mut self, fs: FS) {
+        let bits: usize = unsafe { core::mem::transmute(self.sstatus) };
+        let bits = (bits & !(0b11 << 13)) | ((fs as usize) << 13);
+        self.sstatus = unsafe { core::mem::transmute(bits) };
+    }
+
     pub fn app_init_context(
         entry: usize,
         sp: usize,
@@ -41,9 +56,13 @@ impl TrapContext {
             kernel_sp,
             trap_handler,
             // ch4 新增 --------/
+            kernel_tp: 0,
         };
 
         cx.set_sp(sp);
+
+        // 浮点寄存器处于初始状态，任务第一次被切换进来时会被清零
+        cx.set_fs(FS::Initial);
         cx
     }
 }
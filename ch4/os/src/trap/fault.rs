@@ -0,0 +1,123 @@
+// 应用程序异常报告
+//
+// 应用程序因为异常（比如访问了无效的地址）被内核终止时，打印：
+// - 异常的原因（scause）和相关的地址（stval）
+// - 包含出错地址的内存段（MapArea），没有找到则说明该地址根本没有被映射
+// - TrapContext 里保存的 32 个通用寄存器
+// - 沿着帧指针（fp，即 s0）回溯得到的用户栈调用链
+//
+// 用户程序使用 `-C force-frame-pointers=yes` 编译，每个函数的栈帧布局如下：
+//
+// ```text
+// 高地址  +----------------+ <-- fp（当前函数的帧指针）
+//         | ra             |     fp - 8
+//         | 调用者的 fp     |     fp - 16
+//         | ...            |
+// 低地址  +----------------+ <-- sp
+// ```
+//
+// 所以从 TrapContext 里的 s0 开始，反复读取 `fp - 8`（返回地址）和 `fp - 16`（调用者的帧指针），
+// 就能得到整个调用链。用户栈位于应用的地址空间，需要通过应用的页表读取。
+
+use riscv::register::scause::Trap;
+
+use crate::{
+    mm::{address::VirtAddr, page_table::copy_from_user},
+    task::{current_area_of, current_pid, current_tid, current_user_token},
+};
+
+use super::context::TrapContext;
+
+/// 回溯的最大层数，防止栈被破坏时出现死循环
+const MAX_BACKTRACE_DEPTH: usize = 32;
+
+/// 通用寄存器的 ABI 名称
+const REGISTER_NAMES: [&str; 32] = [
+    "zero", "ra", "sp", "gp", "tp", "t0", "t1", "t2", "s0", "s1", "a0", "a1", "a2", "a3", "a4",
+    "a5", "a6", "a7", "s2", "s3", "s4", "s5", "s6", "s7", "s8", "s9", "s10", "s11", "t3", "t4",
+    "t5", "t6",
+];
+
+/// 打印当前任务的异常报告
+pub fn report_user_fault(cause: Trap, stval: usize, cx: &TrapContext) {
+    println!("[kernel] ---------- user fault report ----------");
+    println!(
+        "[kernel] app {} (tid {}) trapped: scause = {:?}, stval = {:#x}, sepc = {:#x}",
+        current_pid(),
+        current_tid(),
+        cause,
+        stval,
+        cx.sepc
+    );
+
+    match current_area_of(VirtAddr::from(stval)) {
+        Some((start_va, end_va, map_type, map_perm)) => println!(
+            "[kernel] stval is in MapArea [{:#x}, {:#x}), {:?}, {:?}",
+            usize::from(start_va),
+            usize::from(end_va),
+            map_type,
+            map_perm
+        ),
+        None => println!("[kernel] stval is not in any MapArea"),
+    }
+
+    print_registers(cx);
+    print_backtrace(cx);
+    println!("[kernel] ---------------------------------------");
+}
+
+fn print_registers(cx: &TrapContext) {
+    println!("[kernel] registers:");
+    for row in 0..8 {
+        let i = row * 4;
+        println!(
+            "[kernel]   {:>4}: {:#018x} {:>4}: {:#018x} {:>4}: {:#018x} {:>4}: {:#018x}",
+            REGISTER_NAMES[i],
+            cx.x[i],
+            REGISTER_NAMES[i + 1],
+            cx.x[i + 1],
+            REGISTER_NAMES[i + 2],
+            cx.x[i + 2],
+            REGISTER_NAMES[i + 3],
+            cx.x[i + 3]
+        );
+    }
+}
+
+/// 沿着帧指针回溯用户栈，遇到无效的帧指针（为 0、未对齐、未映射，或者没有往高地址方向前进）时停止
+fn print_backtrace(cx: &TrapContext) {
+    let token = current_user_token();
+
+    println!("[kernel] backtrace:");
+    println!("[kernel]   #0  pc = {:#x}", cx.sepc);
+
+    let mut fp = cx.x[8];
+    for depth in 1..=MAX_BACKTRACE_DEPTH {
+        if fp == 0 || fp % core::mem::size_of::<usize>() != 0 {
+            return;
+        }
+
+        let ra = copy_from_user(token, fp.wrapping_sub(8) as *const usize);
+        let prev_fp = copy_from_user(token, fp.wrapping_sub(16) as *const usize);
+        let (ra, prev_fp) = match (ra, prev_fp) {
+            (Some(ra), Some(prev_fp)) => (ra, prev_fp),
+            _ => {
+                println!("[kernel]   (frame pointer {:#x} is not readable)", fp);
+                return;
+            }
+        };
+
+        if ra == 0 {
+            return;
+        }
+        println!("[kernel]   #{:<2} ra = {:#x} (fp = {:#x})", depth, ra, fp);
+
+        // 栈向低地址增长，调用者的栈帧一定位于更高的地址
+        if prev_fp <= fp {
+            return;
+        }
+        fp = prev_fp;
+    }
+
+    println!("[kernel]   ...");
+}
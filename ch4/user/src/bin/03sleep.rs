@@ -4,15 +4,15 @@
 #[macro_use]
 extern crate user;
 
-use user::{get_time, yield_};
+use user::{get_time, sleep};
 
+// 睡眠期间任务被阻塞，不再像之前那样反复 yield 占用 CPU
 #[no_mangle]
 fn main() -> i32 {
-    let current_timer = get_time();
-    let wait_for = current_timer + 3000;
-    while get_time() < wait_for {
-        yield_();
-    }
+    let start = get_time();
+    assert_eq!(sleep(3000), 0);
+    let elapsed = get_time() - start;
+    assert!(elapsed >= 3000, "woke up too early: {} ms", elapsed);
     println!("Test sleep OK!");
     0
 }
@@ -0,0 +1,43 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user;
+
+use user::{getpid, kill, sigaction, SignalAction, SIGUSR1};
+
+static mut RECEIVED: bool = false;
+
+extern "C" fn func(signum: i32) {
+    println!("user signal handler, signum = {}", signum);
+    unsafe {
+        RECEIVED = true;
+    }
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    let new = SignalAction {
+        handler: func as usize,
+        ..Default::default()
+    };
+
+    println!("signal_simple: sigaction");
+    if sigaction(SIGUSR1, Some(&new), None) < 0 {
+        panic!("sigaction failed!");
+    }
+
+    println!("signal_simple: kill");
+    if kill(getpid() as usize, SIGUSR1) < 0 {
+        println!("Kill failed!");
+        return -1;
+    }
+
+    if unsafe { !RECEIVED } {
+        println!("signal handler was not called!");
+        return -1;
+    }
+
+    println!("Test sig_simple OK!");
+    0
+}
@@ -0,0 +1,35 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user;
+
+use core::ptr::null_mut;
+
+use user::{exit, sigaction, SignalAction, SIGSEGV};
+
+extern "C" fn on_segv(signum: i32) {
+    println!("caught SIGSEGV (signum = {}) in user handler", signum);
+    println!("Test sig_fault OK!");
+    exit(0);
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    println!("\nsig_fault APP running...\n");
+
+    let new = SignalAction {
+        handler: on_segv as usize,
+        ..Default::default()
+    };
+    sigaction(SIGSEGV, Some(&new), None);
+
+    println!("Into Test sig_fault, we will insert an invalid store operation...");
+    println!("The handler should catch SIGSEGV instead of the kernel killing this application!");
+    unsafe {
+        null_mut::<u8>().write_volatile(1);
+    }
+
+    println!("Should not reach here!");
+    -1
+}
@@ -0,0 +1,36 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user;
+
+use core::hint::black_box;
+
+// 跟 09float_b 同时运行，两个任务会被时钟中断反复切换，
+// 如果内核没有保存和恢复浮点寄存器，累加的结果会被对方破坏
+const ITER: usize = 2_000_000;
+const STEP: f64 = 0.5;
+
+#[no_mangle]
+fn main() -> i32 {
+    let mut x: f64 = 1.0;
+    let mut sum: f64 = 0.0;
+    for i in 0..ITER {
+        sum += black_box(x);
+        x += STEP;
+        if i % 200_000 == 0 {
+            println!("float_a [{}/{}]", i, ITER);
+        }
+    }
+
+    // 所有的数都是 0.5 的整数倍，并且远小于 2^52，所以累加的结果是精确的
+    let n = ITER as f64;
+    let expected = n + STEP * (n * (n - 1.0) / 2.0);
+    if sum != expected {
+        println!("float_a: sum = {}, expected = {}", sum, expected);
+        return -1;
+    }
+
+    println!("Test float_a OK!");
+    0
+}
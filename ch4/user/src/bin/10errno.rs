@@ -0,0 +1,51 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user;
+
+use user::{
+    getpid, kill, sigaction, write, SignalAction, EBADF, EFAULT, EINVAL, ESRCH, SIGKILL,
+};
+
+/// 检查系统调用的返回值是否为指定的错误码
+fn expect(name: &str, ret: isize, errno: isize) -> bool {
+    if ret == -errno {
+        println!("{}: returned -{} as expected", name, errno);
+        true
+    } else {
+        println!("{}: expected -{}, but got {}", name, errno, ret);
+        false
+    }
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    println!("\nerrno APP running...\n");
+
+    let mut ok = true;
+
+    // 不存在的文件描述符
+    ok &= expect("write to fd 42", write(42, b"hello\n"), EBADF);
+
+    // 缓冲区位于没有映射的地址（用户程序从 0x10000 开始）
+    let bad_buffer = unsafe { core::slice::from_raw_parts(0x1000 as *const u8, 16) };
+    ok &= expect("write bad buffer", write(1, bad_buffer), EFAULT);
+
+    // 不存在的任务
+    ok &= expect("kill pid 9999", kill(9999, SIGKILL), ESRCH);
+
+    // SIGKILL 不允许被捕获
+    let action = SignalAction::default();
+    ok &= expect("sigaction SIGKILL", sigaction(SIGKILL, Some(&action), None), EINVAL);
+
+    // 无效的信号编号
+    ok &= expect("kill signum 64", kill(getpid() as usize, 64), EINVAL);
+
+    if !ok {
+        return -1;
+    }
+
+    println!("Test errno OK!");
+    0
+}
@@ -0,0 +1,40 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user;
+
+use core::ptr::write_volatile;
+
+// 通过几层函数调用之后再访问无效的地址，
+// 内核打印的异常报告里应该能看到完整的调用链：fault_here <- level_2 <- level_1 <- main <- _start
+
+#[inline(never)]
+fn fault_here(addr: usize) {
+    unsafe {
+        write_volatile(addr as *mut usize, 0xdead);
+    }
+}
+
+#[inline(never)]
+fn level_2(addr: usize) {
+    fault_here(addr + 8);
+    println!("level_2 returned");
+}
+
+#[inline(never)]
+fn level_1(addr: usize) {
+    level_2(addr + 8);
+    println!("level_1 returned");
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    println!("\nbacktrace APP running...\n");
+    println!("Into Test backtrace, we will insert an invalid store operation in a nested call...");
+    println!("Kernel should print a fault report with the call chain and kill this application!");
+    level_1(0x1000);
+
+    println!("Should not reach here!");
+    -1
+}
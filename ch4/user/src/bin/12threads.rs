@@ -0,0 +1,60 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user;
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use user::{exit, getpid, gettid, thread_create, waittid, EDEADLK, ESRCH};
+
+// 多个线程共享同一个地址空间：各自累加同一个全局计数器，
+// 主线程等待所有线程退出之后检查计数器以及各个线程的退出码，
+// 最后检查被回收的线程的 tid 可以被新的线程重新使用
+const THREADS: usize = 4;
+const ITER: usize = 1000;
+
+static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+extern "C" fn worker(arg: usize) -> ! {
+    println!("thread {} (tid {}, pid {}) started", arg, gettid(), getpid());
+    for _ in 0..ITER {
+        COUNTER.fetch_add(1, Ordering::Relaxed);
+    }
+    exit(arg as i32 * 10);
+    unreachable!()
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    let mut tids = [0usize; THREADS];
+    for (i, tid) in tids.iter_mut().enumerate() {
+        let ret = thread_create(worker as usize, i);
+        assert!(ret > 0, "thread_create failed: {}", ret);
+        *tid = ret as usize;
+    }
+
+    for (i, tid) in tids.iter().enumerate() {
+        let mut exit_code = 0;
+        assert_eq!(waittid(*tid, &mut exit_code), *tid as isize);
+        assert_eq!(exit_code, i as i32 * 10);
+    }
+
+    let counter = COUNTER.load(Ordering::Relaxed);
+    assert_eq!(counter, THREADS * ITER);
+
+    let mut exit_code = 0;
+    assert_eq!(waittid(gettid() as usize, &mut exit_code), -EDEADLK);
+    assert_eq!(waittid(100, &mut exit_code), -ESRCH);
+
+    // 已经被回收的线程不能再次等待，它的 tid 会分配给新创建的线程
+    assert_eq!(waittid(tids[0], &mut exit_code), -ESRCH);
+    let tid = thread_create(worker as usize, THREADS);
+    assert_eq!(tid, tids[0] as isize);
+    assert_eq!(waittid(tid as usize, &mut exit_code), tid);
+    assert_eq!(exit_code, THREADS as i32 * 10);
+
+    println!("counter = {}", counter);
+    println!("Test threads OK!");
+    0
+}
@@ -0,0 +1,66 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user;
+
+use core::ptr::{addr_of_mut, read_volatile, write_volatile};
+
+use user::{
+    exit, mutex_create, mutex_lock, mutex_unlock, thread_create, waittid, yield_, EDEADLK, EPERM,
+};
+
+// 多个线程在互斥锁的保护下对同一个（非原子的）计数器执行 "读取 -> 让出 CPU -> 写回"，
+// 如果互斥锁不起作用，累加的结果会小于预期。
+// 等待锁的线程被阻塞，而不是反复 yield 占用时间片。
+const THREADS: usize = 4;
+const ITER: usize = 100;
+
+static mut COUNTER: usize = 0;
+static mut MUTEX_ID: usize = 0;
+
+extern "C" fn worker(_arg: usize) -> ! {
+    let mutex_id = unsafe { MUTEX_ID };
+    for _ in 0..ITER {
+        assert_eq!(mutex_lock(mutex_id), 0);
+        unsafe {
+            let counter = addr_of_mut!(COUNTER);
+            let value = read_volatile(counter);
+            yield_();
+            write_volatile(counter, value + 1);
+        }
+        assert_eq!(mutex_unlock(mutex_id), 0);
+    }
+    exit(0);
+    unreachable!()
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    let mutex_id = mutex_create();
+    assert!(mutex_id >= 0);
+    unsafe {
+        MUTEX_ID = mutex_id as usize;
+    }
+
+    // 释放没有持有的锁，以及重复获取已经持有的锁
+    assert_eq!(mutex_unlock(mutex_id as usize), -EPERM);
+    assert_eq!(mutex_lock(mutex_id as usize), 0);
+    assert_eq!(mutex_lock(mutex_id as usize), -EDEADLK);
+    assert_eq!(mutex_unlock(mutex_id as usize), 0);
+
+    let mut tids = [0usize; THREADS];
+    for tid in tids.iter_mut() {
+        *tid = thread_create(worker as usize, 0) as usize;
+    }
+    for tid in tids {
+        let mut exit_code = 0;
+        waittid(tid, &mut exit_code);
+    }
+
+    let counter = unsafe { read_volatile(addr_of_mut!(COUNTER)) };
+    println!("counter = {}", counter);
+    assert_eq!(counter, THREADS * ITER);
+    println!("Test mutex OK!");
+    0
+}
@@ -0,0 +1,58 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user;
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use user::{exit, semaphore_create, semaphore_down, semaphore_up, thread_create, waittid};
+
+// 生产者/消费者：容量为 BUFFER_SIZE 的环形缓冲区，
+// EMPTY 信号量表示空位的数量，FULL 信号量表示已有数据的数量。
+const BUFFER_SIZE: usize = 4;
+const ITEMS: usize = 64;
+
+static mut BUFFER: [usize; BUFFER_SIZE] = [0; BUFFER_SIZE];
+static EMPTY: AtomicUsize = AtomicUsize::new(0);
+static FULL: AtomicUsize = AtomicUsize::new(0);
+
+extern "C" fn producer(_arg: usize) -> ! {
+    let (empty, full) = (EMPTY.load(Ordering::Relaxed), FULL.load(Ordering::Relaxed));
+    for i in 0..ITEMS {
+        semaphore_down(empty);
+        unsafe {
+            BUFFER[i % BUFFER_SIZE] = i;
+        }
+        semaphore_up(full);
+    }
+    exit(0);
+    unreachable!()
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    EMPTY.store(semaphore_create(BUFFER_SIZE) as usize, Ordering::Relaxed);
+    FULL.store(semaphore_create(0) as usize, Ordering::Relaxed);
+    let (empty, full) = (EMPTY.load(Ordering::Relaxed), FULL.load(Ordering::Relaxed));
+
+    let tid = thread_create(producer as usize, 0) as usize;
+
+    // 主线程作为消费者，按顺序取出生产者放入的数据
+    let mut sum = 0;
+    for i in 0..ITEMS {
+        semaphore_down(full);
+        let item = unsafe { BUFFER[i % BUFFER_SIZE] };
+        assert_eq!(item, i);
+        sum += item;
+        semaphore_up(empty);
+    }
+
+    let mut exit_code = 0;
+    waittid(tid, &mut exit_code);
+
+    println!("sum = {}", sum);
+    assert_eq!(sum, ITEMS * (ITEMS - 1) / 2);
+    println!("Test semaphore OK!");
+    0
+}
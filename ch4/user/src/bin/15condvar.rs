@@ -0,0 +1,67 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user;
+
+use core::{
+    ptr::{addr_of_mut, read_volatile, write_volatile},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use user::{
+    condvar_create, condvar_signal, condvar_wait, exit, get_time, mutex_create, mutex_lock,
+    mutex_unlock, thread_create, waittid, yield_,
+};
+
+// 主线程在条件变量上等待 READY 被设置，另一个线程稍后设置 READY 并发出通知
+static mut READY: bool = false;
+static MUTEX_ID: AtomicUsize = AtomicUsize::new(0);
+static CONDVAR_ID: AtomicUsize = AtomicUsize::new(0);
+
+extern "C" fn notifier(_arg: usize) -> ! {
+    let (mutex_id, condvar_id) = (
+        MUTEX_ID.load(Ordering::Relaxed),
+        CONDVAR_ID.load(Ordering::Relaxed),
+    );
+
+    // 让主线程先进入等待
+    let wait_for = get_time() + 100;
+    while get_time() < wait_for {
+        yield_();
+    }
+
+    mutex_lock(mutex_id);
+    unsafe {
+        write_volatile(addr_of_mut!(READY), true);
+    }
+    condvar_signal(condvar_id);
+    mutex_unlock(mutex_id);
+
+    exit(0);
+    unreachable!()
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    MUTEX_ID.store(mutex_create() as usize, Ordering::Relaxed);
+    CONDVAR_ID.store(condvar_create() as usize, Ordering::Relaxed);
+    let (mutex_id, condvar_id) = (
+        MUTEX_ID.load(Ordering::Relaxed),
+        CONDVAR_ID.load(Ordering::Relaxed),
+    );
+
+    let tid = thread_create(notifier as usize, 0) as usize;
+
+    mutex_lock(mutex_id);
+    while !unsafe { read_volatile(addr_of_mut!(READY)) } {
+        assert_eq!(condvar_wait(condvar_id, mutex_id), 0);
+    }
+    mutex_unlock(mutex_id);
+    println!("condvar: ready");
+
+    let mut exit_code = 0;
+    waittid(tid, &mut exit_code);
+    println!("Test condvar OK!");
+    0
+}
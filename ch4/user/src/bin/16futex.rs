@@ -0,0 +1,61 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user;
+
+use core::{
+    ptr::{addr_of_mut, read_volatile, write_volatile},
+    sync::atomic::AtomicU32,
+};
+
+use user::{exit, futex_wait, futex_wake, thread_create, waittid, yield_, FutexMutex, EAGAIN};
+
+// 跟 13mutex 一样，多个线程在锁的保护下累加同一个计数器，
+// 不过这里使用的是基于 futex 的用户态互斥锁，没有争用时不进入内核
+const THREADS: usize = 4;
+const ITER: usize = 100;
+
+static LOCK: FutexMutex = FutexMutex::new();
+static mut COUNTER: usize = 0;
+
+extern "C" fn worker(_arg: usize) -> ! {
+    for i in 0..ITER {
+        LOCK.lock();
+        unsafe {
+            let counter = addr_of_mut!(COUNTER);
+            let value = read_volatile(counter);
+            // 偶尔在持有锁的期间让出 CPU，制造争用
+            if i % 10 == 0 {
+                yield_();
+            }
+            write_volatile(counter, value + 1);
+        }
+        LOCK.unlock();
+    }
+    exit(0);
+    unreachable!()
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    // 值不相等时 FUTEX_WAIT 立即返回 EAGAIN；没有等待者时 FUTEX_WAKE 返回 0
+    let word = AtomicU32::new(1);
+    assert_eq!(futex_wait(&word, 0), -EAGAIN);
+    assert_eq!(futex_wake(&word, 1), 0);
+
+    let mut tids = [0usize; THREADS];
+    for tid in tids.iter_mut() {
+        *tid = thread_create(worker as usize, 0) as usize;
+    }
+    for tid in tids {
+        let mut exit_code = 0;
+        waittid(tid, &mut exit_code);
+    }
+
+    let counter = unsafe { read_volatile(addr_of_mut!(COUNTER)) };
+    println!("counter = {}", counter);
+    assert_eq!(counter, THREADS * ITER);
+    println!("Test futex OK!");
+    0
+}
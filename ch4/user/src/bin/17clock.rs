@@ -0,0 +1,51 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user;
+
+use user::{
+    clock_gettime, get_time_us, gettimeofday, TimeSpec, TimeVal, CLOCK_MONOTONIC, CLOCK_REALTIME,
+    EINVAL,
+};
+
+// 2020-01-01 00:00:00 UTC
+const UNIX_TIME_2020: usize = 1_577_836_800;
+
+// 检查 gettimeofday 和 clock_gettime 返回的时间是合法的、单调递增的，并且精度高于毫秒
+#[no_mangle]
+fn main() -> i32 {
+    let mut tv = TimeVal::default();
+    assert_eq!(gettimeofday(&mut tv), 0);
+    assert!(tv.usec < 1_000_000);
+    println!("gettimeofday: {}.{:06}", tv.sec, tv.usec);
+
+    let mut ts = TimeSpec::default();
+    assert_eq!(clock_gettime(CLOCK_MONOTONIC, &mut ts), 0);
+    assert!(ts.tv_nsec < 1_000_000_000);
+    println!("clock_gettime(CLOCK_MONOTONIC): {}.{:09}", ts.tv_sec, ts.tv_nsec);
+
+    let mut prev = TimeSpec::default();
+    for _ in 0..100 {
+        assert_eq!(clock_gettime(CLOCK_MONOTONIC, &mut ts), 0);
+        assert!((ts.tv_sec, ts.tv_nsec) >= (prev.tv_sec, prev.tv_nsec));
+        prev = ts;
+    }
+
+    // 墙上时间来自 RTC，应该是一个合理的 Unix 时间
+    assert_eq!(clock_gettime(CLOCK_REALTIME, &mut ts), 0);
+    assert!(ts.tv_nsec < 1_000_000_000);
+    assert!(ts.tv_sec > UNIX_TIME_2020);
+    println!("clock_gettime(CLOCK_REALTIME): {}.{:09}", ts.tv_sec, ts.tv_nsec);
+
+    assert_eq!(clock_gettime(100, &mut ts), -EINVAL);
+
+    // 连续两次读取的间隔应该远小于 1 毫秒
+    let start = get_time_us();
+    let end = get_time_us();
+    println!("two consecutive reads: {} us apart", end - start);
+    assert!(end >= start);
+
+    println!("Test clock OK!");
+    0
+}
@@ -0,0 +1,50 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user;
+
+use user::{get_time, sleep, task_info, yield_, TaskInfo, TASK_STATUS_RUNNING};
+
+const SYSCALL_WRITE: usize = 64;
+const SYSCALL_NANOSLEEP: usize = 101;
+const SYSCALL_YIELD: usize = 124;
+const SYSCALL_TASK_INFO: usize = 410;
+
+// TaskInfo 比用户栈还大，所以放在 .bss 段
+static mut INFO: TaskInfo = TaskInfo::new();
+
+// 检查 task_info 统计的系统调用次数和运行时间
+#[no_mangle]
+fn main() -> i32 {
+    let info = unsafe { &mut *core::ptr::addr_of_mut!(INFO) };
+
+    for _ in 0..10 {
+        yield_();
+    }
+
+    // 在用户态忙等一段时间
+    let start = get_time();
+    while get_time() - start < 50 {}
+
+    // 睡眠的时间不应该计入运行时间
+    sleep(100);
+
+    assert_eq!(task_info(info), 0);
+    println!(
+        "user time: {} us, kernel time: {} us, total: {} us, traps: {}",
+        info.user_time, info.kernel_time, info.time, info.trap_count
+    );
+
+    assert_eq!(info.status, TASK_STATUS_RUNNING);
+    assert_eq!(info.syscall_times[SYSCALL_YIELD], 10);
+    assert_eq!(info.syscall_times[SYSCALL_NANOSLEEP], 1);
+    assert_eq!(info.syscall_times[SYSCALL_TASK_INFO], 1);
+    assert!(info.syscall_times[SYSCALL_WRITE] > 0);
+    assert_eq!(info.time, info.user_time + info.kernel_time);
+    assert!(info.time < 100_000 + 50_000);
+    assert!(info.trap_count > 10);
+
+    println!("Test task_info OK!");
+    0
+}
@@ -0,0 +1,50 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user;
+
+use user::{
+    getrlimit, setrlimit, RLimit, EINVAL, EPERM, EXIT_RLIMIT_CPU, RLIMIT_CPU, RLIM_INFINITY,
+};
+
+// 检查 getrlimit/setrlimit 的参数检查，然后把 CPU 时间上限设为 1 秒并进入死循环，
+// 内核应该终止该应用程序
+#[no_mangle]
+fn main() -> i32 {
+    let mut limit = RLimit::default();
+    assert_eq!(getrlimit(RLIMIT_CPU, &mut limit), 0);
+    println!("default RLIMIT_CPU: cur = {}, max = {:#x}", limit.rlim_cur, limit.rlim_max);
+    // 跟 Linux 一样默认没有上限
+    assert_eq!(limit.rlim_cur, RLIM_INFINITY);
+    assert_eq!(limit.rlim_max, RLIM_INFINITY);
+
+    assert_eq!(getrlimit(100, &mut limit), -EINVAL);
+
+    let invalid = RLimit {
+        rlim_cur: 2,
+        rlim_max: 1,
+    };
+    assert_eq!(setrlimit(RLIMIT_CPU, &invalid), -EINVAL);
+
+    let one_second = RLimit {
+        rlim_cur: 1,
+        rlim_max: 1,
+    };
+    assert_eq!(setrlimit(RLIMIT_CPU, &one_second), 0);
+
+    // 不能提高硬限制
+    let raised = RLimit {
+        rlim_cur: 1,
+        rlim_max: 2,
+    };
+    assert_eq!(setrlimit(RLIMIT_CPU, &raised), -EPERM);
+
+    println!(
+        "Kernel should kill this application with exit code {} in about 1 second!",
+        EXIT_RLIMIT_CPU
+    );
+    loop {
+        core::hint::spin_loop();
+    }
+}
@@ -0,0 +1,61 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user;
+
+use core::ptr::write_volatile;
+
+use user::{
+    exit, setrlimit, spawn, thread_create, waittid, RLimit, ENOMEM, EXIT_RLIMIT_RSS, RLIMIT_RSS,
+    RLIMIT_STACK, RLIM_INFINITY,
+};
+
+const PAGE_SIZE: usize = 4096;
+
+// 使用超过一页的栈空间
+extern "C" fn deep_stack(_arg: usize) -> ! {
+    let mut buf = [0u8; PAGE_SIZE + 2048];
+    unsafe {
+        write_volatile(buf.as_mut_ptr(), 1);
+    }
+    exit(buf[0] as i32);
+    unreachable!()
+}
+
+extern "C" fn idle(_arg: usize) -> ! {
+    exit(0);
+    unreachable!()
+}
+
+// 检查 RLIMIT_STACK 和 RLIMIT_RSS：
+// 用户栈只有一页的线程访问栈之外的内存时被 SIGSEGV 终止；
+// 子进程继承驻留内存上限，加载时就超出上限的 `spawn` 返回 ENOMEM；
+// 超出驻留内存上限时整个进程被内核终止
+#[no_mangle]
+fn main() -> i32 {
+    let one_page = RLimit {
+        rlim_cur: PAGE_SIZE,
+        rlim_max: RLIM_INFINITY,
+    };
+    assert_eq!(setrlimit(RLIMIT_STACK, &one_page), 0);
+
+    let tid = thread_create(deep_stack as usize, 0);
+    assert!(tid > 0, "thread_create failed: {}", tid);
+    let mut exit_code = 0;
+    assert_eq!(waittid(tid as usize, &mut exit_code), tid);
+    println!("thread with a one-page stack exited with code {}", exit_code);
+    assert_eq!(exit_code, 128 + 11); // SIGSEGV
+
+    // 之后创建的线程需要分配新的页帧
+    assert_eq!(setrlimit(RLIMIT_RSS, &one_page), 0);
+    assert_eq!(spawn("user_shell"), -ENOMEM);
+    println!(
+        "Kernel should kill this application with exit code {}!",
+        EXIT_RLIMIT_RSS
+    );
+    thread_create(idle as usize, 0);
+
+    println!("RLIMIT_RSS was not enforced!");
+    -1
+}
@@ -0,0 +1,51 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user;
+
+use user::{read, write, EBADF};
+
+const STDIN: usize = 0;
+const STDOUT: usize = 1;
+const STDERR: usize = 2;
+
+/// 检查系统调用的返回值是否为指定的值
+fn expect(name: &str, ret: isize, expected: isize) -> bool {
+    if ret == expected {
+        println!("{}: returned {} as expected", name, expected);
+        true
+    } else {
+        println!("{}: expected {}, but got {}", name, expected, ret);
+        false
+    }
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    println!("\nstdio APP running...\n");
+
+    let mut ok = true;
+
+    // 标准输出和标准错误都可以写入
+    let msg = b"hello from stdout\n";
+    ok &= expect("write to stdout", write(STDOUT, msg), msg.len() as isize);
+    let msg = b"hello from stderr\n";
+    ok &= expect("write to stderr", write(STDERR, msg), msg.len() as isize);
+
+    // 标准输入只读，标准输出和标准错误只写
+    ok &= expect("write to stdin", write(STDIN, b"x"), -EBADF);
+    let mut buf = [0u8; 4];
+    ok &= expect("read from stdout", read(STDOUT, &mut buf), -EBADF);
+    ok &= expect("read from stderr", read(STDERR, &mut buf), -EBADF);
+
+    // 没有打开的文件描述符
+    ok &= expect("read from fd 3", read(3, &mut buf), -EBADF);
+
+    if !ok {
+        return -1;
+    }
+
+    println!("Test stdio OK!");
+    0
+}
@@ -0,0 +1,86 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user;
+
+use user::{close, exit, pipe, read, thread_create, waittid, write, EBADF, EPIPE};
+
+// 生产者线程把数据写入管道后关闭写端，主线程作为检查者从读端读取，直到 EOF。
+// 数据量比管道的缓冲区大，所以两端都会经历阻塞和唤醒。
+const TOTAL: usize = 5000;
+const CHUNK: usize = 100;
+
+fn data_at(i: usize) -> u8 {
+    (i % 251) as u8
+}
+
+extern "C" fn producer(write_fd: usize) -> ! {
+    let mut buf = [0u8; CHUNK];
+    let mut sent = 0;
+    while sent < TOTAL {
+        let len = CHUNK.min(TOTAL - sent);
+        for (j, byte) in buf[..len].iter_mut().enumerate() {
+            *byte = data_at(sent + j);
+        }
+        assert_eq!(write(write_fd, &buf[..len]), len as isize);
+        sent += len;
+    }
+    assert_eq!(close(write_fd), 0);
+    exit(0);
+    unreachable!()
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    println!("\npipe APP running...\n");
+
+    let mut fds = [0u32; 2];
+    assert_eq!(pipe(&mut fds), 0);
+    let (read_fd, write_fd) = (fds[0] as usize, fds[1] as usize);
+    println!("pipe: read end {}, write end {}", read_fd, write_fd);
+
+    // 读端只读，写端只写
+    assert_eq!(write(read_fd, b"x"), -EBADF);
+    assert_eq!(read(write_fd, &mut [0u8; 1]), -EBADF);
+
+    let tid = thread_create(producer as usize, write_fd);
+    assert!(tid > 0, "thread_create failed: {}", tid);
+
+    let mut buf = [0u8; 64];
+    let mut received = 0;
+    loop {
+        let n = read(read_fd, &mut buf);
+        assert!(n >= 0, "read failed: {}", n);
+        if n == 0 {
+            break;
+        }
+        for (j, byte) in buf[..n as usize].iter().enumerate() {
+            assert_eq!(
+                *byte,
+                data_at(received + j),
+                "wrong data at {}",
+                received + j
+            );
+        }
+        received += n as usize;
+    }
+    assert_eq!(received, TOTAL);
+    let mut exit_code = -1;
+    assert_eq!(waittid(tid as usize, &mut exit_code), tid);
+    assert_eq!(exit_code, 0);
+
+    // 写端已经关闭
+    assert_eq!(close(write_fd), -EBADF);
+
+    // 读端关闭之后写入返回 EPIPE
+    let mut fds = [0u32; 2];
+    assert_eq!(pipe(&mut fds), 0);
+    assert_eq!(close(fds[0] as usize), 0);
+    assert_eq!(write(fds[1] as usize, b"x"), -EPIPE);
+    assert_eq!(close(fds[1] as usize), 0);
+
+    println!("received {} bytes", received);
+    println!("Test pipe OK!");
+    0
+}
@@ -0,0 +1,106 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user;
+
+use user::{
+    close, fstat, lseek, open, read, write, Stat, EBADF, EINVAL, ENAMETOOLONG, ENOENT, ESPIPE,
+    O_APPEND, O_CREAT, O_RDONLY, O_RDWR, O_TRUNC, O_WRONLY, SEEK_CUR, SEEK_END, SEEK_SET, S_IFCHR,
+    S_IFREG,
+};
+
+// 创建、读写、定位文件，最后把测试结果追加到磁盘上的日志文件里，
+// 关机之后可以在宿主机上查看：
+// `cargo run --release -- -i ../os/fs.img -c file_test.log`（在 ../easy-fs-fuse 目录里运行）
+const FILE: &str = "file_test.tmp";
+const LOG: &str = "/file_test.log";
+
+#[no_mangle]
+fn main() -> i32 {
+    println!("\nfile APP running...\n");
+
+    // 创建并写入
+    let data = b"Hello, easy-fs!\n";
+    let fd = open(FILE, O_CREAT | O_TRUNC | O_WRONLY);
+    assert!(fd >= 0, "open failed: {}", fd);
+    let fd = fd as usize;
+    assert_eq!(write(fd, data), data.len() as isize);
+    assert_eq!(read(fd, &mut [0u8; 1]), -EBADF);
+    assert_eq!(close(fd), 0);
+
+    // 读取，以及每个打开的文件各自的读写位置
+    let fd_a = open(FILE, O_RDONLY) as usize;
+    let fd_b = open(FILE, O_RDONLY) as usize;
+    let mut buf = [0u8; 64];
+    assert_eq!(read(fd_a, &mut buf[..5]), 5);
+    assert_eq!(&buf[..5], b"Hello");
+    assert_eq!(read(fd_b, &mut buf), data.len() as isize);
+    assert_eq!(&buf[..data.len()], data);
+    assert_eq!(read(fd_b, &mut buf), 0); // 已经到达文件末尾
+    assert_eq!(write(fd_a, b"x"), -EBADF);
+
+    // 定位
+    assert_eq!(lseek(fd_a, 2, SEEK_CUR), 7);
+    assert_eq!(read(fd_a, &mut buf[..6]), 6);
+    assert_eq!(&buf[..6], b"easy-f");
+    assert_eq!(lseek(fd_a, -3, SEEK_END), data.len() as isize - 3);
+    assert_eq!(read(fd_a, &mut buf), 3);
+    assert_eq!(&buf[..3], b"s!\n");
+    assert_eq!(lseek(fd_a, -1, SEEK_SET), -EINVAL);
+    assert_eq!(lseek(fd_a, 0, 42), -EINVAL);
+    assert_eq!(lseek(1, 0, SEEK_SET), -ESPIPE);
+
+    // 文件的状态
+    let mut st = Stat::default();
+    assert_eq!(fstat(fd_a, &mut st), 0);
+    assert_eq!(st.mode, S_IFREG);
+    assert_eq!(st.size, data.len() as u64);
+    assert_eq!(st.blocks, 1);
+    assert_eq!(fstat(1, &mut st), 0);
+    assert_eq!(st.mode, S_IFCHR);
+    assert_eq!(close(fd_a), 0);
+    assert_eq!(close(fd_b), 0);
+    assert_eq!(fstat(fd_a, &mut st), -EBADF);
+
+    // 读写同一个文件：覆盖中间的一部分，然后在末尾之后写入，中间的空隙用 0 填充
+    let fd = open(FILE, O_RDWR) as usize;
+    assert_eq!(lseek(fd, 7, SEEK_SET), 7);
+    assert_eq!(write(fd, b"EASY"), 4);
+    assert_eq!(lseek(fd, 20, SEEK_SET), 20);
+    assert_eq!(write(fd, b"!"), 1);
+    assert_eq!(lseek(fd, 0, SEEK_SET), 0);
+    assert_eq!(read(fd, &mut buf), 21);
+    assert_eq!(&buf[..21], b"Hello, EASY-fs!\n\0\0\0\0!");
+    assert_eq!(close(fd), 0);
+
+    // 错误
+    assert_eq!(open("no_such_file", O_RDONLY), -ENOENT);
+    assert_eq!(open("dir/file", O_CREAT | O_WRONLY), -ENOENT);
+    assert_eq!(
+        open("a_file_name_longer_than_27_bytes", O_CREAT | O_WRONLY),
+        -ENAMETOOLONG
+    );
+    assert_eq!(open(FILE, O_WRONLY | O_RDWR), -EINVAL);
+
+    // 截断
+    let fd = open(FILE, O_WRONLY | O_TRUNC) as usize;
+    assert_eq!(fstat(fd, &mut st), 0);
+    assert_eq!(st.size, 0);
+    assert_eq!(close(fd), 0);
+
+    // 把测试结果追加到日志文件
+    let fd = open(LOG, O_CREAT | O_WRONLY | O_APPEND);
+    assert!(fd >= 0, "open {} failed: {}", LOG, fd);
+    let fd = fd as usize;
+    assert_eq!(lseek(fd, 0, SEEK_SET), 0); // O_APPEND 时写入之前总是移到末尾
+    let line = b"file test passed\n";
+    assert_eq!(write(fd, line), line.len() as isize);
+    assert_eq!(fstat(fd, &mut st), 0);
+    assert_eq!(st.size % line.len() as u64, 0);
+    println!("{}: {} runs recorded", LOG, st.size / line.len() as u64);
+    assert_eq!(close(fd), 0);
+
+    println!("Test file OK!");
+    0
+}
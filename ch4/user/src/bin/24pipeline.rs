@@ -0,0 +1,73 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user;
+
+use user::{
+    close, dup, open, pipe, spawn, waitpid, write, EBADF, ECHILD, EFAULT, ENOENT, ENOEXEC, O_CREAT,
+    O_TRUNC, O_WRONLY,
+};
+
+// 把命令 `pipe_producer | pipe_checker` 写入管道，作为 `user_shell` 的标准输入，
+// shell 读到 EOF 后退出，它的退出码即 `pipe_checker` 的退出码。
+// 另外检查 `spawn`、`waitpid` 和 `dup` 的错误码。
+const STDIN: usize = 0;
+const SCRIPT: &[u8] = b"pipe_producer | pipe_checker\n";
+const NOT_ELF: &str = "pipeline_not_elf";
+const BAD_ELF: &str = "pipeline_bad_elf";
+
+#[no_mangle]
+fn main() -> i32 {
+    println!("\npipeline APP running...\n");
+
+    let mut exit_code = 0;
+    assert_eq!(waitpid(-1, &mut exit_code), -ECHILD);
+    assert_eq!(dup(100), -EBADF);
+    assert_eq!(spawn("no_such_app"), -ENOENT);
+
+    // 不是 ELF 文件
+    let fd = open(NOT_ELF, O_CREAT | O_TRUNC | O_WRONLY);
+    assert!(fd >= 0, "open failed: {}", fd);
+    assert_eq!(write(fd as usize, b"hello"), 5);
+    assert_eq!(close(fd as usize), 0);
+    assert_eq!(spawn(NOT_ELF), -ENOEXEC);
+
+    // 只有 ELF 的魔数，其余是无法解析的数据
+    let fd = open(BAD_ELF, O_CREAT | O_TRUNC | O_WRONLY);
+    assert!(fd >= 0, "open failed: {}", fd);
+    assert_eq!(write(fd as usize, b"\x7fELFjunk"), 8);
+    assert_eq!(close(fd as usize), 0);
+    assert_eq!(spawn(BAD_ELF), -ENOEXEC);
+
+    let mut fds = [0u32; 2];
+    assert_eq!(pipe(&mut fds), 0);
+    let (read_fd, write_fd) = (fds[0] as usize, fds[1] as usize);
+    assert_eq!(write(write_fd, SCRIPT), SCRIPT.len() as isize);
+    // 关闭写端，shell 读完命令之后才能读到 EOF
+    assert_eq!(close(write_fd), 0);
+
+    // 子进程继承打开的文件：暂时把标准输入换成管道的读端
+    let saved_stdin = dup(STDIN);
+    assert!(saved_stdin >= 0, "dup failed: {}", saved_stdin);
+    assert_eq!(close(STDIN), 0);
+    assert_eq!(dup(read_fd), STDIN as isize);
+    let pid = spawn("user_shell");
+    assert_eq!(close(STDIN), 0);
+    assert_eq!(dup(saved_stdin as usize), STDIN as isize);
+    assert_eq!(close(saved_stdin as usize), 0);
+    assert_eq!(close(read_fd), 0);
+    assert!(pid > 0, "spawn failed: {}", pid);
+
+    // 退出码无处写入时不回收子进程
+    let bad_exit_code = unsafe { &mut *(0x1000 as *mut i32) };
+    assert_eq!(waitpid(pid, bad_exit_code), -EFAULT);
+    assert_eq!(waitpid(pid, &mut exit_code), pid);
+    assert_eq!(exit_code, 0, "the pipeline failed");
+
+    // 子进程已经被回收
+    assert_eq!(waitpid(pid, &mut exit_code), -ECHILD);
+
+    println!("Test pipeline OK!");
+    0
+}
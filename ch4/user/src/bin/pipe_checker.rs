@@ -0,0 +1,86 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user;
+
+use user::{read, EINTR};
+
+// 由 `24pipeline` 通过 `user_shell` 启动，标准输入是连接 `pipe_producer` 的管道：
+// 读取到 EOF，检查是否依次收到了 `line 0` 到 `line 99` 以及 `END`。
+// 应用程序启动时会打印各个段的地址，所以忽略其他的行。
+const STDIN: usize = 0;
+const LINES: usize = 100;
+const LINE_MAX: usize = 64;
+
+struct Checker {
+    next: usize,
+    ended: bool,
+}
+
+impl Checker {
+    fn check_line(&mut self, line: &[u8]) -> bool {
+        let line = match core::str::from_utf8(line) {
+            Ok(line) => line,
+            Err(_) => return false,
+        };
+        if line == "END" {
+            self.ended = true;
+        } else if let Some(number) = line.strip_prefix("line ") {
+            if self.ended || number.parse::<usize>() != Ok(self.next) {
+                println!("expected line {}, but got \"{}\"", self.next, line);
+                return false;
+            }
+            self.next += 1;
+        }
+        true
+    }
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    let mut checker = Checker {
+        next: 0,
+        ended: false,
+    };
+    let mut line = [0u8; LINE_MAX];
+    let mut len = 0;
+    let mut buf = [0u8; 32];
+
+    loop {
+        let n = match read(STDIN, &mut buf) {
+            n if n == -EINTR => continue,
+            n if n < 0 => {
+                println!("read failed: {}", n);
+                return -1;
+            }
+            0 => break,
+            n => n as usize,
+        };
+        for &byte in &buf[..n] {
+            if byte == b'\n' {
+                if !checker.check_line(&line[..len]) {
+                    return -1;
+                }
+                len = 0;
+            } else if len < LINE_MAX {
+                line[len] = byte;
+                len += 1;
+            } else {
+                println!("line too long");
+                return -1;
+            }
+        }
+    }
+
+    if len > 0 || !checker.ended || checker.next != LINES {
+        println!(
+            "incomplete input: {} lines, END {}",
+            checker.next,
+            if checker.ended { "received" } else { "missing" }
+        );
+        return -1;
+    }
+    println!("pipe_checker: received {} lines", checker.next);
+    0
+}
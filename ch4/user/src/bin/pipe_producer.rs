@@ -0,0 +1,19 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user;
+
+// 由 `24pipeline` 通过 `user_shell` 启动，标准输出是连接 `pipe_checker` 的管道：
+// 依次输出 `line 0` 到 `line 99`，最后输出 `END`。
+// 数据量比管道的缓冲区大，所以两端都会经历阻塞和唤醒。
+const LINES: usize = 100;
+
+#[no_mangle]
+fn main() -> i32 {
+    for i in 0..LINES {
+        println!("line {}", i);
+    }
+    println!("END");
+    0
+}
@@ -0,0 +1,195 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user;
+
+use user::{close, dup, pipe, read, spawn, waitpid, write, EINTR, ENOENT};
+
+// 一个简单的 shell：每行是一个命令或者用 `|` 连接的多个命令（比如 `pipe_producer | pipe_checker`），
+// 命令即根目录里的应用程序的名称，不支持参数。输入 `exit` 或者标准输入结束（EOF）时退出，
+// 退出码为最后一个命令的退出码。
+//
+// 子进程继承 shell 打开的文件，所以在 `spawn` 之前先用 `dup` 和 `close` 把标准输入输出换成管道，
+// `spawn` 之后再换回来。
+const STDIN: usize = 0;
+const STDOUT: usize = 1;
+
+const LF: u8 = b'\n';
+const CR: u8 = b'\r';
+const BS: u8 = 0x08;
+const DEL: u8 = 0x7f;
+
+const LINE_MAX: usize = 256;
+const MAX_COMMANDS: usize = 8;
+
+/// 读取一行（不包括换行符），同时回显输入的字符；标准输入结束并且没有读到任何字符时返回 None
+fn read_line(line: &mut [u8; LINE_MAX]) -> Option<usize> {
+    let mut len = 0;
+    loop {
+        let mut c = [0u8; 1];
+        match read(STDIN, &mut c) {
+            1 => {}
+            n if n == -EINTR => continue,
+            _ => return if len == 0 { None } else { Some(len) },
+        }
+
+        match c[0] {
+            LF | CR => {
+                println!("");
+                return Some(len);
+            }
+            BS | DEL => {
+                if len > 0 {
+                    len -= 1;
+                    print!("{} {}", BS as char, BS as char);
+                }
+            }
+            c if len < LINE_MAX => {
+                line[len] = c;
+                len += 1;
+                write(STDOUT, &[c]);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// 把文件描述符 `target` 换成 `fd` 指向的文件，返回保存原来的文件的描述符
+fn redirect(target: usize, fd: usize) -> usize {
+    let saved = dup(target);
+    assert!(saved >= 0, "dup failed: {}", saved);
+    close(target);
+    // `target` 是编号最小的空闲描述符
+    assert_eq!(dup(fd), target as isize);
+    saved as usize
+}
+
+/// 恢复 `redirect` 之前的文件
+fn restore(target: usize, saved: usize) {
+    close(target);
+    assert_eq!(dup(saved), target as isize);
+    close(saved);
+}
+
+/// 创建子进程运行 `name`，`stdin` 和 `stdout` 不为 None 时作为子进程的标准输入输出
+fn spawn_redirected(name: &str, stdin: Option<usize>, stdout: Option<usize>) -> isize {
+    let saved_stdin = stdin.map(|fd| redirect(STDIN, fd));
+    let saved_stdout = stdout.map(|fd| redirect(STDOUT, fd));
+    let pid = spawn(name);
+    // 先恢复标准输出，保证提示信息能显示出来
+    if let Some(saved) = saved_stdout {
+        restore(STDOUT, saved);
+    }
+    if let Some(saved) = saved_stdin {
+        restore(STDIN, saved);
+    }
+    pid
+}
+
+/// 运行用 `|` 连接的命令，返回最后一个命令的退出码
+fn run_pipeline(commands: &[&str]) -> i32 {
+    let mut pids = [0isize; MAX_COMMANDS];
+    let mut prev_read = None;
+
+    for (i, name) in commands.iter().enumerate() {
+        let next = if i + 1 < commands.len() {
+            let mut fds = [0u32; 2];
+            assert_eq!(pipe(&mut fds), 0);
+            Some((fds[0] as usize, fds[1] as usize))
+        } else {
+            None
+        };
+
+        pids[i] = spawn_redirected(name, prev_read, next.map(|(_, write_fd)| write_fd));
+        if pids[i] == -ENOENT {
+            println!("{}: command not found", name);
+        } else if pids[i] < 0 {
+            println!("{}: spawn failed: {}", name, pids[i]);
+        }
+
+        // 子进程已经继承了管道，shell 自己要关闭它们，否则读端永远等不到 EOF
+        if let Some(read_fd) = prev_read {
+            close(read_fd);
+        }
+        prev_read = next.map(|(read_fd, write_fd)| {
+            close(write_fd);
+            read_fd
+        });
+    }
+
+    let mut last_exit_code = -1;
+    for (name, &pid) in commands.iter().zip(pids.iter()) {
+        if pid < 0 {
+            last_exit_code = -1;
+            continue;
+        }
+        let mut exit_code = 0;
+        let ret = loop {
+            match waitpid(pid, &mut exit_code) {
+                ret if ret == -EINTR => continue,
+                ret => break ret,
+            }
+        };
+        assert_eq!(ret, pid, "waitpid failed");
+        if exit_code != 0 {
+            println!("{} (pid {}) exited with code {}", name, pid, exit_code);
+        }
+        last_exit_code = exit_code;
+    }
+    last_exit_code
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    let mut buf = [0u8; LINE_MAX];
+    let mut exit_code = 0;
+
+    loop {
+        print!(">> ");
+        let len = match read_line(&mut buf) {
+            Some(len) => len,
+            None => {
+                println!("");
+                break;
+            }
+        };
+        let line = match core::str::from_utf8(&buf[..len]) {
+            Ok(line) => line,
+            Err(_) => {
+                println!("invalid UTF-8 input");
+                continue;
+            }
+        };
+
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        if line == "exit" {
+            break;
+        }
+
+        let mut commands = [""; MAX_COMMANDS];
+        let mut count = 0;
+        for command in line.split('|') {
+            if count == MAX_COMMANDS {
+                count += 1;
+                break;
+            }
+            commands[count] = command.trim();
+            count += 1;
+        }
+        if count > MAX_COMMANDS {
+            println!("too many commands (at most {})", MAX_COMMANDS);
+            continue;
+        }
+        if commands[..count].iter().any(|command| command.is_empty()) {
+            println!("syntax error near `|`");
+            continue;
+        }
+
+        exit_code = run_pipeline(&commands[..count]);
+    }
+    exit_code
+}
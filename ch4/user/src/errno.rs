@@ -0,0 +1,23 @@
+// 系统调用的错误码，数值跟 Linux 一致
+//
+// 系统调用失败时返回错误码的相反数，比如 `write` 返回 `-EBADF`。
+
+pub const EPERM: isize = 1;
+pub const ENOENT: isize = 2;
+pub const ESRCH: isize = 3;
+pub const EINTR: isize = 4;
+pub const EIO: isize = 5;
+pub const ENOEXEC: isize = 8;
+pub const EBADF: isize = 9;
+pub const ECHILD: isize = 10;
+pub const EAGAIN: isize = 11;
+pub const ENOMEM: isize = 12;
+pub const EFAULT: isize = 14;
+pub const ENOTDIR: isize = 20;
+pub const EINVAL: isize = 22;
+pub const ENOSPC: isize = 28;
+pub const ESPIPE: isize = 29;
+pub const EPIPE: isize = 32;
+pub const EDEADLK: isize = 35;
+pub const ENAMETOOLONG: isize = 36;
+pub const ENOSYS: isize = 38;
@@ -0,0 +1,38 @@
+// 文件相关的常量和数据结构，数值跟 Linux 一致（见内核的 `fs.rs` 和 `fs/inode.rs`）
+
+// `open` 的 flags
+pub const O_RDONLY: u32 = 0;
+pub const O_WRONLY: u32 = 1 << 0;
+pub const O_RDWR: u32 = 1 << 1;
+pub const O_CREAT: u32 = 1 << 6;
+pub const O_TRUNC: u32 = 1 << 9;
+pub const O_APPEND: u32 = 1 << 10;
+
+// `openat` 的 dirfd，表示相对路径从当前目录开始
+pub const AT_FDCWD: isize = -100;
+
+// 路径的最大长度，包括末尾的 `\0`
+pub const PATH_MAX: usize = 256;
+
+// `lseek` 的 whence
+pub const SEEK_SET: usize = 0;
+pub const SEEK_CUR: usize = 1;
+pub const SEEK_END: usize = 2;
+
+// 文件类型
+pub const S_IFIFO: u32 = 0o010000;
+pub const S_IFCHR: u32 = 0o020000;
+pub const S_IFDIR: u32 = 0o040000;
+pub const S_IFREG: u32 = 0o100000;
+
+/// 文件的状态，布局需要跟内核保持一致
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Stat {
+    pub dev: u64,    // 文件所在的设备
+    pub ino: u64,    // inode 编号
+    pub mode: u32,   // 文件类型
+    pub nlink: u32,  // 硬链接的数量
+    pub size: u64,   // 文件的大小（bytes）
+    pub blocks: u64, // 占用的块数，包括索引块
+}
@@ -0,0 +1,71 @@
+// 基于 futex 的用户态互斥锁
+//
+// 锁的状态保存在一个 u32 里：
+// - 0：未加锁
+// - 1：已加锁，没有等待者
+// - 2：已加锁，可能有等待者
+//
+// 没有争用时，加锁和解锁都只是一次原子操作，不需要系统调用；
+// 只有加锁失败时才通过 `FUTEX_WAIT` 进入内核等待，解锁时如果可能有等待者才通过 `FUTEX_WAKE` 唤醒。
+//
+// https://www.akkadia.org/drepper/futex.pdf
+
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use crate::syscall::sys_futex;
+
+pub const FUTEX_WAIT: usize = 0;
+pub const FUTEX_WAKE: usize = 1;
+
+const UNLOCKED: u32 = 0;
+const LOCKED: u32 = 1;
+const CONTENDED: u32 = 2;
+
+/// 如果 `futex` 的值等于 `val` 则阻塞，直到被 `futex_wake` 唤醒
+pub fn futex_wait(futex: &AtomicU32, val: u32) -> isize {
+    sys_futex(futex as *const AtomicU32 as *const u32, FUTEX_WAIT, val)
+}
+
+/// 唤醒最多 `count` 个在 `futex` 上等待的线程
+pub fn futex_wake(futex: &AtomicU32, count: u32) -> isize {
+    sys_futex(futex as *const AtomicU32 as *const u32, FUTEX_WAKE, count)
+}
+
+pub struct FutexMutex {
+    state: AtomicU32,
+}
+
+impl FutexMutex {
+    pub const fn new() -> Self {
+        Self {
+            state: AtomicU32::new(UNLOCKED),
+        }
+    }
+
+    pub fn lock(&self) {
+        if self
+            .state
+            .compare_exchange(UNLOCKED, LOCKED, Ordering::Acquire, Ordering::Relaxed)
+            .is_ok()
+        {
+            return;
+        }
+
+        // 发生了争用：把状态设置为 CONTENDED，让持有锁的线程解锁时唤醒等待者
+        while self.state.swap(CONTENDED, Ordering::Acquire) != UNLOCKED {
+            futex_wait(&self.state, CONTENDED);
+        }
+    }
+
+    pub fn unlock(&self) {
+        if self.state.swap(UNLOCKED, Ordering::Release) == CONTENDED {
+            futex_wake(&self.state, 1);
+        }
+    }
+}
+
+impl Default for FutexMutex {
+    fn default() -> Self {
+        Self::new()
+    }
+}
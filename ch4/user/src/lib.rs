@@ -5,10 +5,32 @@
 #[macro_use]
 pub mod console;
 
+mod errno;
+mod fs;
+mod futex;
 mod lang_items;
+mod rlimit;
+mod signal;
 mod syscall;
+mod task;
+mod time;
 
-use syscall::{sys_exit, sys_get_time, sys_write, sys_yield};
+pub use errno::*;
+pub use fs::*;
+pub use futex::*;
+pub use rlimit::*;
+pub use signal::*;
+pub use task::*;
+pub use time::*;
+
+use syscall::{
+    sys_clock_gettime, sys_close, sys_condvar_create, sys_condvar_signal, sys_condvar_wait,
+    sys_dup, sys_exit, sys_fstat, sys_getpid, sys_getrlimit, sys_gettid, sys_gettimeofday,
+    sys_kill, sys_lseek, sys_mutex_create, sys_mutex_lock, sys_mutex_unlock, sys_nanosleep,
+    sys_openat, sys_pipe, sys_read, sys_semaphore_create, sys_semaphore_down, sys_semaphore_up,
+    sys_setrlimit, sys_sigaction, sys_sigprocmask, sys_sigreturn, sys_spawn, sys_task_info,
+    sys_thread_create, sys_waitpid, sys_waittid, sys_write, sys_yield,
+};
 
 #[no_mangle]
 #[link_section = ".text.entry"]
@@ -46,10 +68,48 @@ fn print_section_info() {
     println!("----------");
 }
 
+pub fn read(fd: usize, buf: &mut [u8]) -> isize {
+    sys_read(fd, buf)
+}
+
 pub fn write(fd: usize, buf: &[u8]) -> isize {
     sys_write(fd, buf)
 }
 
+/// 打开文件，`flags` 为 `O_RDONLY`、`O_CREAT` 等的组合，返回文件描述符
+pub fn open(path: &str, flags: u32) -> isize {
+    // 系统调用需要以 `\0` 结尾的路径
+    let mut buf = [0u8; PATH_MAX];
+    if path.len() >= PATH_MAX {
+        return -ENAMETOOLONG;
+    }
+    buf[..path.len()].copy_from_slice(path.as_bytes());
+    sys_openat(AT_FDCWD, buf.as_ptr(), flags, 0)
+}
+
+/// 复制文件描述符 `fd`，返回编号最小的空闲描述符
+pub fn dup(fd: usize) -> isize {
+    sys_dup(fd)
+}
+
+pub fn close(fd: usize) -> isize {
+    sys_close(fd)
+}
+
+/// 创建一个管道，`fds[0]` 为读端，`fds[1]` 为写端
+pub fn pipe(fds: &mut [u32; 2]) -> isize {
+    sys_pipe(fds as *mut [u32; 2])
+}
+
+/// 移动文件的读写位置，`whence` 为 `SEEK_SET`、`SEEK_CUR` 或者 `SEEK_END`，返回新的位置
+pub fn lseek(fd: usize, offset: isize, whence: usize) -> isize {
+    sys_lseek(fd, offset, whence)
+}
+
+pub fn fstat(fd: usize, st: &mut Stat) -> isize {
+    sys_fstat(fd, st as *mut Stat)
+}
+
 pub fn exit(exit_code: i32) -> isize {
     sys_exit(exit_code)
 }
@@ -58,6 +118,170 @@ pub fn yield_() -> isize {
     sys_yield()
 }
 
+pub fn gettimeofday(tv: &mut TimeVal) -> isize {
+    sys_gettimeofday(tv as *mut TimeVal)
+}
+
+pub fn clock_gettime(clock_id: usize, tp: &mut TimeSpec) -> isize {
+    sys_clock_gettime(clock_id, tp as *mut TimeSpec)
+}
+
+/// 从启动开始经过的时间，单位为毫秒
 pub fn get_time() -> isize {
-    sys_get_time()
+    get_time_us() / 1000
+}
+
+/// 从启动开始经过的时间，单位为微秒
+pub fn get_time_us() -> isize {
+    let mut tv = TimeVal::default();
+    match gettimeofday(&mut tv) {
+        0 => (tv.sec * 1_000_000 + tv.usec) as isize,
+        err => err,
+    }
+}
+
+/// 睡眠 `req` 指定的时间，被信号打断时返回 `-EINTR`，剩余的时间被写入 `rem`
+pub fn nanosleep(req: &TimeSpec, rem: Option<&mut TimeSpec>) -> isize {
+    sys_nanosleep(
+        req as *const TimeSpec,
+        rem.map_or(core::ptr::null_mut(), |rem| rem as *mut TimeSpec),
+    )
+}
+
+/// 睡眠 `ms` 毫秒
+pub fn sleep(ms: usize) -> isize {
+    nanosleep(&TimeSpec::from_ms(ms), None)
+}
+
+pub fn getpid() -> isize {
+    sys_getpid()
+}
+
+/// 创建子进程运行应用程序 `path`，返回子进程的 pid
+///
+/// 子进程继承当前进程打开的文件，见 `user_shell` 怎样把标准输入输出换成管道。
+pub fn spawn(path: &str) -> isize {
+    let mut buf = [0u8; PATH_MAX];
+    if path.len() >= PATH_MAX {
+        return -ENAMETOOLONG;
+    }
+    buf[..path.len()].copy_from_slice(path.as_bytes());
+    sys_spawn(buf.as_ptr())
+}
+
+/// 等待子进程 `pid`（为 -1 时表示任意一个子进程）结束，成功时返回它的 pid，
+/// 并把退出码写入 `exit_code`
+pub fn waitpid(pid: isize, exit_code: &mut i32) -> isize {
+    sys_waitpid(pid, exit_code as *mut i32)
+}
+
+pub fn kill(pid: usize, signum: i32) -> isize {
+    sys_kill(pid, signum)
+}
+
+/// 设置信号的处理方式，`action.restorer` 会被自动设置为用户库的 `sigreturn` 跳板
+pub fn sigaction(
+    signum: i32,
+    action: Option<&SignalAction>,
+    old_action: Option<&mut SignalAction>,
+) -> isize {
+    let action = action.map(|action| SignalAction {
+        restorer: sigreturn_trampoline as usize,
+        ..*action
+    });
+
+    sys_sigaction(
+        signum,
+        action
+            .as_ref()
+            .map_or(core::ptr::null(), |action| action as *const SignalAction),
+        old_action.map_or(core::ptr::null_mut(), |action| action as *mut SignalAction),
+    )
+}
+
+pub fn sigprocmask(mask: u32) -> isize {
+    sys_sigprocmask(mask)
+}
+
+pub fn sigreturn() -> isize {
+    sys_sigreturn()
+}
+
+/// 信号处理函数返回之后会跳转到这里，由它通知内核恢复投递信号之前的上下文
+extern "C" fn sigreturn_trampoline() -> ! {
+    sys_sigreturn();
+    panic!("unreachable after sys_sigreturn!");
+}
+
+/// 获取当前进程对资源 `resource` 的限制
+pub fn getrlimit(resource: usize, rlim: &mut RLimit) -> isize {
+    sys_getrlimit(resource, rlim)
+}
+
+/// 设置当前进程对资源 `resource` 的限制，只能降低硬限制，不能提高它
+pub fn setrlimit(resource: usize, rlim: &RLimit) -> isize {
+    sys_setrlimit(resource, rlim)
+}
+
+/// 获取当前任务的状态、运行时间以及各个系统调用的次数
+///
+/// `TaskInfo` 有 4KB 多，注意不要把它放在用户栈上。
+pub fn task_info(info: &mut TaskInfo) -> isize {
+    sys_task_info(info)
+}
+
+/// 创建一个线程，从 `entry` 开始运行，`arg` 作为它的参数，返回新线程的 tid
+///
+/// 线程函数不能返回，结束时需要调用 `exit`（只结束该线程；主线程调用 `exit` 则结束整个进程）。
+pub fn thread_create(entry: usize, arg: usize) -> isize {
+    sys_thread_create(entry, arg)
+}
+
+pub fn gettid() -> isize {
+    sys_gettid()
+}
+
+/// 等待线程 `tid` 退出，成功时返回 `tid`，并把线程的退出码写入 `exit_code`
+pub fn waittid(tid: usize, exit_code: &mut i32) -> isize {
+    sys_waittid(tid, exit_code as *mut i32)
+}
+
+/// 创建一个互斥锁，返回它的 id
+pub fn mutex_create() -> isize {
+    sys_mutex_create()
+}
+
+pub fn mutex_lock(mutex_id: usize) -> isize {
+    sys_mutex_lock(mutex_id)
+}
+
+pub fn mutex_unlock(mutex_id: usize) -> isize {
+    sys_mutex_unlock(mutex_id)
+}
+
+/// 创建一个初始计数为 `count` 的信号量，返回它的 id
+pub fn semaphore_create(count: usize) -> isize {
+    sys_semaphore_create(count)
+}
+
+pub fn semaphore_up(sem_id: usize) -> isize {
+    sys_semaphore_up(sem_id)
+}
+
+pub fn semaphore_down(sem_id: usize) -> isize {
+    sys_semaphore_down(sem_id)
+}
+
+/// 创建一个条件变量，返回它的 id
+pub fn condvar_create() -> isize {
+    sys_condvar_create()
+}
+
+pub fn condvar_signal(condvar_id: usize) -> isize {
+    sys_condvar_signal(condvar_id)
+}
+
+/// 释放互斥锁并等待条件变量，返回之前重新获取互斥锁。返回之后条件不一定成立，需要循环检查
+pub fn condvar_wait(condvar_id: usize, mutex_id: usize) -> isize {
+    sys_condvar_wait(condvar_id, mutex_id)
 }
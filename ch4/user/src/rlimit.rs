@@ -0,0 +1,18 @@
+// 资源限制，跟 Linux 一致（见内核的 `task/rlimit.rs`）
+
+pub const RLIMIT_CPU: usize = 0; // CPU 时间（秒）
+pub const RLIMIT_STACK: usize = 3; // 新线程的用户栈大小（字节）
+pub const RLIMIT_RSS: usize = 5; // 驻留内存的大小（字节）
+
+pub const RLIM_INFINITY: usize = usize::MAX;
+
+// 超出资源限制的进程被内核终止时的退出码
+pub const EXIT_RLIMIT_CPU: i32 = 152;
+pub const EXIT_RLIMIT_RSS: i32 = 160;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct RLimit {
+    pub rlim_cur: usize, // 软限制，即实际生效的上限
+    pub rlim_max: usize, // 硬限制，即软限制所能设置的最大值
+}
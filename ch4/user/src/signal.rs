@@ -0,0 +1,47 @@
+// 信号的编号跟 Linux（RISC-V）一致
+
+pub const SIG_DFL: usize = 0; // 默认的处理方式
+pub const SIG_IGN: usize = 1; // 忽略该信号
+
+pub const SIGHUP: i32 = 1;
+pub const SIGINT: i32 = 2;
+pub const SIGQUIT: i32 = 3;
+pub const SIGILL: i32 = 4;
+pub const SIGTRAP: i32 = 5;
+pub const SIGABRT: i32 = 6;
+pub const SIGBUS: i32 = 7;
+pub const SIGFPE: i32 = 8;
+pub const SIGKILL: i32 = 9;
+pub const SIGUSR1: i32 = 10;
+pub const SIGSEGV: i32 = 11;
+pub const SIGUSR2: i32 = 12;
+pub const SIGPIPE: i32 = 13;
+pub const SIGALRM: i32 = 14;
+pub const SIGTERM: i32 = 15;
+pub const SIGCHLD: i32 = 17;
+pub const SIGCONT: i32 = 18;
+pub const SIGSTOP: i32 = 19;
+
+/// 信号 `signum` 在信号集合（比如 `sigprocmask` 的参数）里对应的 bit
+pub fn sigmask(signum: i32) -> u32 {
+    1 << signum
+}
+
+/// 信号的处理方式，布局需要跟内核保持一致
+#[derive(Copy, Clone)]
+#[repr(C)]
+pub struct SignalAction {
+    pub handler: usize,  // 处理函数 `fn(signum: i32)` 的地址，或者 SIG_DFL/SIG_IGN
+    pub mask: u32,       // 执行处理函数期间额外屏蔽的信号
+    pub restorer: usize, // 由 `sigaction` 自动设置
+}
+
+impl Default for SignalAction {
+    fn default() -> Self {
+        Self {
+            handler: SIG_DFL,
+            mask: 0,
+            restorer: 0,
+        }
+    }
+}
@@ -0,0 +1,31 @@
+// 任务信息，跟内核的 `TaskInfo` 一致
+
+// 系统调用号的上限（不含）
+pub const MAX_SYSCALL_NUM: usize = 1040;
+
+// TaskInfo.status
+pub const TASK_STATUS_RUNNING: usize = 2;
+
+/// `task_info` 返回的任务信息，时间的单位都是微秒
+#[repr(C)]
+pub struct TaskInfo {
+    pub status: usize,
+    pub user_time: usize,
+    pub kernel_time: usize,
+    pub time: usize, // 总运行时间，即 user_time + kernel_time
+    pub trap_count: usize,
+    pub syscall_times: [u32; MAX_SYSCALL_NUM],
+}
+
+impl TaskInfo {
+    pub const fn new() -> Self {
+        Self {
+            status: 0,
+            user_time: 0,
+            kernel_time: 0,
+            time: 0,
+            trap_count: 0,
+            syscall_times: [0; MAX_SYSCALL_NUM],
+        }
+    }
+}
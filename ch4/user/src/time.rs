@@ -0,0 +1,30 @@
+// 时间相关的数据结构，跟 Linux 一致
+
+// 时钟，用于 `clock_gettime`
+pub const CLOCK_REALTIME: usize = 0; // Unix 时间
+pub const CLOCK_MONOTONIC: usize = 1; // 从启动开始经过的时间
+
+/// 跟 Linux 的 `struct timeval` 一致
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct TimeVal {
+    pub sec: usize,
+    pub usec: usize,
+}
+
+/// 跟 Linux 的 `struct timespec` 一致
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct TimeSpec {
+    pub tv_sec: usize,
+    pub tv_nsec: usize,
+}
+
+impl TimeSpec {
+    pub fn from_ms(ms: usize) -> Self {
+        Self {
+            tv_sec: ms / 1000,
+            tv_nsec: ms % 1000 * 1_000_000,
+        }
+    }
+}
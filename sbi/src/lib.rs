@@ -0,0 +1,292 @@
+//! 各章的内核共用的 SBI（RISC-V Supervisor Binary Interface）调用接口
+//!
+//! 按照 SBI v0.2 及之后的规范调用各个扩展：a7 为扩展编号（EID），a6 为函数编号（FID），
+//! 参数放在 a0~a5，返回时 a0 为错误码，a1 为返回值（即 `SbiRet`）。
+//!
+//! 每个扩展在第一次使用时通过 BASE 扩展的 `probe_extension` 探测固件是否支持，
+//! 不支持时（比如只实现了 v0.1 的旧固件）退回到旧版（legacy）的调用方式。
+//!
+//! https://github.com/riscv-non-isa/riscv-sbi-doc/blob/master/riscv-sbi.adoc
+
+#![no_std]
+
+use core::{
+    arch::asm,
+    sync::atomic::{AtomicU8, Ordering},
+};
+
+// 旧版扩展，每个函数占用一个 EID，只通过 a0 返回结果
+// https://github.com/riscv-non-isa/riscv-sbi-doc/blob/master/riscv-sbi.adoc#legacy-extensions-eids-0x00-0x0f
+const LEGACY_SET_TIMER: usize = 0;
+const LEGACY_CONSOLE_PUTCHAR: usize = 1;
+const LEGACY_CONSOLE_GETCHAR: usize = 2;
+const LEGACY_SEND_IPI: usize = 4;
+const LEGACY_REMOTE_FENCE_I: usize = 5;
+const LEGACY_REMOTE_SFENCE_VMA: usize = 6;
+const LEGACY_SHUTDOWN: usize = 8;
+
+// https://github.com/riscv-non-isa/riscv-sbi-doc/blob/master/riscv-sbi.adoc#base-extension-eid-0x10
+const EXT_BASE: usize = 0x10;
+const BASE_GET_SPEC_VERSION: usize = 0;
+const BASE_GET_IMPL_ID: usize = 1;
+const BASE_GET_IMPL_VERSION: usize = 2;
+const BASE_PROBE_EXTENSION: usize = 3;
+
+// https://github.com/riscv-non-isa/riscv-sbi-doc/blob/master/riscv-sbi.adoc#timer-extension-eid-0x54494d45-time
+pub const EXT_TIME: usize = 0x54494D45;
+const TIME_SET_TIMER: usize = 0;
+
+// https://github.com/riscv-non-isa/riscv-sbi-doc/blob/master/riscv-sbi.adoc#ipi-extension-eid-0x735049-spi-s-mode-ipi
+pub const EXT_IPI: usize = 0x735049;
+const IPI_SEND_IPI: usize = 0;
+
+// https://github.com/riscv-non-isa/riscv-sbi-doc/blob/master/riscv-sbi.adoc#rfence-extension-eid-0x52464e43-rfnc
+pub const EXT_RFENCE: usize = 0x52464E43;
+const RFENCE_REMOTE_FENCE_I: usize = 0;
+const RFENCE_REMOTE_SFENCE_VMA: usize = 1;
+
+// https://github.com/riscv-non-isa/riscv-sbi-doc/blob/master/riscv-sbi.adoc#hart-state-management-extension-eid-0x48534d-hsm
+pub const EXT_HSM: usize = 0x48534D;
+const HSM_HART_START: usize = 0;
+const HSM_HART_STOP: usize = 1;
+const HSM_HART_GET_STATUS: usize = 2;
+
+// https://github.com/riscv-non-isa/riscv-sbi-doc/blob/master/riscv-sbi.adoc#system-reset-extension-eid-0x53525354-srst
+pub const EXT_SRST: usize = 0x53525354;
+const SRST_SYSTEM_RESET: usize = 0;
+
+// `system_reset` 的复位类型和原因
+pub const RESET_TYPE_SHUTDOWN: usize = 0;
+pub const RESET_TYPE_COLD_REBOOT: usize = 1;
+pub const RESET_TYPE_WARM_REBOOT: usize = 2;
+pub const RESET_REASON_NO_REASON: usize = 0;
+pub const RESET_REASON_SYSTEM_FAILURE: usize = 1;
+
+// 错误码
+pub const SBI_SUCCESS: isize = 0;
+pub const SBI_ERR_FAILED: isize = -1;
+pub const SBI_ERR_NOT_SUPPORTED: isize = -2;
+pub const SBI_ERR_INVALID_PARAM: isize = -3;
+pub const SBI_ERR_DENIED: isize = -4;
+pub const SBI_ERR_INVALID_ADDRESS: isize = -5;
+pub const SBI_ERR_ALREADY_AVAILABLE: isize = -6;
+pub const SBI_ERR_ALREADY_STARTED: isize = -7;
+pub const SBI_ERR_ALREADY_STOPPED: isize = -8;
+
+/// SBI 调用的返回值
+#[derive(Copy, Clone, Debug)]
+pub struct SbiRet {
+    pub error: isize, // 错误码，SBI_SUCCESS 表示成功
+    pub value: usize, // 返回值，只在成功时有意义
+}
+
+impl SbiRet {
+    pub fn is_ok(&self) -> bool {
+        self.error == SBI_SUCCESS
+    }
+}
+
+/// 新版的调用方式
+#[inline(always)]
+fn sbi_call(
+    eid: usize,
+    fid: usize,
+    arg0: usize,
+    arg1: usize,
+    arg2: usize,
+    arg3: usize,
+) -> SbiRet {
+    let (error, value);
+    unsafe {
+        asm!(
+            "ecall",
+            inlateout("x10") arg0 => error,
+            inlateout("x11") arg1 => value,
+            in("x12") arg2,
+            in("x13") arg3,
+            in("x16") fid,
+            in("x17") eid,
+        )
+    }
+    SbiRet { error, value }
+}
+
+/// 旧版的调用方式：a7 为 EID，a6 不使用（置 0），只有 a0 是返回值
+#[inline(always)]
+fn sbi_call_legacy(eid: usize, arg0: usize, arg1: usize, arg2: usize) -> usize {
+    let mut ret;
+    unsafe {
+        asm!(
+            "ecall",
+            inlateout("x10") arg0 => ret,
+            in("x11") arg1,
+            in("x12") arg2,
+            in("x16") 0usize,
+            in("x17") eid,
+        )
+    }
+    ret
+}
+
+// Base 扩展（所有 v0.2 及之后的固件都必须实现）
+
+/// SBI 规范的版本：bit[30:24] 为主版本号，bit[23:0] 为次版本号
+pub fn get_spec_version() -> SbiRet {
+    sbi_call(EXT_BASE, BASE_GET_SPEC_VERSION, 0, 0, 0, 0)
+}
+
+/// SBI 实现的编号，比如 0 为 BBL、1 为 OpenSBI、4 为 RustSBI
+pub fn get_impl_id() -> SbiRet {
+    sbi_call(EXT_BASE, BASE_GET_IMPL_ID, 0, 0, 0, 0)
+}
+
+pub fn get_impl_version() -> SbiRet {
+    sbi_call(EXT_BASE, BASE_GET_IMPL_VERSION, 0, 0, 0, 0)
+}
+
+/// 固件是否支持扩展 `eid`
+///
+/// 只实现了 v0.1 的固件没有 Base 扩展，调用会返回错误，此时视为不支持。
+pub fn probe_extension(eid: usize) -> bool {
+    let ret = sbi_call(EXT_BASE, BASE_PROBE_EXTENSION, eid, 0, 0, 0);
+    ret.is_ok() && ret.value != 0
+}
+
+// 探测结果的缓存，避免每次调用都先探测一次
+const UNKNOWN: u8 = 0;
+const AVAILABLE: u8 = 1;
+const UNAVAILABLE: u8 = 2;
+
+static TIME_STATE: AtomicU8 = AtomicU8::new(UNKNOWN);
+static IPI_STATE: AtomicU8 = AtomicU8::new(UNKNOWN);
+static RFENCE_STATE: AtomicU8 = AtomicU8::new(UNKNOWN);
+static SRST_STATE: AtomicU8 = AtomicU8::new(UNKNOWN);
+
+/// 多个 hart 同时探测也没有关系，结果都是一样的
+fn has_extension(state: &AtomicU8, eid: usize) -> bool {
+    match state.load(Ordering::Relaxed) {
+        AVAILABLE => true,
+        UNAVAILABLE => false,
+        _ => {
+            let available = probe_extension(eid);
+            let new_state = if available { AVAILABLE } else { UNAVAILABLE };
+            state.store(new_state, Ordering::Relaxed);
+            available
+        }
+    }
+}
+
+// 控制台（只有旧版扩展，新版的 DBCN 扩展要求通过物理地址传递缓冲区）
+
+/// use sbi call to putchar in console (qemu uart handler)
+pub fn console_putchar(c: usize) {
+    sbi_call_legacy(LEGACY_CONSOLE_PUTCHAR, c, 0, 0);
+}
+
+/// use sbi call to getchar from console (qemu uart handler)，没有输入时返回 usize::MAX（即 -1）
+pub fn console_getchar() -> usize {
+    sbi_call_legacy(LEGACY_CONSOLE_GETCHAR, 0, 0, 0)
+}
+
+// Timer 扩展
+
+/// 设置下一次时钟中断的时间（`time` 的值），同时清除当前的时钟中断
+pub fn set_timer(stime_value: usize) {
+    if has_extension(&TIME_STATE, EXT_TIME) {
+        sbi_call(EXT_TIME, TIME_SET_TIMER, stime_value, 0, 0, 0);
+    } else {
+        sbi_call_legacy(LEGACY_SET_TIMER, stime_value, 0, 0);
+    }
+}
+
+// IPI 扩展
+//
+// 新版接口用 (hart_mask, hart_mask_base) 表示一组 hart：第 i 个 bit 对应 hart (hart_mask_base + i)，
+// 这里固定 hart_mask_base 为 0，即最多支持 64 个 hart；旧版接口则通过指针传递 hart mask。
+
+/// 向 `hart_mask` 指定的 hart 发送软件中断
+pub fn send_ipi(hart_mask: usize) -> SbiRet {
+    if has_extension(&IPI_STATE, EXT_IPI) {
+        sbi_call(EXT_IPI, IPI_SEND_IPI, hart_mask, 0, 0, 0)
+    } else {
+        legacy_ret(sbi_call_legacy(LEGACY_SEND_IPI, &hart_mask as *const usize as usize, 0, 0))
+    }
+}
+
+// RFENCE 扩展
+
+/// 让 `hart_mask` 指定的 hart 执行 `fence.i`
+pub fn remote_fence_i(hart_mask: usize) -> SbiRet {
+    if has_extension(&RFENCE_STATE, EXT_RFENCE) {
+        sbi_call(EXT_RFENCE, RFENCE_REMOTE_FENCE_I, hart_mask, 0, 0, 0)
+    } else {
+        legacy_ret(sbi_call_legacy(
+            LEGACY_REMOTE_FENCE_I,
+            &hart_mask as *const usize as usize,
+            0,
+            0,
+        ))
+    }
+}
+
+/// 让 `hart_mask` 指定的 hart 执行 `sfence.vma`，刷新虚拟地址范围 [start, start + size) 的 TLB 项
+pub fn remote_sfence_vma(hart_mask: usize, start: usize, size: usize) -> SbiRet {
+    if has_extension(&RFENCE_STATE, EXT_RFENCE) {
+        sbi_call(EXT_RFENCE, RFENCE_REMOTE_SFENCE_VMA, hart_mask, 0, start, size)
+    } else {
+        legacy_ret(sbi_call_legacy(
+            LEGACY_REMOTE_SFENCE_VMA,
+            &hart_mask as *const usize as usize,
+            start,
+            size,
+        ))
+    }
+}
+
+/// 旧版接口的返回值只有错误码
+fn legacy_ret(error: usize) -> SbiRet {
+    SbiRet {
+        error: error as isize,
+        value: 0,
+    }
+}
+
+// HSM（Hart State Management）扩展，没有对应的旧版接口
+
+// `hart_get_status` 返回的状态
+pub const HART_STATE_STARTED: usize = 0;
+pub const HART_STATE_STOPPED: usize = 1;
+pub const HART_STATE_START_PENDING: usize = 2;
+pub const HART_STATE_STOP_PENDING: usize = 3;
+
+/// 启动 hart `hart_id`，它将以 S 态从物理地址 `start_addr` 开始运行，
+/// 此时 a0 为 hart id，a1 为 `opaque`
+pub fn hart_start(hart_id: usize, start_addr: usize, opaque: usize) -> SbiRet {
+    sbi_call(EXT_HSM, HSM_HART_START, hart_id, start_addr, opaque, 0)
+}
+
+/// 停止当前 hart，成功时不会返回
+pub fn hart_stop() -> SbiRet {
+    sbi_call(EXT_HSM, HSM_HART_STOP, 0, 0, 0, 0)
+}
+
+/// 查询 hart `hart_id` 的状态（`HART_STATE_*`）
+pub fn hart_get_status(hart_id: usize) -> SbiRet {
+    sbi_call(EXT_HSM, HSM_HART_GET_STATUS, hart_id, 0, 0, 0)
+}
+
+// System Reset 扩展
+
+/// 关机或者重启（`RESET_TYPE_*`），成功时不会返回
+pub fn system_reset(reset_type: usize, reset_reason: usize) -> SbiRet {
+    sbi_call(EXT_SRST, SRST_SYSTEM_RESET, reset_type, reset_reason, 0, 0)
+}
+
+/// 关机：优先使用 SRST 扩展，不支持时使用旧版的 `SBI_SHUTDOWN`
+pub fn shutdown() -> ! {
+    if has_extension(&SRST_STATE, EXT_SRST) {
+        system_reset(RESET_TYPE_SHUTDOWN, RESET_REASON_NO_REASON);
+    }
+    sbi_call_legacy(LEGACY_SHUTDOWN, 0, 0, 0);
+    panic!("It should shutdown!");
+}